@@ -0,0 +1,105 @@
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::{Mutex, MutexGuard};
+use crate::consts::{FAULTS_MSG, GET_TRIP_MSG, LOG_MSG, SHUTDOWN_MSG, START_ELECTION_MSG, STATUS_JSON_MSG, STATUS_MSG, TRANSFER_LEADER_MSG, TRIP_MSG};
+use crate::metrics::MESSAGES_REFUSED;
+use crate::process::Process;
+use crate::utils::tcp::get_peer_addr;
+
+// * Lista de direcciones permitidas: solo las IPs de la lista de procesos pueden hablarle al nodo como otro nodo.
+// * Desde cualquier otra direccion se atienden unicamente los mensajes de cliente configurados con --client-messages
+// * (por defecto, crear y consultar trips en el puerto de trabajo); el resto se rechaza con FORBIDDEN.
+// * La lista solo mira la IP, no autentica a nadie: sin --cluster-key cualquier proceso de una maquina de la lista (en
+// * un cluster local, cualquiera en 127.0.0.1) puede mandar SHUTDOWN, TRANSFER LEADER, FAULTS o LOG. Con clave, todo
+// * mensaje al puerto de control tiene que venir firmado, y los de administracion necesitan concurride-ctl --cluster-key.
+
+const NONE: &str = "none";
+
+// ? mensajes que envian los clientes (como concurride-ctl) y no los nodos entre si
+const CLIENT_MESSAGE_TYPES: [&str; 9] = [TRIP_MSG, GET_TRIP_MSG, STATUS_MSG, STATUS_JSON_MSG, START_ELECTION_MSG, TRANSFER_LEADER_MSG, FAULTS_MSG, LOG_MSG, SHUTDOWN_MSG];
+const DEFAULT_CLIENT_MESSAGES: [&str; 2] = [TRIP_MSG, GET_TRIP_MSG];
+
+// ? None mientras no se configure: rigen los mensajes por defecto
+static CLIENT_MESSAGES: Mutex<Option<Vec<&'static str>>> = Mutex::new(None);
+
+fn client_messages() -> MutexGuard<'static, Option<Vec<&'static str>>> {
+    match CLIENT_MESSAGES.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Configura los mensajes que se atienden desde direcciones fuera de la lista, por ejemplo `TRIP,GET TRIP,STATUS`,
+/// o `none` para no atender ninguno. Devuelve la configuracion resultante.
+///
+/// # Errors
+/// Devuelve el motivo si algun tipo no es un mensaje de cliente.
+pub(crate) fn configure(spec: &str) -> Result<String, String> {
+    let mut allowed = Vec::new();
+    if spec.trim() != NONE {
+        for name in spec.split(',').map(str::trim) {
+            match CLIENT_MESSAGE_TYPES.iter().find(|message_type| **message_type == name) {
+                Some(message_type) => allowed.push(*message_type),
+                None => return Err(format!("{} no es un mensaje de cliente, debe ser uno de: {}", name, CLIENT_MESSAGE_TYPES.join(", "))),
+            }
+        }
+    }
+
+    let description = if allowed.is_empty() { NONE.to_string() } else { allowed.join(",") };
+    *client_messages() = Some(allowed);
+    Ok(description)
+}
+
+// ? el tipo mas largo que coincide, para que "STATUS JSON" no cuente como "STATUS"
+fn client_message_type(message: &str) -> Option<&'static str> {
+    CLIENT_MESSAGE_TYPES.iter()
+        .filter(|message_type| message == **message_type || message.starts_with(&format!("{} ", message_type)))
+        .max_by_key(|message_type| message_type.len())
+        .copied()
+}
+
+/// Si `message` es de un tipo que se atiende aunque venga de fuera de la lista de procesos.
+pub(crate) fn is_client_message(message: &str) -> bool {
+    let message_type = match client_message_type(message) {
+        Some(message_type) => message_type,
+        None => return false,
+    };
+
+    match client_messages().as_ref() {
+        Some(allowed) => allowed.contains(&message_type),
+        None => DEFAULT_CLIENT_MESSAGES.contains(&message_type),
+    }
+}
+
+// ? una IPv4 puede llegar como IPv6 mapeada (::ffff:a.b.c.d) si se escucha en todas las interfaces
+fn peer_ip(stream: &TcpStream) -> Result<IpAddr, String> {
+    let addr = get_peer_addr(stream)?;
+    match addr.parse::<SocketAddr>() {
+        Ok(addr) => Ok(addr.ip().to_canonical()),
+        Err(e) => Err(format!("dirección inválida {}: {}", addr, e)),
+    }
+}
+
+/// Si la conexion viene de alguna de las IPs de la lista de procesos. Los hosts se resuelven con la cache de cada proceso.
+///
+/// Cualquier proceso de esa IP pasa: solo la firma con la clave del cluster prueba que el mensaje viene de un nodo.
+pub(crate) fn is_listed(stream: &TcpStream, processes: &[Process]) -> bool {
+    let ip = match peer_ip(stream) {
+        Ok(ip) => ip,
+        Err(e) => {
+            warn!("No se pudo verificar la dirección de la conexión: {}", e);
+            return false;
+        }
+    };
+
+    processes.iter().any(|process| match process.addr.socket_addrs() {
+        Ok(addrs) => addrs.iter().any(|addr| addr.ip().to_canonical() == ip),
+        Err(_) => false,
+    })
+}
+
+/// Registra que se rechazo `what` de `stream` por venir de fuera de la lista de procesos.
+pub(crate) fn record_refused(stream: &TcpStream, what: &str) {
+    MESSAGES_REFUSED.increment();
+    let peer = get_peer_addr(stream).unwrap_or_else(|e| e);
+    warn!("Rechazando {} de {}: la dirección no está en la lista de procesos", what, peer);
+}
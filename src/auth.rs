@@ -0,0 +1,132 @@
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeSet;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use crate::consts::AUTH_MSG;
+use crate::metrics::{MESSAGES_REPLAYED, MESSAGES_UNAUTHENTICATED};
+use crate::utils::hmac::{self, Envelope};
+
+// * Autenticacion de los mensajes entre nodos con una clave compartida (--cluster-key). Cada mensaje viaja firmado con
+// * HMAC-SHA256 junto al id del nodo destino, un timestamp y un nonce: uno sin firma valida no se atiende, y uno
+// * repetido, con un timestamp fuera de la ventana o dirigido a otro nodo tampoco, para que no se pueda grabar un
+// * NEW LEADER y reenviarlo despues, ni al mismo nodo ni a otro. Sin clave configurada los mensajes viajan y se aceptan
+// * sin firma, como siempre.
+// * Las respuestas NO se autentican: viajan sin firma por la misma conexion del pedido. Quien pueda escribir en esa
+// * conexion puede cambiar un OK, un VOTE GRANTED o un trip devuelto; la clave solo evita que se acepten pedidos falsos.
+
+// ? diferencia maxima aceptada entre el timestamp del mensaje y nuestro reloj
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+// ? nonces recordados a lo sumo; al pasarse se olvidan los mas viejos y se rechaza todo lo que no sea mas nuevo que ellos
+const MAX_SEEN_NONCES: usize = 16384;
+
+struct Auth {
+    // ? id de este nodo: solo se aceptan los mensajes firmados para el
+    id: u32,
+    key: Vec<u8>,
+    // ? mensajes firmados por este nodo: ningun nonce se repite mientras el proceso siga vivo
+    sealed: u64,
+    // ? claves al azar del sistema operativo: la parte impredecible del nonce, distinta en cada ejecucion
+    nonce_keys: RandomState,
+    // ? nonces aceptados ordenados por timestamp; pasada la ventana el timestamp ya alcanza para rechazarlos
+    seen: BTreeSet<(u64, String)>,
+    // ? timestamp del nonce mas nuevo que se olvido por falta de lugar
+    forgotten: Option<u64>,
+}
+
+impl Auth {
+    // ? contador del nodo, para que no se repita, y un valor que no se puede adivinar a partir de los anteriores
+    fn next_nonce(&mut self) -> String {
+        self.sealed += 1;
+        format!("{:016x}{:016x}", self.sealed, self.nonce_keys.hash_one(self.sealed))
+    }
+
+    // ? recuerda el nonce si no se vio antes. La cantidad de nonces recordados tiene un tope.
+    fn remember(&mut self, timestamp: u64, nonce: &str, now: u64, window: u64) -> bool {
+        let expired = now.saturating_sub(window);
+        self.seen = self.seen.split_off(&(expired, String::new()));
+        if self.forgotten.is_some_and(|forgotten| timestamp <= forgotten) || !self.seen.insert((timestamp, nonce.to_string())) {
+            return false;
+        }
+        while self.seen.len() > MAX_SEEN_NONCES {
+            self.forgotten = self.seen.pop_first().map(|(timestamp, _)| timestamp).max(self.forgotten);
+        }
+        true
+    }
+}
+
+static AUTH: Mutex<Option<Auth>> = Mutex::new(None);
+
+fn auth() -> MutexGuard<'static, Option<Auth>> {
+    match AUTH.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+pub(crate) fn configure(id: u32, key: Vec<u8>) {
+    *auth() = Some(Auth { id, key, sealed: 0, nonce_keys: RandomState::new(), seen: BTreeSet::new(), forgotten: None });
+}
+
+/// Si `message` viene en un sobre firmado, valido o no.
+pub(crate) fn is_sealed(message: &str) -> bool {
+    message.starts_with(&format!("{} ", AUTH_MSG))
+}
+
+/// Firma `message` para enviarlo al nodo `to`. Sin clave lo devuelve tal cual.
+pub(crate) fn seal(to: u32, message: &str) -> String {
+    match auth().as_mut() {
+        Some(auth) => {
+            let nonce = auth.next_nonce();
+            hmac::seal(&auth.key, to, hmac::now_millis(), &nonce, message)
+        }
+        None => message.to_string(),
+    }
+}
+
+/// Devuelve el mensaje de un sobre autentico que no se haya recibido antes. Sin clave, acepta los mensajes sin firmar.
+///
+/// # Errors
+/// Devuelve el motivo del rechazo. El mensaje rechazado ya queda contado en las metricas.
+pub(crate) fn open(message: &str) -> Result<String, String> {
+    let mut guard = auth();
+    let auth = match guard.as_mut() {
+        Some(auth) => auth,
+        None if is_sealed(message) => {
+            MESSAGES_UNAUTHENTICATED.increment();
+            return Err("mensaje firmado pero este nodo no tiene clave de cluster".to_string());
+        }
+        None => return Ok(message.to_string()),
+    };
+
+    let envelope = match Envelope::parse(message) {
+        Some(envelope) if envelope.is_authentic(&auth.key) => envelope,
+        Some(_) => {
+            MESSAGES_UNAUTHENTICATED.increment();
+            return Err("firma inválida".to_string());
+        }
+        None => {
+            MESSAGES_UNAUTHENTICATED.increment();
+            return Err("mensaje sin firmar".to_string());
+        }
+    };
+
+    // ? un sobre autentico para otro nodo solo puede haber sido copiado de otra conexion
+    if envelope.to != auth.id {
+        MESSAGES_REPLAYED.increment();
+        return Err(format!("mensaje dirigido al nodo {}", envelope.to));
+    }
+
+    let now = hmac::now_millis();
+    let window = MAX_CLOCK_SKEW.as_millis() as u64;
+    if envelope.timestamp.abs_diff(now) > window {
+        MESSAGES_REPLAYED.increment();
+        return Err(format!("timestamp fuera de la ventana de {:?}", MAX_CLOCK_SKEW));
+    }
+
+    if !auth.remember(envelope.timestamp, envelope.nonce, now, window) {
+        MESSAGES_REPLAYED.increment();
+        return Err(format!("nonce repetido: {}", envelope.nonce));
+    }
+    Ok(envelope.payload.to_string())
+}
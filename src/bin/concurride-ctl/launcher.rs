@@ -0,0 +1,167 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use crate::process::Process;
+
+// * Modo launcher: levanta un nodo por cada entrada del archivo de procesos como proceso hijo,
+// * mezcla sus logs con un prefijo y permite matarlos y reiniciarlos para probar la caida del lider.
+
+const NODE_BINARY: &str = "ConcurrideServer";
+const HELP: &str = "Comandos: list | kill <id> | start <id> | restart <id> | quit";
+
+struct Node {
+    id: u32,
+    port: u16,
+    child: Option<Child>,
+}
+
+// ? el binario del nodo se compila junto al del cliente
+fn node_binary() -> PathBuf {
+    match env::current_exe() {
+        Ok(exe) => exe.with_file_name(NODE_BINARY),
+        Err(e) => {
+            eprintln!("Error: no se pudo ubicar el binario del nodo: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? reenvia cada linea de la salida del nodo con su prefijo
+fn forward_output(id: u32, output: impl Read + Send + 'static) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(output);
+        let mut line = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut line), Ok(read) if read > 0) {
+            print!("[nodo {}] {}", id, String::from_utf8_lossy(&line));
+            if !line.ends_with(b"\n") {
+                println!();
+            }
+            line.clear();
+        }
+    });
+}
+
+impl Node {
+    fn is_running(&mut self) -> bool {
+        match self.child.as_mut().map(Child::try_wait) {
+            Some(Ok(None)) => true,
+            Some(Ok(Some(_))) | Some(Err(_)) => {
+                self.child = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn start(&mut self, file: &str, node_args: &[String]) {
+        if self.is_running() {
+            println!("[launcher] El nodo {} ya está corriendo", self.id);
+            return;
+        }
+
+        let spawned = Command::new(node_binary())
+            .arg(self.id.to_string())
+            .arg(self.port.to_string())
+            .arg(file)
+            .args(node_args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        match spawned {
+            Ok(mut child) => {
+                if let Some(stdout) = child.stdout.take() {
+                    forward_output(self.id, stdout);
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    forward_output(self.id, stderr);
+                }
+                println!("[launcher] Nodo {} iniciado (pid {})", self.id, child.id());
+                self.child = Some(child);
+            }
+            Err(e) => eprintln!("[launcher] No se pudo iniciar el nodo {}: {}", self.id, e),
+        }
+    }
+
+    // ? mata al nodo sin darle tiempo a renunciar, como una caida real
+    fn kill(&mut self) {
+        if !self.is_running() {
+            println!("[launcher] El nodo {} no está corriendo", self.id);
+            return;
+        }
+
+        if let Some(mut child) = self.child.take() {
+            if let Err(e) = child.kill() {
+                eprintln!("[launcher] No se pudo matar al nodo {}: {}", self.id, e);
+            }
+            let _ = child.wait();
+            println!("[launcher] Nodo {} detenido", self.id);
+        }
+    }
+}
+
+fn find_node(nodes: &[Node], id: &str) -> Option<usize> {
+    let index = id.parse::<u32>().ok().and_then(|id| nodes.iter().position(|node| node.id == id));
+    if index.is_none() {
+        println!("[launcher] No hay un nodo con id {}", id);
+    }
+    index
+}
+
+/// Levanta todos los nodos del archivo y atiende comandos por la entrada estandar hasta `quit`.
+///
+/// `node_args` se pasan tal cual a cada nodo (por ejemplo `--election raft`).
+pub(crate) fn launch(processes: &[Process], file: &str, node_args: &[String]) {
+    let mut nodes: Vec<Node> = processes.iter().map(|process| Node { id: process.id, port: process.addr.port, child: None }).collect();
+    for node in nodes.iter_mut() {
+        node.start(file, node_args);
+    }
+    println!("[launcher] {}", HELP);
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let command: Vec<&str> = line.split_whitespace().collect();
+
+        match command.as_slice() {
+            [] => {}
+            ["list"] => {
+                for node in nodes.iter_mut() {
+                    let state = if node.is_running() { "corriendo" } else { "detenido" };
+                    println!("[launcher] Nodo {} (puerto {}): {}", node.id, node.port, state);
+                }
+            }
+            ["kill", id] => {
+                if let Some(index) = find_node(&nodes, id) {
+                    nodes[index].kill();
+                }
+            }
+            ["start", id] => {
+                if let Some(index) = find_node(&nodes, id) {
+                    nodes[index].start(file, node_args);
+                }
+            }
+            ["restart", id] => {
+                if let Some(index) = find_node(&nodes, id) {
+                    if nodes[index].is_running() {
+                        nodes[index].kill();
+                    }
+                    nodes[index].start(file, node_args);
+                }
+            }
+            ["quit"] | ["exit"] => break,
+            _ => println!("[launcher] {}", HELP),
+        }
+        let _ = io::stdout().flush();
+    }
+
+    for node in nodes.iter_mut().filter(|node| node.child.is_some()) {
+        node.kill();
+    }
+}
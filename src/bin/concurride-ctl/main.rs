@@ -0,0 +1,276 @@
+// * Cliente de linea de comandos para administrar los nodos.
+// ? habla el mismo protocolo de texto que los nodos: un mensaje por conexion y una respuesta.
+
+#[path = "../../consts.rs"]
+#[allow(dead_code)]
+mod consts;
+#[path = "../../process.rs"]
+#[allow(dead_code)]
+mod process;
+#[path = "../../utils/file_handler.rs"]
+#[allow(dead_code)]
+mod file_handler;
+#[path = "../../utils/json.rs"]
+#[allow(dead_code)]
+mod json;
+#[path = "../../utils/peer_addr.rs"]
+#[allow(dead_code)]
+mod peer_addr;
+#[path = "../../utils/peers_file.rs"]
+mod peers_file;
+#[path = "../../utils/journal_file.rs"]
+#[allow(dead_code)]
+mod journal_file;
+#[path = "../../utils/hmac.rs"]
+#[allow(dead_code)]
+mod hmac;
+mod launcher;
+// ? los modulos compartidos se referencian como crate::utils::...
+mod utils {
+    pub(crate) use super::{json, peer_addr};
+}
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::BuildHasher;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use consts::{NOT_LEADER_ANSWER, SHUTDOWN_MSG, START_ELECTION_MSG, STATUS_JSON_MSG, STATUS_MSG, TRANSFER_LEADER_MSG, TRIP_MSG, FAULTS_MSG, LOG_MSG};
+use process::Process;
+use json::JsonValue;
+use peer_addr::PeerAddr;
+use peers_file::load_processes_or_kill_process;
+
+const USAGE: &str = "Uso: concurride-ctl [--file <archivo>] [--node <id>] [--cluster-key <archivo>] <comando>
+
+Comandos:
+  status [--json]          estado del nodo
+  leader                   lider actual
+  members                  procesos del cluster y su estado
+  transfer-leader [<id>]   traspasa el liderazgo (por defecto, al mejor nodo elegible)
+  trigger-election         pide una eleccion de lider
+  submit-trip <desc>       crea un trip en el lider
+  shutdown <id>            apaga un nodo
+  faults [<fallas>|off]    muestra o cambia las fallas inyectadas (ej: drop=10,latency=200,corrupt=5,blackhole=2+3)
+  log [level <niveles>|format human|json]
+                           muestra o cambia el log (ej: level info,election=debug)
+  journal <archivo>...     une los journals de varios nodos (--journal) en una linea de tiempo
+  launch [args del nodo]   levanta un nodo por cada proceso del archivo y permite matarlos y reiniciarlos";
+
+const DEFAULT_FILE: &str = "servers.csv";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// ? clave del cluster, si los nodos corren con --cluster-key: todos los mensajes se firman con ella
+static CLUSTER_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+// ? mensajes firmados y claves al azar del sistema operativo: el nonce no se repite ni se puede adivinar
+static SEALED: AtomicU64 = AtomicU64::new(0);
+static NONCE_KEYS: OnceLock<RandomState> = OnceLock::new();
+
+struct Options {
+    file: String,
+    node: Option<u32>,
+    command: Vec<String>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    exit(1);
+}
+
+fn parse_options() -> Options {
+    let mut args = env::args().skip(1);
+    let mut options = Options { file: DEFAULT_FILE.to_string(), node: None, command: Vec::new() };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" => options.file = args.next().unwrap_or_else(|| fail("falta el archivo de --file")),
+            "--node" => options.node = match args.next().map(|id| id.parse()) {
+                Some(Ok(id)) => Some(id),
+                _ => fail("--node debe ser un id de proceso"),
+            },
+            "--cluster-key" => match args.next() {
+                Some(file) => {
+                    let _ = CLUSTER_KEY.set(hmac::load_key(Path::new(&file)).unwrap_or_else(|e| fail(&e)));
+                }
+                None => fail("falta el archivo de --cluster-key"),
+            },
+            _ => {
+                options.command.push(arg);
+                options.command.extend(args.by_ref());
+            }
+        }
+    }
+
+    if options.command.is_empty() {
+        eprintln!("{}", USAGE);
+        exit(1);
+    }
+    options
+}
+
+fn seal(to: u32, message: &str) -> String {
+    match CLUSTER_KEY.get() {
+        Some(key) => {
+            let sealed = SEALED.fetch_add(1, Ordering::Relaxed) + 1;
+            let nonce = format!("{:016x}{:016x}", sealed, NONCE_KEYS.get_or_init(RandomState::new).hash_one(sealed));
+            hmac::seal(key, to, hmac::now_millis(), &nonce, message)
+        }
+        None => message.to_string(),
+    }
+}
+
+// ? `to` es el id del nodo que escucha en `addr`: la firma solo vale para el
+fn send(to: u32, addr: &PeerAddr, message: &str) -> Result<String, String> {
+    let message = seal(to, message);
+    let socket_addrs = addr.socket_addrs()?;
+    let mut last_error = format!("{} no tiene direcciones", addr);
+
+    for socket_addr in socket_addrs {
+        let mut stream = match TcpStream::connect_timeout(&socket_addr, TIMEOUT) {
+            Ok(stream) => stream,
+            Err(e) => {
+                last_error = format!("no se pudo conectar a {}: {}", addr, e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_read_timeout(Some(TIMEOUT)) {
+            return Err(format!("no se pudo configurar el timeout: {}", e));
+        }
+        stream.write_all(message.as_bytes()).map_err(|e| format!("no se pudo enviar a {}: {}", addr, e))?;
+
+        // ? el nodo cierra la conexion despues de responder
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).map_err(|e| format!("no se pudo leer la respuesta de {}: {}", addr, e))?;
+        return Ok(answer);
+    }
+
+    Err(last_error)
+}
+
+fn find_process(processes: &[Process], id: u32) -> &Process {
+    processes.iter().find(|process| process.id == id).unwrap_or_else(|| fail(&format!("no hay un proceso con id {}", id)))
+}
+
+// ? nodo al que se le pregunta: el indicado con --node o el primero que responda
+fn ask(processes: &[Process], node: Option<u32>, message: &str) -> String {
+    if let Some(id) = node {
+        return send(id, &find_process(processes, id).addr, message).unwrap_or_else(|e| fail(&e));
+    }
+
+    for process in processes {
+        match send(process.id, &process.addr, message) {
+            Ok(answer) => return answer,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    fail("ningún nodo respondió")
+}
+
+fn status(processes: &[Process], node: Option<u32>) -> JsonValue {
+    let answer = ask(processes, node, STATUS_JSON_MSG);
+    json::parse(&answer).unwrap_or_else(|e| fail(&format!("respuesta de estado inválida: {}", e)))
+}
+
+fn leader_id(processes: &[Process], node: Option<u32>) -> u32 {
+    match status(processes, node).get("leader").and_then(JsonValue::as_u64) {
+        Some(id) => id as u32,
+        None => fail("el nodo no conoce a ningún líder"),
+    }
+}
+
+fn print_members(status: &JsonValue) {
+    println!("{:<6} {:<28} {:<10} {:<8} CONEXION", "ID", "DIRECCION", "ROL", "ESTADO");
+    for process in status.get("processes").and_then(JsonValue::as_array).into_iter().flatten() {
+        println!(
+            "{:<6} {:<28} {:<10} {:<8} {}",
+            process.get("id").and_then(JsonValue::as_u64).map_or("-".to_string(), |id| id.to_string()),
+            process.get("addr").and_then(JsonValue::as_str).unwrap_or("-"),
+            process.get("role").and_then(JsonValue::as_str).unwrap_or("-"),
+            process.get("liveness").and_then(JsonValue::as_str).unwrap_or("-"),
+            process.get("connection").and_then(JsonValue::as_str).unwrap_or("-"),
+        );
+    }
+}
+
+// ? los trips los atiende el puerto de trabajo del lider. Si el nodo responde NOT LEADER, se reintenta una vez con el indicado.
+fn submit_trip(processes: &[Process], node: Option<u32>, description: &str) -> String {
+    let mut leader = leader_id(processes, node);
+    for _ in 0..2 {
+        let process = find_process(processes, leader);
+        let work_port = process.work_port.unwrap_or_else(|| fail(&format!("el líder {} no tiene puerto de trabajo", leader)));
+        let answer = send(process.id, &PeerAddr::new(process.addr.host.clone(), work_port), &format!("{} {}", TRIP_MSG, description)).unwrap_or_else(|e| fail(&e));
+
+        match answer.strip_prefix(NOT_LEADER_ANSWER).and_then(|id| id.trim().parse().ok()) {
+            Some(new_leader) => leader = new_leader,
+            None => return answer,
+        }
+    }
+    fail("el liderazgo cambió mientras se enviaba el trip")
+}
+
+// ? los journals se leen de disco: no hace falta que los nodos esten vivos
+fn print_timeline(files: &[String]) {
+    if files.is_empty() {
+        fail("journal necesita al menos un archivo");
+    }
+
+    let journals = files.iter()
+        .map(|file| journal_file::read_journal(Path::new(file)).unwrap_or_else(|e| fail(&e)))
+        .collect();
+
+    for entry in journal_file::merge(journals) {
+        println!("{} nodo={} term={} {}: {}", entry.ts, entry.node, entry.term, entry.event, entry.detail);
+    }
+}
+
+fn main() {
+    let options = parse_options();
+    if options.command[0] == "journal" {
+        print_timeline(&options.command[1..]);
+        return;
+    }
+    let processes = load_processes_or_kill_process(Path::new(&options.file));
+    let command: Vec<&str> = options.command.iter().map(String::as_str).collect();
+
+    let output = match command.as_slice() {
+        ["launch", node_args @ ..] => {
+            let node_args: Vec<String> = node_args.iter().map(|arg| arg.to_string()).collect();
+            launcher::launch(&processes, &options.file, &node_args);
+            return;
+        }
+        ["status"] => ask(&processes, options.node, STATUS_MSG),
+        ["status", "--json"] => ask(&processes, options.node, STATUS_JSON_MSG),
+        ["leader"] => leader_id(&processes, options.node).to_string(),
+        ["members"] => {
+            print_members(&status(&processes, options.node));
+            return;
+        }
+        ["transfer-leader", target @ ..] if target.len() <= 1 => {
+            let leader = find_process(&processes, leader_id(&processes, options.node));
+            let message = format!("{} {}", TRANSFER_LEADER_MSG, target.first().unwrap_or(&"")).trim().to_string();
+            send(leader.id, &leader.addr, &message).unwrap_or_else(|e| fail(&e))
+        }
+        ["trigger-election"] => ask(&processes, options.node, START_ELECTION_MSG),
+        ["submit-trip", description @ ..] if !description.is_empty() => submit_trip(&processes, options.node, &description.join(" ")),
+        ["faults"] => ask(&processes, options.node, FAULTS_MSG),
+        ["faults", spec] => ask(&processes, options.node, &format!("{} {}", FAULTS_MSG, spec)),
+        ["log"] => ask(&processes, options.node, LOG_MSG),
+        ["log", "level", spec] => ask(&processes, options.node, &format!("{} LEVEL {}", LOG_MSG, spec)),
+        ["log", "format", format] => ask(&processes, options.node, &format!("{} FORMAT {}", LOG_MSG, format)),
+        ["shutdown", id] => {
+            let id = id.parse().unwrap_or_else(|_| fail("el id debe ser un número"));
+            send(id, &find_process(&processes, id).addr, SHUTDOWN_MSG).unwrap_or_else(|e| fail(&e))
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    };
+
+    println!("{}", output.trim_end());
+}
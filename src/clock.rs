@@ -0,0 +1,30 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Fuente de tiempo del nodo. Los nodos reales usan el reloj del sistema; la simulacion usa un reloj virtual.
+pub(crate) trait Clock: Send + Sync {
+    // ? tiempo transcurrido desde que arranco el reloj
+    fn now(&self) -> Duration;
+
+    fn sleep(&self, duration: Duration);
+}
+
+pub(crate) struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub(crate) fn new() -> SystemClock {
+        SystemClock { started: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::auth;
+use crate::consts::{SESSION_ANSWER, SESSION_MSG};
+use crate::process::Process;
+use crate::utils::faults;
+use crate::utils::random::Rng;
+use crate::utils::tcp::{get_peer_connection_timeout, read_frame, write_bytes_to_stream, write_frame};
+
+// * Conexiones persistentes con los otros nodos, para no pagar un handshake TCP por cada heartbeat o mensaje de
+// * eleccion. Con cada nodo se abre una sesion (SESSION) por la que viajan frames con un id de correlacion: varios
+// * hilos pueden tener pedidos en vuelo sobre la misma conexion y cada respuesta vuelve a quien la espera.
+// * Si no se puede conectar, se reintenta con backoff exponencial y jitter; mientras tanto los mensajes fallan enseguida.
+
+// ? maximo para conectar y pedir la sesion; un pedido con un timeout menor usa el suyo
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+// ? el listener cierra las sesiones inactivas: las cerramos antes nosotros para no escribir en una que se esta cerrando
+pub(crate) const IDLE_TIMEOUT: Duration = Duration::from_secs(20);
+// ? espera antes de reintentar la conexion: se duplica con cada falla hasta el maximo
+const BACKOFF_BASE: Duration = Duration::from_millis(100);
+const BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+// ? pedidos en vuelo de una sesion, por id de correlacion
+type Pending = Arc<Mutex<HashMap<u64, Sender<String>>>>;
+
+struct Session {
+    id: u64,
+    writer: TcpStream,
+    pending: Pending,
+    last_used: Instant,
+}
+
+enum Link {
+    // ? nunca se conecto o la sesion se cerro: el proximo mensaje conecta enseguida
+    Idle,
+    Connected(Session),
+    // ? fallo la conexion: no se reintenta hasta `retry_at`
+    Backoff { failures: u32, retry_at: Instant },
+}
+
+static LINKS: Mutex<BTreeMap<u32, Arc<Mutex<Link>>>> = Mutex::new(BTreeMap::new());
+// ? ids de correlacion y de sesion, unicos en todo el nodo
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static JITTER: Mutex<Option<Rng>> = Mutex::new(None);
+// ? estado de las sesiones que abre el runtime event-loop, que no pasan por LINKS
+static REPORTED: Mutex<BTreeMap<u32, &'static str>> = Mutex::new(BTreeMap::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn link_for(peer: u32) -> Arc<Mutex<Link>> {
+    Arc::clone(lock(&LINKS).entry(peer).or_insert_with(|| Arc::new(Mutex::new(Link::Idle))))
+}
+
+// ? se elige al azar entre la mitad y el total de la espera, para que los nodos no reintenten todos a la vez
+pub(crate) fn backoff(failures: u32) -> Duration {
+    let max = BACKOFF_BASE.saturating_mul(1 << failures.saturating_sub(1).min(16)).min(BACKOFF_MAX).as_millis() as u64;
+    let mut jitter = lock(&JITTER);
+    Duration::from_millis(jitter.get_or_insert_with(Rng::from_entropy).range(max / 2, max + 1))
+}
+
+// ? conecta, pide la sesion y deja un hilo leyendo las respuestas. `timeout` acota la conexion y el pedido de sesion.
+fn connect(to: &Process, link: &Arc<Mutex<Link>>, timeout: Duration) -> Result<Session, String> {
+    let mut stream = get_peer_connection_timeout(&to.addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .map_err(|e| format!("Error al configurar timeout en la conexión: {}", e))?;
+    write_bytes_to_stream(&mut stream, format!("{}\n", SESSION_MSG).as_bytes())?;
+
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| format!("Error al duplicar la conexión: {}", e))?);
+    let mut answer = String::new();
+    reader.read_line(&mut answer).map_err(|e| format!("{} no respondió el pedido de sesión: {}", to.id, e))?;
+    if answer.trim_end() != SESSION_ANSWER {
+        return Err(format!("{} no aceptó la sesión: {}", to.id, answer.trim_end()));
+    }
+    stream.set_read_timeout(None).map_err(|e| format!("Error al configurar timeout en la conexión: {}", e))?;
+
+    let session = Session { id: NEXT_ID.fetch_add(1, Ordering::SeqCst), writer: stream, pending: Pending::default(), last_used: Instant::now() };
+    let (peer, session_id, pending, link) = (to.id, session.id, Arc::clone(&session.pending), Arc::clone(link));
+    thread::spawn(move || read_answers(peer, session_id, reader, pending, link));
+
+    debug!("Sesión abierta con {}", to.id);
+    Ok(session)
+}
+
+// ? entrega cada respuesta a quien la espera. Al cerrarse la conexion, los pedidos en vuelo fallan.
+fn read_answers(peer: u32, session_id: u64, mut reader: BufReader<TcpStream>, pending: Pending, link: Arc<Mutex<Link>>) {
+    let reason = loop {
+        match read_frame(&mut reader) {
+            // ? si nadie la espera (un mensaje sin respuesta o un pedido que ya vencio) se descarta
+            Ok(Some((id, answer))) => if let Some(tx) = lock(&pending).remove(&id) {
+                let _ = tx.send(answer);
+            },
+            Ok(None) => break "el otro nodo cerró la conexión".to_string(),
+            Err(e) => break e,
+        }
+    };
+    debug!("Sesión con {} cerrada: {}", peer, reason);
+
+    let mut link = lock(&link);
+    if matches!(&*link, Link::Connected(session) if session.id == session_id) {
+        *link = Link::Idle;
+    }
+    lock(&pending).clear();
+}
+
+// ? None si la sesion abierta sirve, o las fallas previas si hay que conectar
+fn needs_connection(link: &mut Link, to: &Process) -> Result<Option<u32>, String> {
+    match &*link {
+        Link::Connected(session) if session.last_used.elapsed() < IDLE_TIMEOUT => Ok(None),
+        Link::Connected(session) => {
+            let _ = session.writer.shutdown(Shutdown::Both);
+            *link = Link::Idle;
+            Ok(Some(0))
+        }
+        Link::Backoff { retry_at, .. } if Instant::now() < *retry_at => {
+            Err(format!("sin conexión con {}, reintento en {:?}", to.id, retry_at.saturating_duration_since(Instant::now())))
+        }
+        Link::Backoff { failures, .. } => Ok(Some(*failures)),
+        Link::Idle => Ok(Some(0)),
+    }
+}
+
+// ? escribe el mensaje en la sesion y, si se pasa `answer_tx`, registra quien espera la respuesta. Si hay que conectar
+// ? se hace sin el lock del nodo, para que un nodo que no responde no frene a los demas hilos que le envian mensajes.
+fn send_frame(to: &Process, message: &str, answer_tx: Option<Sender<String>>, connect_timeout: Duration) -> Result<(u64, Pending), String> {
+    let payload = faults::apply(&to.addr, auth::seal(to.id, message).as_bytes())?;
+    let shared = link_for(to.id);
+
+    // ? el lock se suelta al terminar esta linea, antes de conectar
+    let needed = needs_connection(&mut lock(&shared), to)?;
+    let connected = needed.map(|failures| (failures, connect(to, &shared, connect_timeout)));
+    let mut link = lock(&shared);
+    match connected {
+        // ? otro hilo conecto mientras tanto: nos quedamos con su sesion
+        Some((_, Ok(session))) if matches!(&*link, Link::Connected(_)) => {
+            let _ = session.writer.shutdown(Shutdown::Both);
+        }
+        Some((_, Ok(session))) => *link = Link::Connected(session),
+        Some((failures, Err(e))) => {
+            if matches!(&*link, Link::Connected(_)) {
+                return Err(e);
+            }
+            let wait = backoff(failures + 1);
+            *link = Link::Backoff { failures: failures + 1, retry_at: Instant::now() + wait };
+            return Err(format!("{} (reintento en {:?})", e, wait));
+        }
+        None => {}
+    }
+    let session = match &mut *link {
+        Link::Connected(session) => session,
+        _ => return Err(format!("sin conexión con {}", to.id)),
+    };
+
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    if let Some(tx) = answer_tx {
+        lock(&session.pending).insert(id, tx);
+    }
+    if let Err(e) = write_frame(&mut session.writer, id, &payload) {
+        // ? la sesion quedo inservible: el proximo mensaje vuelve a conectar
+        lock(&session.pending).remove(&id);
+        let _ = session.writer.shutdown(Shutdown::Both);
+        *link = Link::Idle;
+        return Err(e);
+    }
+    session.last_used = Instant::now();
+    Ok((id, Arc::clone(&session.pending)))
+}
+
+/// Envia `message` a `to` por su sesion sin esperar la respuesta.
+pub(crate) fn send(to: &Process, message: &str) -> Result<(), String> {
+    send_frame(to, message, None, CONNECT_TIMEOUT).map(|_| ())
+}
+
+/// Envia `message` a `to` por su sesion y espera la respuesta hasta `timeout`, contando lo que tarde en conectar.
+pub(crate) fn request(to: &Process, message: &str, timeout: Duration) -> Result<String, String> {
+    let deadline = Instant::now() + timeout;
+    let (tx, rx) = channel();
+    let (id, pending) = send_frame(to, message, Some(tx), timeout.min(CONNECT_TIMEOUT))?;
+
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(answer) => Ok(answer),
+        Err(RecvTimeoutError::Timeout) => {
+            lock(&pending).remove(&id);
+            Err(format!("{} no respondió en {:?}", to.id, timeout))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(format!("se perdió la conexión con {}", to.id)),
+    }
+}
+
+/// Estado de la conexion con `peer`: `connected`, `idle` (sin sesion abierta), `backoff` (esperando para reintentar)
+/// o `none` si nunca se le envio nada.
+pub(crate) fn connection_state(peer: u32) -> &'static str {
+    if let Some(state) = lock(&REPORTED).get(&peer) {
+        return state;
+    }
+
+    let link = match lock(&LINKS).get(&peer) {
+        Some(link) => Arc::clone(link),
+        None => return "none",
+    };
+
+    let state = match &*lock(&link) {
+        Link::Idle => "idle",
+        Link::Connected(_) => "connected",
+        Link::Backoff { .. } => "backoff",
+    };
+    state
+}
+
+/// Registra el estado de la sesion con `peer` cuando no la maneja este modulo, para que `connection_state` lo informe.
+pub(crate) fn report_state(peer: u32, state: &'static str) {
+    lock(&REPORTED).insert(peer, state);
+}
@@ -4,3 +4,37 @@ pub(crate) const NEW_LIDER_MSG: &str = "NEW LEADER";
 pub(crate) const START_ELECTION_MSG: &str = "ELECTION";
 pub(crate) const ELECTION_MSG: &str = "OK ELECTION";
 pub(crate) const NEW_LEADER_ANSWER: &str = "OK";
+pub(crate) const PROPOSE_LEADER_MSG: &str = "PROPOSE LEADER";
+pub(crate) const STEP_DOWN_MSG: &str = "STEP DOWN";
+pub(crate) const SHUTDOWN_MSG: &str = "SHUTDOWN";
+pub(crate) const TRANSFER_LEADER_MSG: &str = "TRANSFER LEADER";
+pub(crate) const TAKE_OVER_MSG: &str = "TAKE OVER";
+pub(crate) const STATUS_MSG: &str = "STATUS";
+pub(crate) const STATUS_JSON_MSG: &str = "STATUS JSON";
+pub(crate) const FAULTS_MSG: &str = "FAULTS";
+pub(crate) const LOG_MSG: &str = "LOG";
+pub(crate) const TRIP_MSG: &str = "TRIP";
+pub(crate) const GET_TRIP_MSG: &str = "GET TRIP";
+pub(crate) const REPLICATE_TRIP_MSG: &str = "REPLICATE TRIP";
+pub(crate) const SYNC_TRIPS_MSG: &str = "SYNC TRIPS";
+pub(crate) const PULL_TRIPS_MSG: &str = "PULL TRIPS";
+pub(crate) const TRIPS_ANSWER: &str = "TRIPS";
+pub(crate) const LAST_TRIP_MSG: &str = "LAST TRIP";
+pub(crate) const LEADER_CHECK_MSG: &str = "LEADER CHECK";
+pub(crate) const STALE_TERM_ANSWER: &str = "STALE TERM";
+pub(crate) const NOT_LEADER_ANSWER: &str = "NOT LEADER";
+pub(crate) const NOT_COMMITTED_ANSWER: &str = "NOT COMMITTED";
+pub(crate) const BUSY_ANSWER: &str = "BUSY";
+pub(crate) const SESSION_MSG: &str = "SESSION";
+pub(crate) const SESSION_ANSWER: &str = "SESSION OK";
+pub(crate) const AUTH_MSG: &str = "AUTH";
+pub(crate) const UNAUTHORIZED_ANSWER: &str = "UNAUTHORIZED";
+pub(crate) const FORBIDDEN_ANSWER: &str = "FORBIDDEN";
+pub(crate) const NOT_FOUND_ANSWER: &str = "NOT FOUND";
+pub(crate) const RING_ELECTION_MSG: &str = "RING ELECTION";
+pub(crate) const REQUEST_VOTE_MSG: &str = "REQUEST VOTE";
+pub(crate) const VOTE_GRANTED_ANSWER: &str = "VOTE GRANTED";
+pub(crate) const VOTE_DENIED_ANSWER: &str = "VOTE DENIED";
+pub(crate) const PRE_VOTE_MSG: &str = "PRE VOTE";
+pub(crate) const PRE_VOTE_GRANTED_ANSWER: &str = "PRE VOTE GRANTED";
+pub(crate) const PRE_VOTE_DENIED_ANSWER: &str = "PRE VOTE DENIED";
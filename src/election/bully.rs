@@ -0,0 +1,93 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use crate::consts::{ELECTION_MSG, START_ELECTION_MSG};
+use crate::election::{announce_leader, send_and_wait_answer, snapshot_processes, ElectionEnv, ElectionOutcome, ElectionStats, ElectionStrategy};
+use crate::process::Process;
+
+/// Algoritmo bully: gana el nodo elegible de mayor rango (prioridad y luego id) que este vivo.
+pub(crate) struct Bully {
+    env: ElectionEnv,
+}
+
+impl Bully {
+    pub(crate) fn new(env: ElectionEnv) -> Bully {
+        Bully { env }
+    }
+}
+
+impl ElectionStrategy for Bully {
+    fn name(&self) -> &'static str {
+        "bully"
+    }
+
+    fn env(&self) -> &ElectionEnv {
+        &self.env
+    }
+
+    fn start_election(&self, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+        info!("Iniciando eleccion de lider...");
+        let mut stats = ElectionStats::start(self.name());
+
+        let mut answers = 0;
+        let snapshot = match snapshot_processes(processes) {
+            Some(snapshot) => snapshot,
+            None => return ElectionOutcome::Finished, //TODO
+        };
+
+        let me = match snapshot.iter().find(|process| process.me) {
+            Some(me) => me,
+            None => {
+                error!("No se encontro el proceso actual en la lista de procesos");
+                return ElectionOutcome::Finished; //TODO
+            }
+        };
+        let observer = me.is_observer();
+        let my_rank = me.election_rank();
+
+        // ? solo le avisamos a los nodos que pueden ser lider y que tienen mejor rango que yo (mayor prioridad, o igual prioridad y mayor id).
+        // ? si yo no puedo ser lider, les aviso a todos los que pueden serlo.
+        for process in snapshot.iter() {
+            if !process.is_observer() && !process.me && (process.election_rank() > my_rank || observer) {
+                debug!("Enviando mensaje de ELECTION a {}", process.addr);
+                stats.message_sent();
+
+                // ? si un proceso no responde lo consideramos caido y seguimos con el resto.
+                // ? solo cuenta la respuesta de quien toma la eleccion.
+                match send_and_wait_answer(self.env.transport.as_ref(), process, START_ELECTION_MSG, Duration::from_secs(5)) {
+                    Ok(response) if response == ELECTION_MSG => {
+                        debug!("Respuesta recibida de {}: {}", process.addr, response);
+                        answers += 1;
+                    }
+                    Ok(response) => warn!("Respuesta inesperada de {}: {}", process.addr, response),
+                    Err(e) => warn!("Error o timeout esperando respuesta de {}: {}", process.addr, e),
+                }
+            }
+        }
+
+        // ? un nodo no elegible nunca se autoproclama lider, espera que lo haga otro
+        let mut outcome = ElectionOutcome::Finished;
+        if answers == 0 && observer {
+            info!("No se recibieron respuestas, pero este nodo es observador y no puede ser líder.");
+        } else if answers == 0 {
+            info!("No se recibieron respuestas. Autoproclamandose líder...");
+            outcome = announce_leader(self.env.transport.as_ref(), &snapshot, me.id, self.env.term.next(), tx, &mut stats);
+        }
+
+        stats.finish();
+        outcome
+    }
+
+    fn handle_message(&self, message: &str, _processes: &Arc<RwLock<Vec<Process>>>, election_tx: &Sender<String>) -> Option<String> {
+        if message != START_ELECTION_MSG {
+            return None;
+        }
+
+        // ? respondemos que tomamos la eleccion y la iniciamos nosotros
+        if let Err(e) = election_tx.send(START_ELECTION_MSG.to_string()) {
+            error!("Error al enviar mensaje de eleccion: {}", e); // ? podria romperse todo porque no sabemos que pasa con el mensaje que no se pudo enviar de election.
+        }
+
+        Some(ELECTION_MSG.to_string())
+    }
+}
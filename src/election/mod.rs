@@ -0,0 +1,353 @@
+mod bully;
+mod pre_vote;
+mod raft;
+mod ring;
+mod split_brain;
+mod term;
+
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::clock::{Clock, SystemClock};
+use crate::journal::{self, Event};
+use crate::metrics::{record_connection_error, record_election_started};
+use crate::consts::{NEW_LEADER_ANSWER, NEW_LIDER_MSG, PROPOSE_LEADER_MSG, STALE_TERM_ANSWER, START_ELECTION_MSG, STEP_DOWN_MSG, TAKE_OVER_MSG};
+use crate::process::Process;
+use crate::shutdown::{StopSignal, STOP_POLL_INTERVAL};
+use crate::status::record_contact;
+use crate::supervisor::spawn_supervised;
+use crate::transport::{TcpTransport, Transport};
+use crate::utils::random::Rng;
+
+pub(crate) use bully::Bully;
+pub(crate) use raft::Raft;
+pub(crate) use pre_vote::{answer_pre_vote, is_leader_alive, pre_vote_answer, run_pre_vote, PRE_VOTE_TIMEOUT};
+pub(crate) use ring::Ring;
+pub(crate) use split_brain::{adoption_messages, handle_leader_claim, judge_leader_claim, ClaimVerdict, LeaderClaim};
+pub(crate) use term::Term;
+
+pub(crate) const ELECTION_STRATEGIES: [&str; 3] = ["bully", "ring", "raft"];
+pub(crate) const QUORUM_POLICIES: [&str; 2] = ["none", "majority"];
+
+// ? tiempo maximo que se espera la confirmacion de un PROPOSE LEADER cuando se exige quorum
+const NEW_LEADER_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+// ? espera entre reintentos sin quorum: se duplica en cada intento hasta el maximo
+const QUORUM_RETRY_BASE: Duration = Duration::from_secs(1);
+const QUORUM_RETRY_MAX: Duration = Duration::from_secs(30);
+
+// ? si esta activo, un nodo solo se proclama lider si la mayoria del cluster confirma el anuncio
+static MAJORITY_QUORUM: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn require_majority_quorum() {
+    MAJORITY_QUORUM.store(true, Ordering::SeqCst);
+}
+
+fn is_majority_quorum_required() -> bool {
+    MAJORITY_QUORUM.load(Ordering::SeqCst)
+}
+
+/// Lo que un nodo usa para elegir lider: la red, el reloj y su term.
+///
+/// Los nodos reales usan TCP y el reloj del sistema. La simulacion reemplaza ambos para correr un cluster
+/// entero en un solo proceso.
+#[derive(Clone)]
+pub(crate) struct ElectionEnv {
+    pub(crate) transport: Arc<dyn Transport>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) term: Arc<Term>,
+    // ? semilla de los timeouts aleatorios de la estrategia
+    pub(crate) seed: u64,
+}
+
+impl ElectionEnv {
+    pub(crate) fn system(term: Term) -> ElectionEnv {
+        ElectionEnv {
+            transport: Arc::new(TcpTransport),
+            clock: Arc::new(SystemClock::new()),
+            term: Arc::new(term),
+            seed: Rng::from_entropy().next_u64(),
+        }
+    }
+}
+
+/// Resultado de un intento de eleccion.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ElectionOutcome {
+    // ? la eleccion termino: gane, gano otro o no hay a quien elegir
+    Finished,
+    // ? gane pero la mayoria del cluster no confirmo el anuncio: sigo como candidato y reintento
+    NoQuorum,
+}
+
+/// Algoritmo de eleccion de lider.
+///
+/// El hilo de eleccion llama a `start_election` cuando hay que elegir un lider y a `handle_deferred`
+/// con los mensajes que el listener le deriva. El listener llama a `handle_message` con cada mensaje
+/// recibido y responde lo que devuelva, por lo que no debe bloquearse haciendo IO.
+///
+/// Todas las estrategias anuncian al ganador con `NEW LEADER {pid}`, que el listener y el process
+/// handler atienden igual sin importar el algoritmo.
+pub(crate) trait ElectionStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn env(&self) -> &ElectionEnv;
+
+    fn start_election(&self, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome;
+
+    /// Devuelve la respuesta para el mensaje, o `None` si no es un mensaje de esta estrategia.
+    fn handle_message(&self, message: &str, processes: &Arc<RwLock<Vec<Process>>>, election_tx: &Sender<String>) -> Option<String>;
+
+    fn handle_deferred(&self, _message: &str, _processes: &Arc<RwLock<Vec<Process>>>, _tx: &mut Sender<String>) -> ElectionOutcome {
+        ElectionOutcome::Finished
+    }
+}
+
+pub(crate) fn strategy_from_name(name: &str, env: ElectionEnv) -> Option<Arc<dyn ElectionStrategy>> {
+    match name {
+        "bully" => Some(Arc::new(Bully::new(env))),
+        "ring" => Some(Arc::new(Ring::new(env))),
+        "raft" => Some(Arc::new(Raft::new(env))),
+        _ => None,
+    }
+}
+
+pub(crate) fn start_election_thread(processes: Arc<RwLock<Vec<Process>>>, rx: std::sync::mpsc::Receiver<String>, mut tx: Sender<String>, strategy: Arc<dyn ElectionStrategy>, stop: StopSignal) -> JoinHandle<()> {
+    info!("Usando el algoritmo de eleccion {}", strategy.name());
+
+    spawn_supervised("election", stop.clone(), move || {
+        // ? proximo reintento pendiente por falta de quorum y cantidad de intentos fallidos
+        let clock = Arc::clone(&strategy.env().clock);
+        let mut retry: Option<(Duration, u32)> = None;
+
+        while !stop.is_stopped() {
+            let outcome = match rx.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(msg) => Some(handle_election_request(&msg, strategy.as_ref(), &processes, &mut tx)),
+                Err(RecvTimeoutError::Timeout) => match retry {
+                    // ? si mientras esperaba se eligio a otro, ya no hace falta reintentar
+                    Some((deadline, _)) if clock.now() >= deadline && has_leader(&processes) => Some(ElectionOutcome::Finished),
+                    Some((deadline, attempts)) if clock.now() >= deadline => {
+                        info!("Reintentando eleccion sin quorum (intento {})...", attempts + 1);
+                        Some(strategy.start_election(&processes, &mut tx))
+                    }
+                    _ => None,
+                },
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+
+            retry = match (outcome, retry) {
+                (Some(ElectionOutcome::NoQuorum), previous) => {
+                    let attempts = previous.map_or(0, |(_, attempts)| attempts) + 1;
+                    let backoff = quorum_backoff(attempts);
+                    warn!("No se alcanzó el quorum. Reintento en {:?}.", backoff);
+                    Some((clock.now() + backoff, attempts))
+                }
+                (Some(ElectionOutcome::Finished), _) => None,
+                (None, pending) => pending,
+            };
+        }
+        Ok(())
+    })
+}
+
+/// Atiende un pedido que llego al hilo de eleccion: iniciar una eleccion, tomar el liderazgo traspasado
+/// o un mensaje que la estrategia derivo.
+pub(crate) fn handle_election_request(msg: &str, strategy: &dyn ElectionStrategy, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+    if msg == START_ELECTION_MSG {
+        record_election_started();
+        journal::record(Event::ElectionStarted { strategy: strategy.name() });
+        strategy.start_election(processes, tx)
+    } else if msg == TAKE_OVER_MSG {
+        take_over(strategy.env(), processes, tx)
+    } else {
+        strategy.handle_deferred(msg, processes, tx)
+    }
+}
+
+fn quorum_backoff(attempts: u32) -> Duration {
+    QUORUM_RETRY_BASE.saturating_mul(1 << attempts.saturating_sub(1).min(16)).min(QUORUM_RETRY_MAX)
+}
+
+/// Copia de la lista de procesos, para no retener el lock mientras se habla con otros nodos: mientras dura una
+/// ronda el process handler tiene que poder tomar el write, por ejemplo para registrar un NEW LEADER.
+pub(crate) fn snapshot_processes(processes: &Arc<RwLock<Vec<Process>>>) -> Option<Vec<Process>> {
+    match processes.read() {
+        Ok(guard) => Some(guard.clone()),
+        Err(e) => {
+            error!("Error al obtener el guard de procesos: {}", e);
+            None
+        }
+    }
+}
+
+fn has_leader(processes: &Arc<RwLock<Vec<Process>>>) -> bool {
+    match processes.read() {
+        Ok(guard) => guard.iter().any(|process| process.leader),
+        Err(_) => false,
+    }
+}
+
+/// Cantidad de mensajes y duracion de una eleccion, para comparar los algoritmos.
+pub(crate) struct ElectionStats {
+    strategy: &'static str,
+    started: Instant,
+    messages: u32,
+}
+
+impl ElectionStats {
+    pub(crate) fn start(strategy: &'static str) -> ElectionStats {
+        ElectionStats { strategy, started: Instant::now(), messages: 0 }
+    }
+
+    pub(crate) fn message_sent(&mut self) {
+        self.messages += 1;
+    }
+
+    pub(crate) fn finish(self) {
+        info!("Eleccion {}: {} mensajes enviados en {:?}", self.strategy, self.messages, self.started.elapsed());
+    }
+}
+
+// ? envia un mensaje sin esperar respuesta
+pub(crate) fn send_message(transport: &dyn Transport, process: &Process, msg: &str) -> Result<(), String> {
+    let result = transport.send(process, msg);
+    record_contact(process.id, result.is_ok());
+    if result.is_err() {
+        record_connection_error(process.id);
+    }
+    result
+}
+
+// ? envia un mensaje y espera la respuesta hasta `timeout`
+pub(crate) fn send_and_wait_answer(transport: &dyn Transport, process: &Process, msg: &str, timeout: Duration) -> Result<String, String> {
+    let answer = transport.request(process, msg, timeout);
+    record_contact(process.id, answer.is_ok());
+    match &answer {
+        Ok(answer) => journal::record(Event::AnswerReceived { from: process.id, answer }),
+        Err(_) => record_connection_error(process.id),
+    }
+    answer
+}
+
+/// Me marco como lider del `term` y se lo aviso al resto de los procesos.
+///
+/// Si se exige quorum, primero propone el liderazgo con `PROPOSE LEADER {pid} {term}` y solo si la mayoria del cluster
+/// lo confirma se marca como lider y envia el NEW LEADER. Sin mayoria nadie adopto al lider, asi que no hay que retractarse.
+///
+/// Recibe una copia de la lista de procesos (ver `snapshot_processes`): el aviso al process handler necesita el write.
+pub(crate) fn announce_leader(transport: &dyn Transport, processes: &[Process], my_id: u32, term: u64, tx: &mut Sender<String>, stats: &mut ElectionStats) -> ElectionOutcome {
+    let msg = format!("{} {} {}", NEW_LIDER_MSG, my_id, term);
+
+    if is_majority_quorum_required() && !is_proposal_confirmed(transport, processes, my_id, term, stats) {
+        return ElectionOutcome::NoQuorum;
+    }
+
+    // ? aviso al hilo que maneja los procesos que hay un nuevo lider, yo
+    match tx.send(msg.clone()) {
+        Ok(_) => info!("Me setee como lider. Avisando al resto"),
+        Err(e) => {
+            error!("Error al enviar mensaje: {}", e);
+            return ElectionOutcome::Finished; //TODO
+        }
+    }
+
+    // ? aviso al resto de los procesos que hay un nuevo lider, yo
+    //TODO Manejar el caso de que no se pueda enviar un aviso a un proceso. Definir timeouts
+    for process in processes.iter() {
+        if process.id != my_id {
+            debug!("Enviando mensaje de nuevo lider a {}", process.id);
+            stats.message_sent();
+
+            match send_message(transport, process, &msg) {
+                Ok(_) => trace!("Mensaje enviado a {}", process.addr),
+                Err(e) => warn!("Error enviando mensaje de nuevo lider a {}: {}", process.id, e),
+            }
+        }
+    }
+
+    ElectionOutcome::Finished
+}
+
+// ? propone el liderazgo y devuelve si lo confirmo la mayoria del cluster, contando mi propio voto
+fn is_proposal_confirmed(transport: &dyn Transport, processes: &[Process], my_id: u32, term: u64, stats: &mut ElectionStats) -> bool {
+    let msg = format!("{} {} {}", PROPOSE_LEADER_MSG, my_id, term);
+    let mut acks = 1;
+    for process in processes.iter().filter(|process| process.id != my_id) {
+        debug!("Proponiéndome como lider a {}", process.id);
+        stats.message_sent();
+
+        match send_and_wait_answer(transport, process, &msg, NEW_LEADER_ACK_TIMEOUT) {
+            Ok(answer) if answer == NEW_LEADER_ANSWER => acks += 1,
+            Ok(answer) => warn!("Respuesta inesperada de {} a la propuesta de lider: {}", process.id, answer),
+            Err(e) => warn!("Error enviando la propuesta de lider a {}: {}", process.id, e),
+        }
+    }
+
+    // ? la mayoria es sobre el cluster configurado: un nodo aislado en una particion minoritaria nunca la alcanza
+    info!("{} de {} nodos confirmaron la propuesta de lider", acks, processes.len());
+    acks * 2 > processes.len()
+}
+
+/// Respuesta a `PROPOSE LEADER {pid} {term}`: se confirma sin adoptar al lider, que recien se adopta con el NEW LEADER
+/// que el candidato envia si consigue la mayoria. Se rechaza si ya conocemos un term mayor.
+pub(crate) fn answer_leader_proposal(message: &str, term: &Term) -> String {
+    let proposed = match message.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        [_, _, id, proposed] if id.parse::<u32>().is_ok() => proposed.parse::<u64>().ok(),
+        _ => None,
+    };
+    match proposed.map(|proposed| (proposed, term.observe(proposed))) {
+        Some((proposed, known)) if known > proposed => format!("{} {}", STALE_TERM_ANSWER, known),
+        Some(_) => NEW_LEADER_ANSWER.to_string(),
+        None => "error".to_string(),
+    }
+}
+
+// ? el lider me traspaso el liderazgo: me anuncio como nuevo lider en un term nuevo, sin importar el algoritmo
+fn take_over(env: &ElectionEnv, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+    let snapshot = match snapshot_processes(processes) {
+        Some(snapshot) => snapshot,
+        None => return ElectionOutcome::Finished,
+    };
+    let me = match snapshot.iter().find(|process| process.me) {
+        Some(me) => me,
+        None => return ElectionOutcome::Finished,
+    };
+    if me.is_observer() {
+        info!("Me traspasaron el liderazgo pero este nodo es observador, no puede ser líder.");
+        return ElectionOutcome::Finished;
+    }
+
+    info!("El lider me traspasó el liderazgo. Anunciándome como nuevo lider...");
+    let mut stats = ElectionStats::start("traspaso");
+    let outcome = announce_leader(env.transport.as_ref(), &snapshot, me.id, env.term.next(), tx, &mut stats);
+    stats.finish();
+    outcome
+}
+
+// ? si soy lider, aviso al resto que renuncio para que elijan otro sin esperar el timeout de heartbeat
+pub(crate) fn step_down_if_leader(transport: &dyn Transport, processes: &Arc<RwLock<Vec<Process>>>) {
+    let snapshot = match snapshot_processes(processes) {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    let my_id = match snapshot.iter().find(|process| process.me && process.leader) {
+        Some(me) => me.id,
+        None => return,
+    };
+
+    info!("Soy lider. Avisando al resto que renuncio...");
+    let msg = format!("{} {}", STEP_DOWN_MSG, my_id);
+
+    for process in snapshot.iter() {
+        if process.id == my_id {
+            continue;
+        }
+
+        match send_message(transport, process, &msg) {
+            Ok(_) => debug!("Renuncia enviada a {}", process.addr),
+            Err(e) => warn!("Error enviando renuncia a {}: {}", process.id, e),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use crate::consts::{PRE_VOTE_DENIED_ANSWER, PRE_VOTE_GRANTED_ANSWER, PRE_VOTE_MSG};
+use crate::election::{send_and_wait_answer, snapshot_processes};
+use crate::healthchecker::HEARTBEAT_INTERVAL;
+use crate::process::Process;
+use crate::transport::Transport;
+
+pub(crate) const PRE_VOTE_TIMEOUT: Duration = Duration::from_secs(2);
+// ? un lider vivo manda un heartbeat por intervalo: si faltan tres seguidos lo damos por perdido
+const LEADER_LOST_AFTER: Duration = Duration::from_secs(HEARTBEAT_INTERVAL.as_secs() * 3);
+
+/// Pregunta al resto si tambien perdieron al lider antes de iniciar una eleccion.
+///
+/// Devuelve `true` si la mayoria del cluster configurado (contandome) esta de acuerdo. Asi un nodo con un enlace
+/// inestable no interrumpe a un cluster sano.
+pub(crate) fn run_pre_vote(transport: &dyn Transport, processes: &Arc<RwLock<Vec<Process>>>) -> bool {
+    let snapshot = match snapshot_processes(processes) {
+        Some(snapshot) => snapshot,
+        None => return false,
+    };
+    let me = match snapshot.iter().find(|process| process.me) {
+        Some(me) => me,
+        None => return false,
+    };
+
+    let request = format!("{} {}", PRE_VOTE_MSG, me.id);
+    let mut agreed = 1;
+    for process in snapshot.iter().filter(|process| !process.me) {
+        match send_and_wait_answer(transport, process, &request, PRE_VOTE_TIMEOUT) {
+            Ok(answer) if answer == PRE_VOTE_GRANTED_ANSWER => agreed += 1,
+            Ok(answer) => debug!("{} todavía ve al líder: {}", process.id, answer),
+            Err(e) => warn!("{} no respondió: {}", process.id, e),
+        }
+    }
+
+    info!("{} de {} nodos perdieron al líder", agreed, snapshot.len());
+    agreed * 2 > snapshot.len()
+}
+
+/// Si el lider sigue vivo segun el ultimo heartbeat recibido de el. `last_heartbeat` y `now` se miden con el reloj del nodo.
+pub(crate) fn is_leader_alive(last_heartbeat: Option<Duration>, now: Duration) -> bool {
+    last_heartbeat.is_some_and(|last| now.saturating_sub(last) < LEADER_LOST_AFTER)
+}
+
+/// Responde un `PRE VOTE {candidato}`: lo acepto si no soy lider y tampoco tengo noticias del lider.
+pub(crate) fn answer_pre_vote(processes: &Arc<RwLock<Vec<Process>>>, last_heartbeat: Option<Duration>, now: Duration) -> String {
+    match processes.read() {
+        Ok(guard) => pre_vote_answer(&guard, last_heartbeat, now),
+        Err(_) => pre_vote_answer(&[], last_heartbeat, now),
+    }
+}
+
+/// Igual que `answer_pre_vote`, sobre la lista de procesos ya tomada.
+pub(crate) fn pre_vote_answer(processes: &[Process], last_heartbeat: Option<Duration>, now: Duration) -> String {
+    let leader = processes.iter().find(|process| process.leader).map(|leader| (leader.id, leader.me));
+
+    let leader_alive = is_leader_alive(last_heartbeat, now);
+    match leader {
+        Some((id, true)) => format!("{} {}", PRE_VOTE_DENIED_ANSWER, id),
+        Some((id, false)) if leader_alive => format!("{} {}", PRE_VOTE_DENIED_ANSWER, id),
+        _ => PRE_VOTE_GRANTED_ANSWER.to_string(),
+    }
+}
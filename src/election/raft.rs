@@ -0,0 +1,217 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use crate::consts::{REQUEST_VOTE_MSG, VOTE_DENIED_ANSWER, VOTE_GRANTED_ANSWER};
+use crate::election::{announce_leader, send_and_wait_answer, snapshot_processes, ElectionEnv, ElectionOutcome, ElectionStats, ElectionStrategy};
+use crate::process::Process;
+use crate::utils::random::Rng;
+
+// ? rango del timeout aleatorio antes de postularse, en milisegundos
+const MIN_ELECTION_TIMEOUT_MS: u64 = 150;
+const MAX_ELECTION_TIMEOUT_MS: u64 = 1500;
+// ? espera extra por cada nivel de prioridad mayor al mio: cubre todo el rango aleatorio, asi los nodos de mayor
+// ? prioridad se postulan primero y solo si no lo hacen se postula el resto
+const PRIORITY_DELAY_MS: u64 = MAX_ELECTION_TIMEOUT_MS;
+// ? cantidad de rondas sin mayoria antes de esperar al proximo pedido de eleccion
+const MAX_ROUNDS: u32 = 5;
+const VOTE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Eleccion al estilo Raft: cada candidato espera un timeout aleatorio, incrementa el term,
+/// se vota a si mismo y pide votos al resto. Gana quien consigue la mayoria del cluster configurado.
+/// Cada nodo vota a lo sumo una vez por term: el voto se guarda junto al term, que es el compartido por todas las estrategias.
+/// La prioridad alarga el timeout de los nodos menos prioritarios, para que gane el de mayor prioridad que este vivo.
+/// Los observadores votan pero nunca se postulan.
+/// A diferencia de Raft, el voto no mira si el candidato tiene los trips al dia: esta estrategia solo elige al lider. Los
+/// trips confirmados los protege el lider nuevo, que junta los de la mayoria antes de atender (ver `trips::collect_trips`).
+pub(crate) struct Raft {
+    env: ElectionEnv,
+    rng: Mutex<Rng>,
+}
+
+enum RoundResult {
+    Won,
+    Lost,
+    // ? gane la votacion pero la mayoria no confirmo el anuncio
+    NoQuorum,
+    // ? algun nodo respondio con un term mayor, dejamos de postularnos
+    NewerTerm,
+}
+
+// ? niveles de prioridad distintos y mayores al mio entre los nodos que pueden ser lider
+fn priority_levels_above(processes: &[Process], me: &Process) -> u64 {
+    let mut above: Vec<u32> = processes.iter()
+        .filter(|process| !process.is_observer() && process.priority > me.priority)
+        .map(|process| process.priority)
+        .collect();
+    above.sort_unstable();
+    above.dedup();
+    above.len() as u64
+}
+
+fn current_leader(processes: &Arc<RwLock<Vec<Process>>>) -> Option<u32> {
+    match processes.read() {
+        Ok(guard) => guard.iter().find(|process| process.leader).map(|leader| leader.id),
+        Err(_) => None,
+    }
+}
+
+// ? "VOTE GRANTED {term}" o "VOTE DENIED {term}"
+fn parse_vote(answer: &str) -> Option<(bool, u64)> {
+    let (granted, term) = if let Some(term) = answer.strip_prefix(VOTE_GRANTED_ANSWER) {
+        (true, term)
+    } else {
+        (false, answer.strip_prefix(VOTE_DENIED_ANSWER)?)
+    };
+
+    Some((granted, term.trim().parse().ok()?))
+}
+
+impl Raft {
+    pub(crate) fn new(env: ElectionEnv) -> Raft {
+        Raft {
+            rng: Mutex::new(Rng::new(env.seed)),
+            env,
+        }
+    }
+
+    // ? aleatorio para no postularse todos a la vez, mas la espera por los nodos de mayor prioridad
+    fn election_timeout(&self, processes: &[Process], me: &Process) -> Duration {
+        let mut rng = match self.rng.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let priority_delay = PRIORITY_DELAY_MS * priority_levels_above(processes, me);
+        Duration::from_millis(rng.range(MIN_ELECTION_TIMEOUT_MS, MAX_ELECTION_TIMEOUT_MS) + priority_delay)
+    }
+
+    fn run_round(&self, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>, stats: &mut ElectionStats) -> RoundResult {
+        let snapshot = match snapshot_processes(processes) {
+            Some(snapshot) => snapshot,
+            None => return RoundResult::Lost,
+        };
+        let me = match snapshot.iter().find(|process| process.me) {
+            Some(me) => me,
+            None => return RoundResult::Lost,
+        };
+
+        // ? nuevo term, me voto a mi mismo
+        let term = match self.env.term.campaign(me.id) {
+            Ok(term) => term,
+            Err(e) => {
+                error!("{}. No me postulo.", e);
+                return RoundResult::Lost;
+            }
+        };
+        info!("Postulandome en el term {}", term);
+
+        let request = format!("{} {} {}", REQUEST_VOTE_MSG, term, me.id);
+        let mut votes = 1;
+        for process in snapshot.iter().filter(|process| !process.me) {
+            stats.message_sent();
+
+            match send_and_wait_answer(self.env.transport.as_ref(), process, &request, VOTE_TIMEOUT).map(|answer| parse_vote(&answer)) {
+                Ok(Some((true, _))) => votes += 1,
+                Ok(Some((false, their_term))) if their_term > term => {
+                    self.env.term.observe(their_term);
+                    info!("{} está en el term {}, mayor al mío. Dejo de postularme.", process.id, their_term);
+                    return RoundResult::NewerTerm;
+                }
+                Ok(Some((false, _))) => debug!("{} no me votó en el term {}", process.id, term),
+                Ok(None) => warn!("Respuesta inválida de {}", process.id),
+                Err(e) => warn!("{} no respondió el pedido de voto: {}", process.id, e),
+            }
+        }
+
+        // ? la mayoria es sobre el cluster configurado, no sobre los nodos que respondieron
+        let cluster_size = snapshot.len();
+        info!("Obtuve {} de {} votos en el term {}", votes, cluster_size, term);
+        if votes * 2 > cluster_size {
+            match announce_leader(self.env.transport.as_ref(), &snapshot, me.id, term, tx, stats) {
+                ElectionOutcome::Finished => RoundResult::Won,
+                ElectionOutcome::NoQuorum => RoundResult::NoQuorum,
+            }
+        } else {
+            RoundResult::Lost
+        }
+    }
+}
+
+impl ElectionStrategy for Raft {
+    fn name(&self) -> &'static str {
+        "raft"
+    }
+
+    fn env(&self) -> &ElectionEnv {
+        &self.env
+    }
+
+    fn start_election(&self, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+        let snapshot = match snapshot_processes(processes) {
+            Some(snapshot) => snapshot,
+            None => return ElectionOutcome::Finished,
+        };
+        let me = match snapshot.iter().find(|process| process.me) {
+            Some(me) => me,
+            None => return ElectionOutcome::Finished,
+        };
+        if me.is_observer() {
+            info!("Este nodo es observador, no se postula como líder.");
+            return ElectionOutcome::Finished;
+        }
+
+        let mut stats = ElectionStats::start(self.name());
+        let leader_before = current_leader(processes);
+
+        let mut outcome = ElectionOutcome::Finished;
+        for _ in 0..MAX_ROUNDS {
+            // ? el timeout aleatorio evita que todos se postulen a la vez y dividan los votos
+            self.env.clock.sleep(self.election_timeout(&snapshot, me));
+
+            let leader_now = current_leader(processes);
+            if leader_now.is_some() && leader_now != leader_before {
+                info!("Ya se eligió a {:?} mientras esperaba, no me postulo.", leader_now);
+                break;
+            }
+
+            match self.run_round(processes, tx, &mut stats) {
+                RoundResult::Won | RoundResult::NewerTerm => break,
+                // ? el hilo de eleccion reintenta con backoff
+                RoundResult::NoQuorum => {
+                    outcome = ElectionOutcome::NoQuorum;
+                    break;
+                }
+                RoundResult::Lost => info!("No alcancé la mayoría, reintentando..."),
+            }
+        }
+
+        stats.finish();
+        outcome
+    }
+
+    fn handle_message(&self, message: &str, processes: &Arc<RwLock<Vec<Process>>>, _election_tx: &Sender<String>) -> Option<String> {
+        // ? "REQUEST VOTE {term} {candidato}"
+        let parts: Vec<&str> = message.strip_prefix(REQUEST_VOTE_MSG)?.split_whitespace().collect();
+        let (term, candidate) = match (parts.first().and_then(|t| t.parse::<u64>().ok()), parts.get(1).and_then(|c| c.parse::<u32>().ok())) {
+            (Some(term), Some(candidate)) if parts.len() == 2 => (term, candidate),
+            _ => return Some("error".to_string()),
+        };
+
+        let candidate_can_lead = match processes.read() {
+            Ok(guard) => guard.iter().any(|process| process.id == candidate && !process.is_observer()),
+            Err(_) => false,
+        };
+
+        // ? un voto de un term anterior no cuenta: en un term nuevo puedo volver a votar. No se compara el ultimo trip.
+        let (grant, current) = if candidate_can_lead {
+            self.env.term.grant_vote(term, candidate)
+        } else {
+            self.env.term.observe(term);
+            (false, self.env.term.current())
+        };
+        if grant {
+            Some(format!("{} {}", VOTE_GRANTED_ANSWER, current))
+        } else {
+            Some(format!("{} {}", VOTE_DENIED_ANSWER, current))
+        }
+    }
+}
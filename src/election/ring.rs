@@ -0,0 +1,226 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock};
+use crate::consts::{NEW_LIDER_MSG, RING_ELECTION_MSG};
+use crate::election::{announce_leader, send_message, snapshot_processes, ElectionEnv, ElectionOutcome, ElectionStats, ElectionStrategy};
+use crate::process::Process;
+
+// ? candidato vacio: lo usa un observador que inicia la eleccion, el primer nodo elegible lo reemplaza
+const NO_CANDIDATE: &str = "-";
+
+/// Algoritmo de anillo (Chang–Roberts).
+///
+/// Los nodos forman un anillo ordenado por id. La eleccion circula como `RING ELECTION {origen} {candidato} {saltos}`
+/// y cada nodo elegible reemplaza al candidato si tiene mejor rango. Cuando el mensaje vuelve al candidato, este gana.
+/// Un nodo que ya propuso su candidatura descarta los mensajes con candidatos peores.
+pub(crate) struct Ring {
+    env: ElectionEnv,
+    participant: Mutex<bool>,
+}
+
+struct RingMessage {
+    origin: u32,
+    candidate: Option<u32>,
+    hops: usize,
+}
+
+impl RingMessage {
+    fn parse(message: &str) -> Option<RingMessage> {
+        let parts: Vec<&str> = message[RING_ELECTION_MSG.len()..].split_whitespace().collect();
+        if parts.len() != 3 {
+            return None;
+        }
+
+        let candidate = match parts[1] {
+            NO_CANDIDATE => None,
+            id => Some(id.parse().ok()?),
+        };
+
+        Some(RingMessage { origin: parts[0].parse().ok()?, candidate, hops: parts[2].parse().ok()? })
+    }
+
+    fn to_message(&self) -> String {
+        let candidate = self.candidate.map_or(NO_CANDIDATE.to_string(), |id| id.to_string());
+        format!("{} {} {} {}", RING_ELECTION_MSG, self.origin, candidate, self.hops)
+    }
+}
+
+fn rank_of(processes: &[Process], id: u32) -> (u32, u32) {
+    processes.iter().find(|process| process.id == id).map_or((0, id), Process::election_rank)
+}
+
+impl Ring {
+    pub(crate) fn new(env: ElectionEnv) -> Ring {
+        Ring { env, participant: Mutex::new(false) }
+    }
+
+    fn set_participant(&self, participant: bool) {
+        match self.participant.lock() {
+            Ok(mut guard) => *guard = participant,
+            Err(poisoned) => *poisoned.into_inner() = participant,
+        }
+    }
+
+    fn is_participant(&self) -> bool {
+        match self.participant.lock() {
+            Ok(guard) => *guard,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    /// Envia el mensaje al siguiente nodo vivo del anillo. Devuelve `false` si no hay ninguno.
+    fn forward(&self, processes: &[Process], me: &Process, mut message: RingMessage, stats: &mut ElectionStats) -> bool {
+        let mut ring: Vec<&Process> = processes.iter().collect();
+        ring.sort_by_key(|process| process.id);
+        let my_index = match ring.iter().position(|process| process.me) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        message.hops += 1;
+        for k in 1..ring.len() {
+            let successor = ring[(my_index + k) % ring.len()];
+            stats.message_sent();
+
+            match send_message(self.env.transport.as_ref(), successor, &message.to_message()) {
+                Ok(_) => {
+                    debug!("Eleccion reenviada a {} con candidato {:?}", successor.id, message.candidate);
+                    return true;
+                }
+                Err(e) => {
+                    warn!("{} no responde, lo salteo: {}", successor.id, e);
+                    // ? si el candidato se cayo, el mensaje nunca volveria a el: lo reemplazo
+                    if message.candidate == Some(successor.id) {
+                        message.candidate = if me.is_observer() { None } else { Some(me.id) };
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    fn become_leader_alone(&self, processes: &[Process], me: &Process, tx: &mut Sender<String>, stats: &mut ElectionStats) -> ElectionOutcome {
+        self.set_participant(false);
+        if me.is_observer() {
+            info!("No hay otros nodos vivos y este nodo es observador, no hay lider.");
+            ElectionOutcome::Finished
+        } else {
+            info!("No hay otros nodos vivos. Autoproclamandose líder...");
+            announce_leader(self.env.transport.as_ref(), processes, me.id, self.env.term.next(), tx, stats)
+        }
+    }
+}
+
+impl ElectionStrategy for Ring {
+    fn name(&self) -> &'static str {
+        "ring"
+    }
+
+    fn env(&self) -> &ElectionEnv {
+        &self.env
+    }
+
+    fn start_election(&self, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+        info!("Iniciando eleccion de lider...");
+        let mut stats = ElectionStats::start(self.name());
+
+        let snapshot = match snapshot_processes(processes) {
+            Some(snapshot) => snapshot,
+            None => return ElectionOutcome::Finished,
+        };
+        let me = match snapshot.iter().find(|process| process.me) {
+            Some(me) => me,
+            None => return ElectionOutcome::Finished,
+        };
+
+        self.set_participant(true);
+        let candidate = if me.is_observer() { None } else { Some(me.id) };
+        let message = RingMessage { origin: me.id, candidate, hops: 0 };
+
+        let mut outcome = ElectionOutcome::Finished;
+        if !self.forward(&snapshot, me, message, &mut stats) {
+            outcome = self.become_leader_alone(&snapshot, me, tx, &mut stats);
+        }
+        stats.finish();
+        outcome
+    }
+
+    fn handle_message(&self, message: &str, _processes: &Arc<RwLock<Vec<Process>>>, election_tx: &Sender<String>) -> Option<String> {
+        // ? la eleccion termino: dejo de participar, el mensaje lo sigue atendiendo el listener
+        if message.starts_with(NEW_LIDER_MSG) {
+            self.set_participant(false);
+            return None;
+        }
+
+        if !message.starts_with(RING_ELECTION_MSG) {
+            return None;
+        }
+
+        // ? el reenvio implica conectarse al siguiente nodo, lo hace el hilo de eleccion
+        if let Err(e) = election_tx.send(message.to_string()) {
+            error!("Error al derivar el mensaje al hilo de eleccion: {}", e);
+            return Some("error".to_string());
+        }
+
+        Some("OK".to_string())
+    }
+
+    fn handle_deferred(&self, message: &str, processes: &Arc<RwLock<Vec<Process>>>, tx: &mut Sender<String>) -> ElectionOutcome {
+        let mut ring_message = match RingMessage::parse(message) {
+            Some(ring_message) => ring_message,
+            None => {
+                warn!("Mensaje de eleccion inválido: {}", message);
+                return ElectionOutcome::Finished;
+            }
+        };
+
+        let snapshot = match snapshot_processes(processes) {
+            Some(snapshot) => snapshot,
+            None => return ElectionOutcome::Finished,
+        };
+        let me = match snapshot.iter().find(|process| process.me) {
+            Some(me) => me,
+            None => return ElectionOutcome::Finished,
+        };
+        let mut stats = ElectionStats::start(self.name());
+
+        // ? el mensaje dio la vuelta completa con mi candidatura: gane
+        if ring_message.candidate == Some(me.id) {
+            info!("Mi candidatura dio la vuelta al anillo. Autoproclamandose líder...");
+            self.set_participant(false);
+            let outcome = announce_leader(self.env.transport.as_ref(), &snapshot, me.id, self.env.term.next(), tx, &mut stats);
+            stats.finish();
+            return outcome;
+        }
+
+        // ? si volvio al origen sin candidato, o dio demasiadas vueltas, no hay a quien elegir
+        let exhausted = ring_message.hops > 2 * snapshot.len();
+        if (ring_message.candidate.is_none() && ring_message.origin == me.id) || exhausted {
+            info!("La eleccion terminó sin candidatos elegibles.");
+            self.set_participant(false);
+            return ElectionOutcome::Finished;
+        }
+
+        let i_am_better = match ring_message.candidate {
+            Some(candidate) => me.election_rank() > rank_of(&snapshot, candidate),
+            None => true,
+        };
+
+        if !me.is_observer() && i_am_better {
+            // ? ya propuse mi candidatura, que es mejor que la recibida: descarto el mensaje
+            if self.is_participant() {
+                debug!("Descarto candidato {:?}, ya propuse uno mejor.", ring_message.candidate);
+                return ElectionOutcome::Finished;
+            }
+            ring_message.candidate = Some(me.id);
+        }
+
+        self.set_participant(true);
+        let mut outcome = ElectionOutcome::Finished;
+        if !self.forward(&snapshot, me, ring_message, &mut stats) {
+            outcome = self.become_leader_alone(&snapshot, me, tx, &mut stats);
+        }
+        stats.finish();
+        outcome
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use crate::consts::{HEARTBEAT_MSG, NEW_LIDER_MSG, STEP_DOWN_MSG};
+use crate::election::Term;
+use crate::metrics::record_split_brain;
+use crate::process::Process;
+use crate::work_thread::WorkCommand;
+
+/// Lider que dice ser el emisor de un heartbeat (`HEARTBEAT {lider} {term}`), con el term en el que fue elegido.
+pub(crate) struct LeaderClaim {
+    pub(crate) leader: u32,
+    pub(crate) term: u64,
+}
+
+impl LeaderClaim {
+    pub(crate) fn parse(message: &str) -> Option<LeaderClaim> {
+        let parts: Vec<&str> = message.strip_prefix(HEARTBEAT_MSG)?.split_whitespace().collect();
+        if parts.len() != 2 {
+            return None;
+        }
+
+        Some(LeaderClaim { leader: parts[0].parse().ok()?, term: parts[1].parse().ok()? })
+    }
+
+    // ? gana el term mas alto y, a igual term, el id mas alto. Todos los nodos llegan a la misma conclusion.
+    fn beats(&self, term: u64, leader: u32) -> bool {
+        (self.term, self.leader) > (term, leader)
+    }
+}
+
+/// Que hacer con el heartbeat de un nodo que dice ser lider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ClaimVerdict {
+    // ? el emisor ya es nuestro lider
+    Current,
+    // ? el emisor gana el conflicto: hay que adoptarlo como lider y resincronizar los trips
+    Adopt,
+    // ? el heartbeat no cuenta como del lider
+    Ignore,
+}
+
+/// Compara el lider que anuncia un heartbeat con el que conoce este nodo.
+///
+/// Si este nodo tambien se cree lider (por ejemplo, al sanar una particion), el conflicto se resuelve por el term en el
+/// que fue elegido cada uno y luego por id: el perdedor renuncia y adopta al ganador. Un subordinado que seguia a otro
+/// lider adopta al ganador de la misma forma. Se compara `leader_term` y no el term actual del nodo, que puede haber
+/// crecido por un candidato que no gano: asi nadie reclama un term que no gano.
+///
+/// Un subordinado solo ignora al emisor si su lider sigue vivo (`leader_alive`): un lider que dejo de mandar heartbeats
+/// no puede retener a sus seguidores, aunque haya ganado con un term mayor. Cada conflicto entre lideres se cuenta una
+/// sola vez en las metricas.
+pub(crate) fn judge_leader_claim(claim: &LeaderClaim, leader_alive: bool, processes: &[Process]) -> ClaimVerdict {
+    let me = match processes.iter().find(|process| process.me) {
+        Some(me) => me,
+        None => return ClaimVerdict::Ignore,
+    };
+    let known_leader = processes.iter().find(|process| process.leader);
+    if me.id == claim.leader {
+        return ClaimVerdict::Ignore;
+    }
+    if known_leader.is_some_and(|leader| leader.id == claim.leader) {
+        return ClaimVerdict::Current;
+    }
+
+    if me.leader {
+        let my_term = me.leader_term.unwrap_or(0);
+        let detected = record_split_brain(claim.leader, claim.term);
+        if !claim.beats(my_term, me.id) {
+            // ? el perdedor sigue mandando heartbeats hasta enterarse: se avisa solo la primera vez
+            if let Some(total) = detected {
+                warn!("{} también se cree líder (term {}), pero gano yo (term {}). Total detectados: {}", claim.leader, claim.term, my_term, total);
+            }
+            return ClaimVerdict::Ignore;
+        }
+        warn!("{} también se cree líder (term {}) y gana sobre mi term {}. Renuncio.", claim.leader, claim.term, my_term);
+    } else if leader_alive && known_leader.is_some_and(|leader| !claim.beats(leader.leader_term.unwrap_or(0), leader.id)) {
+        // ? heartbeat de un lider que perdio el conflicto: lo ignoramos mientras el nuestro siga vivo
+        return ClaimVerdict::Ignore;
+    } else {
+        info!("Adopto a {} como líder por su heartbeat (term {}).", claim.leader, claim.term);
+    }
+    ClaimVerdict::Adopt
+}
+
+/// Avisos para el process handler al adoptar al emisor de `claim`. Un NEW LEADER de un term menor al del lider conocido
+/// se descarta, asi que si el lider conocido fue elegido en un term mayor (y se adopta igual porque dejo de mandar
+/// heartbeats) primero se lo da de baja.
+pub(crate) fn adoption_messages(claim: &LeaderClaim, processes: &[Process]) -> Vec<String> {
+    let mut messages = Vec::new();
+    if let Some(leader) = processes.iter().find(|process| process.leader && process.leader_term.is_some_and(|term| term > claim.term)) {
+        messages.push(format!("{} {}", STEP_DOWN_MSG, leader.id));
+    }
+    messages.push(format!("{} {} {}", NEW_LIDER_MSG, claim.leader, claim.term));
+    messages
+}
+
+/// Resuelve el heartbeat de un nodo que dice ser lider con `judge_leader_claim`. Si hay que adoptarlo, se avisa al
+/// process handler y se pide al hilo de trabajo que resincronice los trips con el nuevo lider.
+///
+/// Devuelve `true` si el emisor es (o pasa a ser) el lider de este nodo, es decir, si el heartbeat cuenta como del lider.
+pub(crate) fn handle_leader_claim(claim: &LeaderClaim, term: &Term, leader_alive: bool, processes: &Arc<RwLock<Vec<Process>>>, process_handler_tx: &Sender<String>, work_tx: &Sender<WorkCommand>) -> bool {
+    term.observe(claim.term);
+
+    let messages = match processes.read() {
+        Ok(guard) => match judge_leader_claim(claim, leader_alive, &guard) {
+            ClaimVerdict::Current => return true,
+            ClaimVerdict::Ignore => return false,
+            ClaimVerdict::Adopt => adoption_messages(claim, &guard),
+        },
+        Err(e) => {
+            error!("Error al obtener el guard de procesos: {}", e);
+            return false;
+        }
+    };
+
+    for message in messages {
+        if let Err(e) = process_handler_tx.send(message) {
+            error!("Error al enviar mensaje: {}", e);
+            return false;
+        }
+    }
+    if let Err(e) = work_tx.send(WorkCommand::Resync(claim.leader)) {
+        error!("Error al pedir la resincronización de trips: {}", e);
+    }
+    true
+}
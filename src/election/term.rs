@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use crate::utils::file_handler::write_file_synced;
+
+// ? term actual y a quien se voto en el: raft vota a sus candidatos y los trips al lider que atienden en el term
+#[derive(Clone, Copy, Default)]
+struct TermState {
+    term: u64,
+    voted_for: Option<u32>,
+}
+
+/// Term de la ultima eleccion conocida por el nodo. Lo comparten todas las estrategias y viaja en NEW LEADER y HEARTBEAT.
+///
+/// Junto al term se guarda el voto del nodo en ese term. Si se indica un archivo, ambos se guardan en el en cada cambio:
+/// un nodo que se reinicia sin ellos podria votar dos veces en el mismo term y raft elegiria dos lideres, o atenderia los
+/// trips de dos lideres del mismo term. Por eso un voto que no se pudo guardar en disco no se da.
+#[derive(Default)]
+pub(crate) struct Term {
+    state: Mutex<TermState>,
+    // ? copia del term para leerlo sin el lock, por ejemplo al escribir el log mientras se guarda
+    current: AtomicU64,
+    file: Option<PathBuf>,
+}
+
+fn parse_state(content: &str) -> Option<TermState> {
+    let mut parts = content.split_whitespace();
+    let term = parts.next()?.parse().ok()?;
+    let voted_for = match parts.next()? {
+        "-" => None,
+        id => Some(id.parse().ok()?),
+    };
+    Some(TermState { term, voted_for })
+}
+
+impl Term {
+    pub(crate) fn new() -> Term {
+        Term::default()
+    }
+
+    /// Term guardado en `file`. Si el archivo no existe se empieza en el term 0 y se crea en el primer cambio.
+    pub(crate) fn persisted(file: &Path) -> Result<Term, String> {
+        let state = match fs::read_to_string(file) {
+            Ok(content) => parse_state(&content).ok_or(format!("el archivo {} no tiene un term válido", file.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => TermState::default(),
+            Err(e) => return Err(format!("no se pudo leer {}: {}", file.display(), e)),
+        };
+
+        Ok(Term { current: AtomicU64::new(state.term), state: Mutex::new(state), file: Some(file.to_path_buf()) })
+    }
+
+    fn state(&self) -> MutexGuard<'_, TermState> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // ? guarda el estado en el archivo, si hay. Sin error, el estado queda en disco aunque el nodo se caiga enseguida.
+    fn save(&self, state: &TermState) -> Result<(), String> {
+        match &self.file {
+            Some(file) => write_state(file, state).map_err(|e| format!("Error al guardar el term en {}: {}", file.display(), e)),
+            None => Ok(()),
+        }
+    }
+
+    // ? el estado pasa a ser `next` aunque no se haya podido guardar: solo los votos necesitan estar en disco
+    fn update(&self, state: &mut TermState, next: TermState) {
+        if let Err(e) = self.save(&next) {
+            error!("{}", e);
+        }
+        *state = next;
+        self.current.store(next.term, Ordering::SeqCst);
+    }
+
+    pub(crate) fn current(&self) -> u64 {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    // ? avanza al siguiente term y lo devuelve
+    pub(crate) fn next(&self) -> u64 {
+        let mut state = self.state();
+        let next = TermState { term: state.term + 1, voted_for: None };
+        self.update(&mut state, next);
+        next.term
+    }
+
+    /// Avanza al siguiente term votandose a si mismo, como candidato de raft, y lo devuelve.
+    ///
+    /// # Errors
+    /// Si no se puede guardar el voto no se postula: el term queda como estaba.
+    pub(crate) fn campaign(&self, candidate: u32) -> Result<u64, String> {
+        let mut state = self.state();
+        let next = TermState { term: state.term + 1, voted_for: Some(candidate) };
+        self.save(&next)?;
+        *state = next;
+        self.current.store(next.term, Ordering::SeqCst);
+        Ok(next.term)
+    }
+
+    // ? adopta el term recibido si es mayor al actual. Devuelve el term que habia antes.
+    pub(crate) fn observe(&self, term: u64) -> u64 {
+        let mut state = self.state();
+        let previous = state.term;
+        if term > previous {
+            self.update(&mut state, TermState { term, voted_for: None });
+        }
+        previous
+    }
+
+    /// Vota a `candidate` en `term` si es el term actual y no se voto a otro en el. Devuelve si se voto y el term actual.
+    ///
+    /// Si el voto no se puede guardar en disco se niega: tras un reinicio el nodo podria votar a otro en el mismo term.
+    pub(crate) fn grant_vote(&self, term: u64, candidate: u32) -> (bool, u64) {
+        let mut state = self.state();
+        if term > state.term {
+            self.update(&mut state, TermState { term, voted_for: None });
+        }
+
+        let grant = term == state.term && state.voted_for.is_none_or(|voted| voted == candidate);
+        if !grant || state.voted_for == Some(candidate) {
+            return (grant, state.term);
+        }
+
+        let next = TermState { term: state.term, voted_for: Some(candidate) };
+        if let Err(e) = self.save(&next) {
+            error!("{}. No voto a {} en el term {}.", e, candidate, state.term);
+            return (false, state.term);
+        }
+        *state = next;
+        (true, state.term)
+    }
+}
+
+fn write_state(file: &Path, state: &TermState) -> std::io::Result<()> {
+    let voted_for = state.voted_for.map_or("-".to_string(), |id| id.to_string());
+    write_file_synced(file, &format!("{} {}\n", state.term, voted_for))
+}
@@ -0,0 +1,411 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::consts::{ELECTION_MSG, FAULTS_MSG, HEARTBEAT_MSG, LOG_MSG, NEW_LEADER_ANSWER, NEW_LIDER_MSG, PROPOSE_LEADER_MSG, PRE_VOTE_GRANTED_ANSWER, PRE_VOTE_MSG, SHUTDOWN_MSG, START_ELECTION_MSG, STATUS_JSON_MSG, STATUS_MSG, STEP_DOWN_MSG, TAKE_OVER_MSG, TRANSFER_LEADER_MSG};
+use crate::election::{adoption_messages, answer_leader_proposal, is_leader_alive, judge_leader_claim, pre_vote_answer, ClaimVerdict, LeaderClaim, Term, PRE_VOTE_TIMEOUT};
+use crate::healthchecker::{HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT};
+use crate::journal::{self, Event as JournalEvent};
+use crate::listener::{choose_transfer_target, configure_faults, configure_log};
+use crate::metrics::{record_election_started, record_heartbeat_received, HEARTBEATS_MISSED, HEARTBEATS_SENT};
+use crate::procceses_list_handler::{apply_message, parse_process_id};
+use crate::process::Process;
+use crate::status::{node_status, record_contact, status_as_text};
+use crate::work_thread::WorkCommand;
+
+// * Protocolo del nodo como maquina de estados: cada `Event` (un mensaje recibido, la respuesta a un pedido, un timer
+// * vencido) se procesa de a uno y devuelve las `Action` de red, timers y trabajo que el reactor tiene que ejecutar. La
+// * maquina no toca sockets ni lee el reloj: el tiempo llega con cada evento.
+// * Cubre la eleccion bully con pre vote, los heartbeats, la resolucion de split brain y los comandos de administracion.
+// * No es pura: el term se adopta y avanza en `handle`, y con --state-file eso escribe y sincroniza el archivo antes de
+// * seguir. Los logs, las metricas, el journal, el estado de FAULTS y el nivel de log tambien se cambian directamente.
+
+// ? la misma espera que bully por la respuesta a ELECTION
+const ELECTION_ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Id de un pedido hecho con `Action::Request`; su respuesta vuelve en `Event::Answer` con el mismo id.
+pub(crate) type RequestId = u64;
+
+/// A quien responder un mensaje recibido: la conexion y, si llego por una sesion, el id del frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ReplyTo {
+    pub(crate) conn: u64,
+    pub(crate) frame: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Timer {
+    // ? el lider manda heartbeats
+    Heartbeat,
+    // ? no llego un heartbeat a tiempo. Lleva la generacion vigente al agendarlo: los de generaciones viejas se ignoran
+    HeartbeatTimeout(u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Event {
+    Started,
+    Message { from: ReplyTo, text: String },
+    Answer { request: RequestId, from: u32, answer: Result<String, String> },
+    Timer(Timer),
+    // ? hay que apagar el nodo (señal o comando SHUTDOWN)
+    Shutdown,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Action {
+    Reply { to: ReplyTo, text: String },
+    // ? mensaje sin respuesta
+    Send { to: u32, text: String },
+    // ? si no hay respuesta en `timeout`, vuelve un `Event::Answer` con error
+    Request { id: RequestId, to: u32, text: String, timeout: Duration },
+    SetTimer { after: Duration, timer: Timer },
+    // ? pedido al hilo de trabajo, que sigue atendiendo los trips por su cuenta
+    Work(WorkCommand),
+    // ? se pidio apagar el nodo por el canal de administracion
+    Shutdown,
+    // ? el nodo termino: el reactor envia lo pendiente y sale
+    Stop,
+}
+
+// ? consulta en curso a otros nodos: a quienes falta escuchar y cuantos estuvieron de acuerdo
+#[derive(Default)]
+struct Round {
+    waiting: BTreeSet<RequestId>,
+    agreed: usize,
+}
+
+/// Estado del protocolo de un nodo. Solo lo toca el loop.
+pub(crate) struct Node {
+    processes: Vec<Process>,
+    term: Arc<Term>,
+    // ? ultimo heartbeat del lider, con el tiempo de los eventos
+    last_heartbeat: Option<Duration>,
+    heartbeat_generation: u64,
+    pre_vote: Option<Round>,
+    election: Option<Round>,
+    next_request: RequestId,
+}
+
+impl Node {
+    pub(crate) fn new(processes: Vec<Process>, term: Arc<Term>) -> Node {
+        Node { processes, term, last_heartbeat: None, heartbeat_generation: 0, pre_vote: None, election: None, next_request: 0 }
+    }
+
+    pub(crate) fn processes(&self) -> &[Process] {
+        &self.processes
+    }
+
+    // ? el lider junto al term en el que se anuncio
+    pub(crate) fn leader(&self) -> Option<(u32, Option<u64>)> {
+        self.processes.iter().find(|process| process.leader).map(|leader| (leader.id, leader.leader_term))
+    }
+
+    fn me(&self) -> Option<&Process> {
+        self.processes.iter().find(|process| process.me)
+    }
+
+    /// Procesa `event`, ocurrido en `now`, y devuelve lo que hay que hacer en orden.
+    pub(crate) fn handle(&mut self, now: Duration, event: Event) -> Vec<Action> {
+        let mut actions = Vec::new();
+        match event {
+            Event::Started => {
+                actions.push(Action::SetTimer { after: HEARTBEAT_INTERVAL, timer: Timer::Heartbeat });
+                actions.push(Action::SetTimer { after: HEARTBEAT_TIMEOUT, timer: Timer::HeartbeatTimeout(self.heartbeat_generation) });
+            }
+            Event::Message { from, text } => {
+                debug!("Mensaje recibido: {}", text);
+                // ? primero la respuesta y despues lo que el mensaje desencadena
+                let mut after = Vec::new();
+                let answer = self.answer(now, &text, &mut after);
+                if answer == "error" {
+                    error!("Error al procesar mensaje: {}", text);
+                }
+                actions.push(Action::Reply { to: from, text: answer });
+                actions.extend(after);
+            }
+            Event::Answer { request, from, answer } => self.on_answer(request, from, answer, &mut actions),
+            Event::Timer(Timer::Heartbeat) => self.send_heartbeats(&mut actions),
+            Event::Timer(Timer::HeartbeatTimeout(generation)) => self.on_heartbeat_timeout(generation, &mut actions),
+            Event::Shutdown => {
+                self.step_down(&mut actions);
+                actions.push(Action::Stop);
+            }
+        }
+        actions
+    }
+
+    fn answer(&mut self, now: Duration, message: &str, after: &mut Vec<Action>) -> String {
+        if message == START_ELECTION_MSG {
+            // ? bully: respondemos que tomamos la eleccion y la iniciamos nosotros
+            self.start_election(after);
+            ELECTION_MSG.to_string()
+        } else if message.starts_with(PROPOSE_LEADER_MSG) {
+            // ? un nodo del runtime de hilos con --quorum majority pregunta antes de anunciarse
+            answer_leader_proposal(message, &self.term)
+        } else if message.starts_with(NEW_LIDER_MSG) || message.starts_with(STEP_DOWN_MSG) {
+            if parse_process_id(message).is_none() {
+                return "error".to_string();
+            }
+            // ? "NEW LEADER {pid} {term}": nos quedamos con el term si es mas nuevo
+            if let Some(term) = message.split_whitespace().nth(3).and_then(|term| term.parse().ok()) {
+                self.term.observe(term);
+            }
+
+            apply_message(&mut self.processes, message);
+            // ? si el lider renuncio hay que elegir uno nuevo
+            if message.starts_with(STEP_DOWN_MSG) {
+                self.start_election(after);
+            }
+            NEW_LEADER_ANSWER.to_string()
+        } else if message.starts_with(HEARTBEAT_MSG) {
+            self.on_heartbeat(now, message, after)
+        } else if message.starts_with(PRE_VOTE_MSG) {
+            pre_vote_answer(&self.processes, self.last_heartbeat, now)
+        } else if let Some(target) = message.strip_prefix(TRANSFER_LEADER_MSG) {
+            match choose_transfer_target(target.trim(), &self.processes) {
+                Ok(target) => {
+                    after.push(Action::Work(WorkCommand::TransferLeadership(target)));
+                    format!("OK {}", target)
+                }
+                Err(answer) => answer,
+            }
+        } else if message == TAKE_OVER_MSG {
+            self.take_over(after);
+            "ok".to_string()
+        } else if message == STATUS_MSG || message == STATUS_JSON_MSG {
+            let since_heartbeat = self.last_heartbeat.map(|last| now.saturating_sub(last));
+            let status = node_status(&self.processes, since_heartbeat, self.term.current());
+            if message == STATUS_JSON_MSG {
+                status.to_string()
+            } else {
+                status_as_text(&status)
+            }
+        } else if let Some(spec) = message.strip_prefix(FAULTS_MSG) {
+            configure_faults(spec.trim(), &self.processes)
+        } else if let Some(setting) = message.strip_prefix(LOG_MSG) {
+            configure_log(setting.trim())
+        } else if message == SHUTDOWN_MSG {
+            info!("Se recibio un pedido de apagado.");
+            after.push(Action::Shutdown);
+            "ok".to_string()
+        } else {
+            warn!("Mensaje desconocido: {}", message);
+            "error".to_string()
+        }
+    }
+
+    // ? "HEARTBEAT {lider} {term}": si otro nodo tambien se cree lider se resuelve el conflicto
+    fn on_heartbeat(&mut self, now: Duration, message: &str, after: &mut Vec<Action>) -> String {
+        record_heartbeat_received();
+        if let Some(claim) = LeaderClaim::parse(message) {
+            record_contact(claim.leader, true);
+            let leader_alive = is_leader_alive(self.last_heartbeat, now);
+            self.term.observe(claim.term);
+            // ? solo cuentan los heartbeats de nuestro lider: los de otro no deben evitar que detectemos que el nuestro se cayo
+            match judge_leader_claim(&claim, leader_alive, &self.processes) {
+                ClaimVerdict::Current => {}
+                ClaimVerdict::Ignore => return "ok".to_string(),
+                ClaimVerdict::Adopt => {
+                    for message in adoption_messages(&claim, &self.processes) {
+                        apply_message(&mut self.processes, &message);
+                    }
+                    after.push(Action::Work(WorkCommand::Resync(claim.leader)));
+                }
+            }
+        }
+
+        // ? el timeout vuelve a contar desde este heartbeat; el timer anterior queda viejo
+        self.last_heartbeat = Some(now);
+        self.heartbeat_generation += 1;
+        after.push(Action::SetTimer { after: HEARTBEAT_TIMEOUT, timer: Timer::HeartbeatTimeout(self.heartbeat_generation) });
+        "ok".to_string()
+    }
+
+    fn on_answer(&mut self, request: RequestId, from: u32, answer: Result<String, String>, actions: &mut Vec<Action>) {
+        if let Some(round) = self.pre_vote.as_mut().filter(|round| round.waiting.contains(&request)) {
+            round.waiting.remove(&request);
+            match &answer {
+                Ok(answer) if answer == PRE_VOTE_GRANTED_ANSWER => round.agreed += 1,
+                Ok(answer) => debug!("{} todavía ve al líder: {}", from, answer),
+                Err(e) => warn!("{} no respondió: {}", from, e),
+            }
+            self.finish_pre_vote_if_done(actions);
+        } else if let Some(round) = self.election.as_mut().filter(|round| round.waiting.contains(&request)) {
+            round.waiting.remove(&request);
+            // ? solo cuenta la respuesta de quien toma la eleccion
+            match &answer {
+                Ok(answer) if answer == ELECTION_MSG => {
+                    debug!("Respuesta recibida de {}: {}", from, answer);
+                    round.agreed += 1;
+                }
+                Ok(answer) => warn!("Respuesta inesperada de {}: {}", from, answer),
+                Err(e) => warn!("Error o timeout esperando respuesta de {}: {}", from, e),
+            }
+            self.finish_election_if_done(actions);
+        } else {
+            trace!("Respuesta de {} a un pedido que ya no se espera", from);
+        }
+    }
+
+    fn request(&mut self, to: u32, text: &str, timeout: Duration, actions: &mut Vec<Action>) -> RequestId {
+        self.next_request += 1;
+        actions.push(Action::Request { id: self.next_request, to, text: text.to_string(), timeout });
+        self.next_request
+    }
+
+    fn send_heartbeats(&mut self, actions: &mut Vec<Action>) {
+        actions.push(Action::SetTimer { after: HEARTBEAT_INTERVAL, timer: Timer::Heartbeat });
+        // ? un lider siempre se anuncia con el term en el que fue elegido
+        let (my_id, leader_term) = match self.me() {
+            Some(me) if me.leader => (me.id, me.leader_term.unwrap_or(0)),
+            _ => return,
+        };
+
+        trace!("Enviando heartbeat a los demas procesos...");
+        // ? el heartbeat lleva quien soy y el term en el que me eligieron, para que otro lider pueda detectar el conflicto
+        let msg = format!("{} {} {}", HEARTBEAT_MSG, my_id, leader_term);
+        for process in self.processes.iter().filter(|process| !process.leader && !process.me) {
+            HEARTBEATS_SENT.increment();
+            actions.push(Action::Send { to: process.id, text: msg.clone() });
+        }
+    }
+
+    fn on_heartbeat_timeout(&mut self, generation: u64, actions: &mut Vec<Action>) {
+        if generation != self.heartbeat_generation {
+            return;
+        }
+        actions.push(Action::SetTimer { after: HEARTBEAT_TIMEOUT, timer: Timer::HeartbeatTimeout(generation) });
+        if self.me().is_some_and(|me| me.leader) {
+            return;
+        }
+
+        // ? primero confirmo con el resto que el lider se perdio (pre vote) y recien ahi inicio una eleccion
+        warn!("No se recibió heartbeat en el tiempo esperado. TIMEOUT. Consultando si el resto también perdió al líder...");
+        HEARTBEATS_MISSED.increment();
+        journal::record(JournalEvent::HeartbeatMissed);
+        self.start_pre_vote(actions);
+    }
+
+    fn start_pre_vote(&mut self, actions: &mut Vec<Action>) {
+        if self.pre_vote.is_some() || self.election.is_some() {
+            return;
+        }
+        let my_id = match self.me() {
+            Some(me) => me.id,
+            None => return,
+        };
+
+        let request = format!("{} {}", PRE_VOTE_MSG, my_id);
+        let others: Vec<u32> = self.processes.iter().filter(|process| !process.me).map(|process| process.id).collect();
+        let mut round = Round { waiting: BTreeSet::new(), agreed: 1 };
+        for to in others {
+            round.waiting.insert(self.request(to, &request, PRE_VOTE_TIMEOUT, actions));
+        }
+        self.pre_vote = Some(round);
+        self.finish_pre_vote_if_done(actions);
+    }
+
+    fn finish_pre_vote_if_done(&mut self, actions: &mut Vec<Action>) {
+        let agreed = match &self.pre_vote {
+            Some(round) if round.waiting.is_empty() => round.agreed,
+            _ => return,
+        };
+        self.pre_vote = None;
+
+        // ? la mayoria es sobre el cluster configurado, contandome
+        info!("{} de {} nodos perdieron al líder", agreed, self.processes.len());
+        if agreed * 2 > self.processes.len() {
+            info!("La mayoría perdió al líder. Iniciando elección de líder...");
+            self.start_election(actions);
+        } else {
+            info!("La mayoría todavía ve al líder. No se inicia una elección.");
+        }
+    }
+
+    fn start_election(&mut self, actions: &mut Vec<Action>) {
+        if self.election.is_some() {
+            debug!("Ya hay una eleccion en curso");
+            return;
+        }
+        let (observer, my_rank) = match self.me() {
+            Some(me) => (me.is_observer(), me.election_rank()),
+            None => {
+                error!("No se encontro el proceso actual en la lista de procesos");
+                return;
+            }
+        };
+
+        info!("Iniciando eleccion de lider...");
+        record_election_started();
+        journal::record(JournalEvent::ElectionStarted { strategy: "bully" });
+
+        // ? solo le avisamos a los nodos elegibles de mejor rango que yo; si yo no puedo ser lider, a todos los elegibles
+        let candidates: Vec<u32> = self.processes.iter()
+            .filter(|process| !process.is_observer() && !process.me && (process.election_rank() > my_rank || observer))
+            .map(|process| process.id)
+            .collect();
+        let mut round = Round::default();
+        for to in candidates {
+            debug!("Enviando mensaje de ELECTION a {}", to);
+            round.waiting.insert(self.request(to, START_ELECTION_MSG, ELECTION_ANSWER_TIMEOUT, actions));
+        }
+        self.election = Some(round);
+        self.finish_election_if_done(actions);
+    }
+
+    fn finish_election_if_done(&mut self, actions: &mut Vec<Action>) {
+        let answers = match &self.election {
+            Some(round) if round.waiting.is_empty() => round.agreed,
+            _ => return,
+        };
+        self.election = None;
+
+        // ? un nodo no elegible nunca se autoproclama lider, espera que lo haga otro
+        let observer = self.me().is_some_and(Process::is_observer);
+        if answers == 0 && observer {
+            info!("No se recibieron respuestas, pero este nodo es observador y no puede ser líder.");
+        } else if answers == 0 {
+            info!("No se recibieron respuestas. Autoproclamandose líder...");
+            self.become_leader(actions);
+        }
+    }
+
+    // ? me marco como lider de un term nuevo y se lo aviso al resto
+    fn become_leader(&mut self, actions: &mut Vec<Action>) {
+        let my_id = match self.me() {
+            Some(me) => me.id,
+            None => return,
+        };
+
+        let msg = format!("{} {} {}", NEW_LIDER_MSG, my_id, self.term.next());
+        apply_message(&mut self.processes, &msg);
+        info!("Me setee como lider. Avisando al resto");
+        for process in self.processes.iter().filter(|process| !process.me) {
+            actions.push(Action::Send { to: process.id, text: msg.clone() });
+        }
+    }
+
+    // ? el lider me traspaso el liderazgo: me anuncio como nuevo lider en un term nuevo
+    fn take_over(&mut self, actions: &mut Vec<Action>) {
+        if self.me().is_some_and(Process::is_observer) {
+            info!("Me traspasaron el liderazgo pero este nodo es observador, no puede ser líder.");
+            return;
+        }
+
+        info!("El lider me traspasó el liderazgo. Anunciándome como nuevo lider...");
+        self.become_leader(actions);
+    }
+
+    // ? si soy lider, aviso al resto que renuncio para que elijan otro sin esperar el timeout de heartbeat
+    fn step_down(&self, actions: &mut Vec<Action>) {
+        let my_id = match self.me() {
+            Some(me) if me.leader => me.id,
+            _ => return,
+        };
+
+        info!("Soy lider. Avisando al resto que renuncio...");
+        let msg = format!("{} {}", STEP_DOWN_MSG, my_id);
+        for process in self.processes.iter().filter(|process| !process.me) {
+            actions.push(Action::Send { to: process.id, text: msg.clone() });
+        }
+    }
+}
@@ -0,0 +1,35 @@
+mod machine;
+mod poll;
+mod reactor;
+mod timer_wheel;
+#[cfg(test)]
+mod tests;
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use crate::election::ElectionEnv;
+use crate::process::Process;
+use crate::utils::tcp::get_tcp_listener_or_kill_process;
+use crate::work_thread::WorkCommand;
+use machine::Node;
+
+// * Runtime alternativo del nodo (--runtime event-loop): en lugar de los hilos de listener, heartbeat, eleccion y lista
+// * de procesos coordinados por canales, un solo loop atiende los sockets no bloqueantes y los timers, y todo el estado
+// * del protocolo vive en una maquina de estados que recibe eventos de a uno. El orden entre cambios de lider,
+// * heartbeats y respuestas queda fijado por el orden de los eventos.
+// * El hilo de trabajo (trips) y el de metricas siguen aparte: leen una copia de la lista de procesos que el loop
+// * actualiza cuando cambia el lider.
+
+/// Corre el protocolo del nodo en el hilo actual hasta que se pida apagarlo.
+///
+/// `processes` es la lista de procesos del nodo, que pasa a ser de la maquina de estados; `mirror` es la copia
+/// que leen los demas hilos.
+pub(crate) fn run(bind_ip: IpAddr, port: u16, processes: Vec<Process>, mirror: Arc<RwLock<Vec<Process>>>, env: &ElectionEnv, work_tx: Sender<WorkCommand>) -> Result<(), String> {
+    let listener = get_tcp_listener_or_kill_process(bind_ip, port);
+    let machine = Node::new(processes, Arc::clone(&env.term));
+    let reactor = reactor::Reactor::new(listener, machine, Arc::clone(&env.clock), mirror, work_tx)?;
+
+    info!("Escuchando conexiones de otros nodos en {} con el runtime event-loop", SocketAddr::new(bind_ip, port));
+    reactor.run()
+}
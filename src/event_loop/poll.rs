@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+// * Espera de disponibilidad sobre varios sockets a la vez con poll(2), sin hilos ni dependencias externas.
+// * En plataformas sin poll se duerme un instante y se dan todos los sockets por listos: los sockets no bloquean,
+// * asi que leer o escribir de mas solo devuelve WouldBlock.
+
+/// Que se quiere saber de un socket.
+#[derive(Clone, Copy)]
+pub(crate) struct Interest {
+    pub(crate) readable: bool,
+    pub(crate) writable: bool,
+}
+
+/// Que se puede hacer con un socket sin bloquear. Un error o un cierre del otro lado cuentan como lectura lista:
+/// la lectura es la que lo informa.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Readiness {
+    pub(crate) readable: bool,
+    pub(crate) writable: bool,
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io;
+    use std::os::fd::{AsRawFd, RawFd};
+    use std::os::raw::{c_int, c_short};
+    use std::time::Duration;
+    use super::{Interest, Readiness};
+
+    const POLLIN: c_short = 0x1;
+    const POLLOUT: c_short = 0x4;
+    const POLLERR: c_short = 0x8;
+    const POLLHUP: c_short = 0x10;
+    const POLLNVAL: c_short = 0x20;
+
+    #[cfg(target_os = "linux")]
+    type NfdsT = std::os::raw::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    type NfdsT = std::os::raw::c_uint;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: RawFd,
+        events: c_short,
+        revents: c_short,
+    }
+
+    extern "C" {
+        fn poll(fds: *mut PollFd, nfds: NfdsT, timeout: c_int) -> c_int;
+    }
+
+    pub(crate) trait Source: AsRawFd {}
+
+    impl<T: AsRawFd> Source for T {}
+
+    #[derive(Default)]
+    pub(crate) struct Poller {
+        fds: Vec<PollFd>,
+    }
+
+    impl Poller {
+        pub(crate) fn register(&mut self, source: &dyn Source, interest: Interest) -> usize {
+            let mut events = 0;
+            if interest.readable {
+                events |= POLLIN;
+            }
+            if interest.writable {
+                events |= POLLOUT;
+            }
+            self.fds.push(PollFd { fd: source.as_raw_fd(), events, revents: 0 });
+            self.fds.len() - 1
+        }
+
+        pub(crate) fn wait(&mut self, timeout: Duration) -> Result<(), String> {
+            let millis = timeout.as_millis().min(c_int::MAX as u128) as c_int;
+            let ready = unsafe { poll(self.fds.as_mut_ptr(), self.fds.len() as NfdsT, millis) };
+            if ready >= 0 {
+                return Ok(());
+            }
+
+            // ? una señal (por ejemplo SIGTERM) corta la espera: no es un error, el loop revisa si hay que apagarse
+            let error = io::Error::last_os_error();
+            for fd in self.fds.iter_mut() {
+                fd.revents = 0;
+            }
+            match error.kind() {
+                io::ErrorKind::Interrupted => Ok(()),
+                _ => Err(format!("Error en poll: {}", error)),
+            }
+        }
+
+        pub(crate) fn readiness(&self, index: usize) -> Readiness {
+            let revents = self.fds.get(index).map_or(0, |fd| fd.revents);
+            Readiness {
+                readable: revents & (POLLIN | POLLERR | POLLHUP | POLLNVAL) != 0,
+                writable: revents & POLLOUT != 0,
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::thread;
+    use std::time::Duration;
+    use super::{Interest, Readiness};
+
+    // ? sin poll, la espera es fija y corta
+    const FALLBACK_WAIT: Duration = Duration::from_millis(10);
+
+    pub(crate) trait Source {}
+
+    impl<T> Source for T {}
+
+    #[derive(Default)]
+    pub(crate) struct Poller {
+        interests: Vec<Interest>,
+    }
+
+    impl Poller {
+        pub(crate) fn register(&mut self, _source: &dyn Source, interest: Interest) -> usize {
+            self.interests.push(interest);
+            self.interests.len() - 1
+        }
+
+        pub(crate) fn wait(&mut self, timeout: Duration) -> Result<(), String> {
+            thread::sleep(timeout.min(FALLBACK_WAIT));
+            Ok(())
+        }
+
+        pub(crate) fn readiness(&self, index: usize) -> Readiness {
+            let interest = self.interests.get(index).copied().unwrap_or(Interest { readable: false, writable: false });
+            Readiness { readable: interest.readable, writable: interest.writable }
+        }
+    }
+}
+
+pub(crate) use sys::Source;
+
+/// Conjunto de sockets a esperar en una vuelta del loop. Se arma de nuevo en cada vuelta.
+#[derive(Default)]
+pub(crate) struct Poll {
+    poller: sys::Poller,
+}
+
+impl Poll {
+    pub(crate) fn new() -> Poll {
+        Poll::default()
+    }
+
+    /// Agrega un socket y devuelve su posicion, para consultar despues con `readiness`.
+    pub(crate) fn register(&mut self, source: &dyn Source, interest: Interest) -> usize {
+        self.poller.register(source, interest)
+    }
+
+    /// Espera hasta que algun socket este listo o pase `timeout`.
+    pub(crate) fn wait(&mut self, timeout: Duration) -> Result<(), String> {
+        self.poller.wait(timeout)
+    }
+
+    pub(crate) fn readiness(&self, index: usize) -> Readiness {
+        self.poller.readiness(index)
+    }
+}
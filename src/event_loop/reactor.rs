@@ -0,0 +1,812 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use crate::{allowlist, auth};
+use crate::clock::Clock;
+use crate::connections::{backoff, report_state, CONNECT_TIMEOUT, IDLE_TIMEOUT};
+use crate::consts::{BUSY_ANSWER, SESSION_ANSWER, SESSION_MSG, UNAUTHORIZED_ANSWER, FORBIDDEN_ANSWER};
+use crate::event_loop::machine::{Action, Event, Node, ReplyTo, RequestId, Timer};
+use crate::event_loop::poll::{Interest, Poll, Readiness};
+use crate::event_loop::timer_wheel::TimerWheel;
+use crate::journal::{self, Event as JournalEvent};
+use crate::listener::{CONNECTION_TIMEOUT, MAX_SESSIONS, SESSION_IDLE_TIMEOUT};
+use crate::worker_pool::MAX_PENDING_CONNECTIONS;
+use crate::metrics::{record_connection_error, CONNECTIONS_SHED};
+use crate::process::Process;
+use crate::shutdown::{is_shutdown_requested, request_shutdown};
+use crate::status::record_contact;
+use crate::utils::faults;
+use crate::utils::peer_addr::PeerAddr;
+use crate::utils::tcp::{get_peer_addr, get_peer_connection_timeout, split_frame, write_frame, MAX_FRAME_SIZE};
+use crate::work_thread::WorkCommand;
+
+// * Todos los sockets del nodo son no bloqueantes y un solo loop espera con poll a que alguno este listo. Lo que llega
+// * se convierte en eventos para la maquina de estados, y sus acciones se ejecutan aca: escribir respuestas, enviar
+// * por las sesiones con los otros nodos y agendar timers en la rueda.
+// * Se habla el mismo protocolo que el runtime de hilos (mensajes sueltos y sesiones con frames), por lo que ambos
+// * runtimes pueden convivir en un cluster.
+
+// ? resolucion de la rueda de timers y cantidad de ranuras: una vuelta cada ~10 segundos
+const TICK: Duration = Duration::from_millis(10);
+const WHEEL_SLOTS: usize = 1024;
+// ? espera maxima de poll, para revisar seguido si se pidio apagar el nodo
+const MAX_POLL_WAIT: Duration = Duration::from_millis(100);
+// ? espera maxima para enviar las renuncias y respuestas pendientes al apagar
+const SHUTDOWN_FLUSH: Duration = Duration::from_secs(1);
+// ? conexiones entrantes abiertas a la vez: las sesiones y las de un solo mensaje
+const MAX_CONNECTIONS: usize = MAX_SESSIONS + MAX_PENDING_CONNECTIONS;
+// ? datos recibidos sin procesar que se toleran por conexion
+const MAX_INPUT: usize = 2 * MAX_FRAME_SIZE;
+
+// ? lo que se puede agendar en la rueda
+enum Scheduled {
+    Machine(Timer),
+    // ? vencio la espera de la respuesta al frame `frame` enviado a `peer`
+    RequestTimeout { peer: u32, frame: u64, timeout: Duration },
+    // ? mensaje demorado por la latencia inyectada
+    Delayed { peer: u32, payload: Vec<u8>, request: Option<(RequestId, Duration)> },
+}
+
+#[derive(PartialEq)]
+enum InboundMode {
+    // ? todavia no llego el primer mensaje
+    New,
+    // ? un solo mensaje: se responde y se cierra
+    Single,
+    Session,
+}
+
+// ? conexion que abrio otro nodo (o concurride-ctl)
+struct Inbound {
+    stream: TcpStream,
+    mode: InboundMode,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    // ? se cierra cuando termine de escribir
+    closing: bool,
+    last_activity: Duration,
+    // ? viene de una IP de la lista de procesos
+    listed: bool,
+}
+
+enum Link {
+    Idle,
+    // ? un hilo aparte esta conectando, para que un nodo inalcanzable no frene al loop
+    Connecting { failures: u32, result: Receiver<Result<TcpStream, String>> },
+    // ? se pidio la sesion y se espera el SESSION OK
+    Handshake(TcpStream),
+    Connected(TcpStream),
+    Backoff { failures: u32, retry_at: Duration },
+}
+
+// ? sesion con otro nodo, para los mensajes que este nodo le envia
+struct Outbound {
+    addr: PeerAddr,
+    link: Link,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    // ? frames que esperan a que el otro nodo acepte la sesion
+    queued: Vec<u8>,
+    // ? pedidos en vuelo, por id de frame
+    pending: BTreeMap<u64, RequestId>,
+    last_used: Duration,
+}
+
+#[derive(Clone, Copy)]
+enum Key {
+    Listener,
+    Inbound(u64),
+    Outbound(u32),
+}
+
+/// El loop del nodo: dueño de los sockets, de la rueda de timers y de la maquina de estados.
+pub(crate) struct Reactor {
+    machine: Node,
+    clock: Arc<dyn Clock>,
+    // ? None desde que se empieza a apagar
+    listener: Option<TcpListener>,
+    inbound: BTreeMap<u64, Inbound>,
+    outbound: BTreeMap<u32, Outbound>,
+    wheel: TimerWheel<Scheduled>,
+    events: VecDeque<Event>,
+    next_conn: u64,
+    next_frame: u64,
+    // ? copia de la lista de procesos para el hilo de trabajo y las metricas
+    mirror: Arc<RwLock<Vec<Process>>>,
+    published_leader: Option<(u32, Option<u64>)>,
+    work_tx: Sender<WorkCommand>,
+    shutting_down: bool,
+    // ? hasta cuando se espera a enviar lo pendiente una vez que la maquina termino
+    stop_deadline: Option<Duration>,
+}
+
+// ? lee todo lo disponible sin bloquear. Devuelve false si el otro lado cerro la conexion.
+fn read_available(stream: &mut TcpStream, input: &mut Vec<u8>) -> Result<bool, String> {
+    let mut buffer = [0u8; 4096];
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Ok(false),
+            Ok(read) => {
+                input.extend_from_slice(&buffer[..read]);
+                if input.len() > MAX_INPUT {
+                    return Err("se recibieron demasiados datos sin procesar".to_string());
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(true),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("Error al leer: {}", e)),
+        }
+    }
+}
+
+// ? un mensaje sin firma valida, o que no es de cliente y viene de fuera de la lista de procesos, se responde aca y no
+// ? llega a la maquina
+fn admit(conn: &mut Inbound, message: &str, frame: Option<u64>) -> Option<String> {
+    let answer = match auth::open(message) {
+        Ok(text) if conn.listed || allowlist::is_client_message(&text) => return Some(text),
+        Ok(_) => {
+            allowlist::record_refused(&conn.stream, "el mensaje");
+            FORBIDDEN_ANSWER
+        }
+        Err(e) => {
+            warn!("Mensaje rechazado de {}: {}", get_peer_addr(&conn.stream).unwrap_or_else(|e| e), e);
+            UNAUTHORIZED_ANSWER
+        }
+    };
+
+    match frame {
+        Some(frame) => {
+            let _ = write_frame(&mut conn.output, frame, answer.as_bytes());
+        }
+        None => {
+            conn.output.extend_from_slice(answer.as_bytes());
+            conn.closing = true;
+        }
+    }
+    None
+}
+
+// ? escribe lo que se pueda sin bloquear; lo que queda espera a que el socket vuelva a estar listo
+fn write_pending(stream: &mut TcpStream, output: &mut Vec<u8>) -> Result<(), String> {
+    while !output.is_empty() {
+        match stream.write(output) {
+            Ok(0) => return Err("la conexión no acepta más datos".to_string()),
+            Ok(written) => {
+                output.drain(..written);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("Error sending message: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+impl Reactor {
+    pub(crate) fn new(listener: TcpListener, machine: Node, clock: Arc<dyn Clock>, mirror: Arc<RwLock<Vec<Process>>>, work_tx: Sender<WorkCommand>) -> Result<Reactor, String> {
+        listener.set_nonblocking(true).map_err(|e| format!("Error al configurar el socket como no bloqueante: {}", e))?;
+
+        let outbound = machine.processes().iter()
+            .filter(|process| !process.me)
+            .map(|process| (process.id, Outbound {
+                addr: process.addr.clone(),
+                link: Link::Idle,
+                input: Vec::new(),
+                output: Vec::new(),
+                queued: Vec::new(),
+                pending: BTreeMap::new(),
+                last_used: Duration::ZERO,
+            }))
+            .collect();
+        let published_leader = machine.leader();
+
+        Ok(Reactor {
+            machine,
+            clock,
+            listener: Some(listener),
+            inbound: BTreeMap::new(),
+            outbound,
+            wheel: TimerWheel::new(TICK, WHEEL_SLOTS),
+            events: VecDeque::new(),
+            next_conn: 0,
+            next_frame: 0,
+            mirror,
+            published_leader,
+            work_tx,
+            shutting_down: false,
+            stop_deadline: None,
+        })
+    }
+
+    /// Corre el loop hasta que el nodo se apague. Solo devuelve error si no se puede seguir esperando en los sockets.
+    pub(crate) fn run(mut self) -> Result<(), String> {
+        self.dispatch(Event::Started);
+
+        loop {
+            if is_shutdown_requested() && !self.shutting_down {
+                // ? primero dejamos de recibir conexiones y despues la maquina renuncia si es lider
+                self.shutting_down = true;
+                self.listener = None;
+                self.dispatch(Event::Shutdown);
+            }
+            if let Some(deadline) = self.stop_deadline {
+                if self.clock.now() >= deadline || self.is_flushed() {
+                    return Ok(());
+                }
+            }
+
+            self.turn()?;
+        }
+    }
+
+    // ? una vuelta: esperar sockets listos, atenderlos, vencer timers y procesar los eventos que quedaron
+    fn turn(&mut self) -> Result<(), String> {
+        let mut poll = Poll::new();
+        let mut keys = Vec::new();
+        if let Some(listener) = &self.listener {
+            keys.push((poll.register(listener, Interest { readable: true, writable: false }), Key::Listener));
+        }
+        for (id, conn) in &self.inbound {
+            let interest = Interest { readable: !conn.closing, writable: !conn.output.is_empty() };
+            keys.push((poll.register(&conn.stream, interest), Key::Inbound(*id)));
+        }
+        for (peer, out) in &self.outbound {
+            if let Link::Handshake(stream) | Link::Connected(stream) = &out.link {
+                let interest = Interest { readable: true, writable: !out.output.is_empty() };
+                keys.push((poll.register(stream, interest), Key::Outbound(*peer)));
+            }
+        }
+
+        // ? las conexiones en curso no tienen socket que esperar: se revisan en cada tick
+        let connecting = self.outbound.values().any(|out| matches!(out.link, Link::Connecting { .. }));
+        let max_wait = if connecting { TICK } else { MAX_POLL_WAIT };
+        let now = self.clock.now();
+        let wait = self.wheel.next_tick().map_or(max_wait, |tick| tick.saturating_sub(now)).min(max_wait);
+        poll.wait(wait)?;
+
+        for (index, key) in keys {
+            let ready = poll.readiness(index);
+            match key {
+                Key::Listener if ready.readable => self.accept(),
+                Key::Listener => {}
+                Key::Inbound(id) => self.service_inbound(id, ready),
+                Key::Outbound(peer) => self.service_outbound(peer, ready),
+            }
+        }
+        self.finish_connects();
+
+        let now = self.clock.now();
+        for scheduled in self.wheel.expire(now) {
+            self.fire(scheduled);
+        }
+        self.close_idle(now);
+        self.drain();
+        Ok(())
+    }
+
+    fn dispatch(&mut self, event: Event) {
+        self.events.push_back(event);
+        self.drain();
+    }
+
+    // ? procesa los eventos de a uno, en orden; ejecutar una accion puede agregar eventos nuevos (un envio que fallo)
+    fn drain(&mut self) {
+        while let Some(event) = self.events.pop_front() {
+            let now = self.clock.now();
+            for action in self.machine.handle(now, event) {
+                self.execute(action, now);
+            }
+        }
+        self.publish();
+    }
+
+    fn execute(&mut self, action: Action, now: Duration) {
+        match action {
+            Action::Reply { to, text } => self.reply(to, &text),
+            Action::Send { to, text } => self.send(to, &text, None),
+            Action::Request { id, to, text, timeout } => self.send(to, &text, Some((id, timeout))),
+            Action::SetTimer { after, timer } => self.wheel.schedule(now + after, Scheduled::Machine(timer)),
+            Action::Work(command) => {
+                if let Err(e) = self.work_tx.send(command) {
+                    error!("Error al enviar el pedido al hilo de trabajo: {}", e);
+                }
+            }
+            Action::Shutdown => request_shutdown(),
+            Action::Stop => self.stop_deadline = Some(now + SHUTDOWN_FLUSH),
+        }
+    }
+
+    // ? el hilo de trabajo y las metricas leen al lider de la copia compartida
+    fn publish(&mut self) {
+        let leader = self.machine.leader();
+        if leader == self.published_leader {
+            return;
+        }
+
+        match self.mirror.write() {
+            Ok(mut guard) => {
+                for process in guard.iter_mut() {
+                    process.leader = leader.is_some_and(|(id, _)| id == process.id);
+                    process.leader_term = leader.filter(|_| process.leader).and_then(|(_, term)| term);
+                }
+                self.published_leader = leader;
+            }
+            Err(e) => error!("Error al obtener el guard write de procesos: {}", e),
+        }
+    }
+
+    fn is_flushed(&self) -> bool {
+        self.inbound.values().all(|conn| conn.output.is_empty())
+            && self.outbound.values().all(|out| out.output.is_empty() && out.queued.is_empty())
+    }
+
+    fn accept(&mut self) {
+        let listener = match &self.listener {
+            Some(listener) => listener,
+            None => return,
+        };
+
+        loop {
+            let (stream, addr) = match listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    error!("Error al aceptar conexión: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = stream.set_nonblocking(true) {
+                error!("Error al configurar el socket como no bloqueante: {}", e);
+                continue;
+            }
+
+            // ? la respuesta es de cortesia: si no se puede escribir enseguida, se descarta
+            if self.inbound.len() >= MAX_CONNECTIONS {
+                CONNECTIONS_SHED.increment();
+                warn!("Nodo sobrecargado, rechazando la conexión de {}", addr);
+                let _ = (&stream).write(BUSY_ANSWER.as_bytes());
+                continue;
+            }
+
+            self.next_conn += 1;
+            let listed = allowlist::is_listed(&stream, self.machine.processes());
+            let conn = Inbound { stream, mode: InboundMode::New, input: Vec::new(), output: Vec::new(), closing: false, last_activity: self.clock.now(), listed };
+            self.inbound.insert(self.next_conn, conn);
+        }
+    }
+
+    fn close_inbound(&mut self, id: u64, reason: &str) {
+        if let Some(conn) = self.inbound.remove(&id) {
+            if conn.mode == InboundMode::Session {
+                let peer = conn.stream.peer_addr().map_or("?".to_string(), |addr| addr.to_string());
+                debug!("Sesión con {} cerrada: {}", peer, reason);
+            }
+        }
+    }
+
+    fn service_inbound(&mut self, id: u64, ready: Readiness) {
+        let now = self.clock.now();
+        let conn = match self.inbound.get_mut(&id) {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        if ready.readable && !conn.closing {
+            let open = match read_available(&mut conn.stream, &mut conn.input) {
+                Ok(open) => open,
+                Err(e) => {
+                    warn!("Error en la conexión entrante: {}", e);
+                    self.close_inbound(id, &e);
+                    return;
+                }
+            };
+            conn.last_activity = now;
+            self.parse_inbound(id);
+            if !open {
+                // ? un mensaje suelto todavia puede responderse; una sesion cerrada ya no espera nada
+                match self.inbound.get(&id) {
+                    Some(conn) if conn.mode == InboundMode::Single => {}
+                    _ => self.close_inbound(id, "el otro nodo cerró la conexión"),
+                }
+                return;
+            }
+        }
+
+        if ready.writable {
+            self.flush_inbound(id);
+        }
+    }
+
+    // ? convierte lo recibido en mensajes para la maquina
+    fn parse_inbound(&mut self, id: u64) {
+        let sessions = self.inbound.values().filter(|conn| conn.mode == InboundMode::Session).count();
+        let conn = match self.inbound.get_mut(&id) {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        if conn.mode == InboundMode::New && !conn.input.is_empty() {
+            let session_request = format!("{}\n", SESSION_MSG);
+            let message = String::from_utf8_lossy(&conn.input).into_owned();
+            if message.trim_end() == SESSION_MSG || conn.input.starts_with(session_request.as_bytes()) {
+                conn.input.drain(..session_request.len().min(conn.input.len()));
+                // ? las sesiones son solo entre nodos
+                if !conn.listed {
+                    allowlist::record_refused(&conn.stream, "la sesión");
+                    conn.output.extend_from_slice(format!("{}\n", FORBIDDEN_ANSWER).as_bytes());
+                    conn.closing = true;
+                } else if sessions >= MAX_SESSIONS {
+                    CONNECTIONS_SHED.increment();
+                    warn!("Demasiadas sesiones abiertas, rechazando una conexión");
+                    conn.output.extend_from_slice(format!("{}\n", BUSY_ANSWER).as_bytes());
+                    conn.closing = true;
+                } else {
+                    conn.mode = InboundMode::Session;
+                    conn.output.extend_from_slice(format!("{}\n", SESSION_ANSWER).as_bytes());
+                }
+            } else {
+                // ? como en el runtime de hilos, el mensaje suelto es lo que llego en la primera lectura
+                conn.input.clear();
+                conn.mode = InboundMode::Single;
+                if let Some(text) = admit(conn, &message, None) {
+                    self.events.push_back(Event::Message { from: ReplyTo { conn: id, frame: None }, text });
+                }
+            }
+        }
+
+        match conn.mode {
+            InboundMode::Session => loop {
+                match split_frame(&conn.input) {
+                    Ok(Some((frame, message, consumed))) => {
+                        conn.input.drain(..consumed);
+                        if let Some(text) = admit(conn, &message, Some(frame)) {
+                            self.events.push_back(Event::Message { from: ReplyTo { conn: id, frame: Some(frame) }, text });
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        self.close_inbound(id, &e);
+                        return;
+                    }
+                }
+            },
+            // ? una conexion de un solo mensaje no espera nada mas
+            InboundMode::Single => conn.input.clear(),
+            InboundMode::New => {}
+        }
+        self.flush_inbound(id);
+    }
+
+    fn flush_inbound(&mut self, id: u64) {
+        let conn = match self.inbound.get_mut(&id) {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        if let Err(e) = write_pending(&mut conn.stream, &mut conn.output) {
+            error!("Error al enviar respuesta: {}", e);
+            self.close_inbound(id, &e);
+            return;
+        }
+        if conn.closing && conn.output.is_empty() {
+            self.close_inbound(id, "respuesta enviada");
+        }
+    }
+
+    fn reply(&mut self, to: ReplyTo, text: &str) {
+        let conn = match self.inbound.get_mut(&to.conn) {
+            Some(conn) => conn,
+            None => {
+                debug!("La conexión se cerró antes de la respuesta: {}", text);
+                return;
+            }
+        };
+
+        match to.frame {
+            Some(frame) => {
+                let _ = write_frame(&mut conn.output, frame, text.as_bytes());
+            }
+            None => {
+                conn.output.extend_from_slice(text.as_bytes());
+                conn.closing = true;
+            }
+        }
+        self.flush_inbound(to.conn);
+    }
+
+    // ? las conexiones sin actividad se cierran con los mismos tiempos que en el runtime de hilos
+    fn close_idle(&mut self, now: Duration) {
+        let expired: Vec<u64> = self.inbound.iter()
+            .filter(|(_, conn)| {
+                let limit = if conn.mode == InboundMode::Session { SESSION_IDLE_TIMEOUT } else { CONNECTION_TIMEOUT };
+                now.saturating_sub(conn.last_activity) > limit
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            self.close_inbound(id, "sin actividad");
+        }
+    }
+
+    // ? un mensaje que no se pudo enviar: si alguien esperaba la respuesta, se le avisa con un error
+    fn fail(&mut self, peer: u32, request: Option<RequestId>, error: String) {
+        record_contact(peer, false);
+        record_connection_error(peer);
+        match request {
+            Some(request) => self.events.push_back(Event::Answer { request, from: peer, answer: Err(error) }),
+            None => warn!("Error enviando mensaje a {}: {}", peer, error),
+        }
+    }
+
+    fn send(&mut self, peer: u32, text: &str, request: Option<(RequestId, Duration)>) {
+        let injected = match self.outbound.get(&peer) {
+            Some(out) => faults::inject(&out.addr, auth::seal(peer, text).as_bytes()),
+            None => Err(format!("no hay un proceso con id {}", peer)),
+        };
+
+        match injected {
+            Ok((payload, latency)) if !latency.is_zero() => {
+                let at = self.clock.now() + latency;
+                self.wheel.schedule(at, Scheduled::Delayed { peer, payload, request });
+            }
+            Ok((payload, _)) => self.write_to_peer(peer, payload, request),
+            Err(e) => self.fail(peer, request.map(|(request, _)| request), e),
+        }
+    }
+
+    fn write_to_peer(&mut self, peer: u32, payload: Vec<u8>, request: Option<(RequestId, Duration)>) {
+        let now = self.clock.now();
+        if let Err(e) = self.open_link(peer, now) {
+            self.fail(peer, request.map(|(request, _)| request), e);
+            return;
+        }
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return,
+        };
+
+        self.next_frame += 1;
+        let frame = self.next_frame;
+        let buffer = if matches!(out.link, Link::Connected(_)) { &mut out.output } else { &mut out.queued };
+        let _ = write_frame(buffer, frame, &payload);
+        out.last_used = now;
+
+        match request {
+            Some((request, timeout)) => {
+                out.pending.insert(frame, request);
+                self.wheel.schedule(now + timeout, Scheduled::RequestTimeout { peer, frame, timeout });
+            }
+            None => record_contact(peer, true),
+        }
+        self.flush_outbound(peer);
+    }
+
+    // ? deja la sesion con `peer` abierta o pidiendose, conectando si hace falta
+    fn open_link(&mut self, peer: u32, now: Duration) -> Result<(), String> {
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return Err(format!("no hay un proceso con id {}", peer)),
+        };
+
+        // ? el otro nodo cierra las sesiones inactivas: la cerramos antes para no escribir en una que se esta cerrando
+        if matches!(out.link, Link::Connected(_)) && now.saturating_sub(out.last_used) >= IDLE_TIMEOUT {
+            self.close_link(peer, "sin actividad", false);
+        }
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return Err(format!("no hay un proceso con id {}", peer)),
+        };
+
+        let failures = match out.link {
+            Link::Connected(_) | Link::Handshake(_) | Link::Connecting { .. } => return Ok(()),
+            Link::Backoff { retry_at, .. } if now < retry_at => {
+                return Err(format!("sin conexión con {}, reintento en {:?}", peer, retry_at - now));
+            }
+            Link::Backoff { failures, .. } => failures,
+            Link::Idle => 0,
+        };
+
+        // ? los frames esperan en `queued` hasta que se acepte la sesion
+        let (result_tx, result) = channel();
+        let addr = out.addr.clone();
+        thread::spawn(move || {
+            let stream = get_peer_connection_timeout(&addr, CONNECT_TIMEOUT)
+                .and_then(|stream| stream.set_nonblocking(true).map(|_| stream).map_err(|e| format!("Error al configurar el socket como no bloqueante: {}", e)));
+            let _ = result_tx.send(stream);
+        });
+        out.link = Link::Connecting { failures, result };
+        out.last_used = now;
+        Ok(())
+    }
+
+    // ? pide la sesion por las conexiones que se terminaron de abrir, y espera antes de reintentar las que fallaron
+    fn finish_connects(&mut self) {
+        let now = self.clock.now();
+        let mut failed = Vec::new();
+        for (peer, out) in self.outbound.iter_mut() {
+            let connected = match &out.link {
+                Link::Connecting { failures, result } => match result.try_recv() {
+                    Ok(connected) => connected.map_err(|e| (*failures, e)),
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Disconnected) => Err((*failures, "el hilo que conectaba terminó sin resultado".to_string())),
+                },
+                _ => continue,
+            };
+
+            match connected {
+                Ok(stream) => {
+                    out.input.clear();
+                    out.output = format!("{}\n", SESSION_MSG).into_bytes();
+                    out.link = Link::Handshake(stream);
+                }
+                Err((failures, e)) => {
+                    let wait = backoff(failures + 1);
+                    out.link = Link::Backoff { failures: failures + 1, retry_at: now + wait };
+                    report_state(*peer, "backoff");
+                    failed.push((*peer, format!("{} (reintento en {:?})", e, wait)));
+                }
+            }
+        }
+
+        for (peer, error) in failed {
+            self.fail_queued(peer, &error);
+        }
+    }
+
+    // ? lo que esperaba a la sesion no se envio: los pedidos fallan con el error de la conexion
+    fn fail_queued(&mut self, peer: u32, error: &str) {
+        let requests = match self.outbound.get_mut(&peer) {
+            Some(out) => {
+                out.queued.clear();
+                std::mem::take(&mut out.pending)
+            }
+            None => return,
+        };
+
+        if requests.is_empty() {
+            self.fail(peer, None, error.to_string());
+        }
+        for request in requests.into_values() {
+            self.fail(peer, Some(request), error.to_string());
+        }
+    }
+
+    // ? cierra la sesion con `peer`; los pedidos en vuelo fallan. Con `failed` se espera antes de reconectar.
+    fn close_link(&mut self, peer: u32, reason: &str, failed: bool) {
+        let now = self.clock.now();
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return,
+        };
+
+        debug!("Sesión con {} cerrada: {}", peer, reason);
+        if failed {
+            let wait = backoff(1);
+            out.link = Link::Backoff { failures: 1, retry_at: now + wait };
+            report_state(peer, "backoff");
+        } else {
+            out.link = Link::Idle;
+            report_state(peer, "idle");
+        }
+        out.input.clear();
+        out.output.clear();
+        out.queued.clear();
+
+        for request in std::mem::take(&mut out.pending).into_values() {
+            self.fail(peer, Some(request), format!("se perdió la conexión con {}: {}", peer, reason));
+        }
+    }
+
+    fn flush_outbound(&mut self, peer: u32) {
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return,
+        };
+
+        let written = match &mut out.link {
+            Link::Handshake(stream) | Link::Connected(stream) => write_pending(stream, &mut out.output),
+            _ => Ok(()),
+        };
+        if let Err(e) = written {
+            self.close_link(peer, &e, false);
+        }
+    }
+
+    fn service_outbound(&mut self, peer: u32, ready: Readiness) {
+        if ready.readable {
+            let read = match self.outbound.get_mut(&peer) {
+                Some(Outbound { link: Link::Handshake(stream) | Link::Connected(stream), input, .. }) => read_available(stream, input),
+                _ => return,
+            };
+            match read {
+                Ok(open) => {
+                    self.parse_outbound(peer);
+                    if !open {
+                        self.close_link(peer, "el otro nodo cerró la conexión", false);
+                        return;
+                    }
+                }
+                Err(e) => {
+                    self.close_link(peer, &e, false);
+                    return;
+                }
+            }
+        }
+
+        if ready.writable {
+            self.flush_outbound(peer);
+        }
+    }
+
+    // ? la respuesta al pedido de sesion y despues las respuestas a los frames enviados
+    fn parse_outbound(&mut self, peer: u32) {
+        let out = match self.outbound.get_mut(&peer) {
+            Some(out) => out,
+            None => return,
+        };
+
+        if matches!(out.link, Link::Handshake(_)) {
+            let end = match out.input.iter().position(|byte| *byte == b'\n') {
+                Some(end) => end,
+                None => return,
+            };
+            let answer = String::from_utf8_lossy(&out.input[..end]).into_owned();
+            out.input.drain(..=end);
+            if answer != SESSION_ANSWER {
+                self.close_link(peer, &format!("{} no aceptó la sesión: {}", peer, answer), true);
+                return;
+            }
+
+            if let Link::Handshake(stream) = std::mem::replace(&mut out.link, Link::Idle) {
+                out.link = Link::Connected(stream);
+            }
+            let queued = std::mem::take(&mut out.queued);
+            out.output.extend_from_slice(&queued);
+            report_state(peer, "connected");
+            debug!("Sesión abierta con {}", peer);
+        }
+
+        let mut answers = Vec::new();
+        loop {
+            match split_frame(&out.input) {
+                Ok(Some((frame, answer, consumed))) => {
+                    out.input.drain(..consumed);
+                    // ? si nadie la espera (un mensaje sin respuesta o un pedido que ya vencio) se descarta
+                    if let Some(request) = out.pending.remove(&frame) {
+                        answers.push((request, answer));
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    self.close_link(peer, &e, false);
+                    break;
+                }
+            }
+        }
+
+        for (request, answer) in answers {
+            record_contact(peer, true);
+            journal::record(JournalEvent::AnswerReceived { from: peer, answer: &answer });
+            self.events.push_back(Event::Answer { request, from: peer, answer: Ok(answer) });
+        }
+        self.flush_outbound(peer);
+    }
+
+    fn fire(&mut self, scheduled: Scheduled) {
+        match scheduled {
+            Scheduled::Machine(timer) => self.events.push_back(Event::Timer(timer)),
+            Scheduled::RequestTimeout { peer, frame, timeout } => {
+                let request = self.outbound.get_mut(&peer).and_then(|out| out.pending.remove(&frame));
+                if let Some(request) = request {
+                    self.fail(peer, Some(request), format!("{} no respondió en {:?}", peer, timeout));
+                }
+            }
+            Scheduled::Delayed { peer, payload, request } => self.write_to_peer(peer, payload, request),
+        }
+    }
+}
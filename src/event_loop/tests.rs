@@ -0,0 +1,221 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::consts::{ELECTION_MSG, NEW_LEADER_ANSWER, PRE_VOTE_GRANTED_ANSWER, START_ELECTION_MSG};
+use crate::election::Term;
+use crate::event_loop::machine::{Action, Event, Node, ReplyTo, Timer};
+use crate::event_loop::timer_wheel::TimerWheel;
+use crate::healthchecker::{HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT};
+use crate::process::Process;
+use crate::utils::peer_addr::{Host, PeerAddr};
+use crate::work_thread::WorkCommand;
+
+// * Pruebas del runtime event-loop sin sockets ni reloj: la maquina de estados recibe eventos armados a mano y se
+// * comparan las acciones que devuelve, y la rueda de timers se avanza con tiempos ficticios.
+
+const FROM: ReplyTo = ReplyTo { conn: 1, frame: Some(1) };
+
+fn node(me: u32, ids: &[u32]) -> Node {
+    let processes = ids.iter().map(|id| {
+        let mut process = Process::new(*id, PeerAddr::new(Host::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)), 7000 + *id as u16));
+        process.me = *id == me;
+        process
+    }).collect();
+    Node::new(processes, Arc::new(Term::new()))
+}
+
+fn leader_id(node: &Node) -> Option<u32> {
+    node.leader().map(|(id, _)| id)
+}
+
+fn message(text: &str) -> Event {
+    Event::Message { from: FROM, text: text.to_string() }
+}
+
+fn sends(actions: &[Action]) -> Vec<(u32, String)> {
+    actions.iter().filter_map(|action| match action {
+        Action::Send { to, text } => Some((*to, text.clone())),
+        _ => None,
+    }).collect()
+}
+
+// ? (id del pedido, destino, mensaje)
+fn requests(actions: &[Action]) -> Vec<(u64, u32, String)> {
+    actions.iter().filter_map(|action| match action {
+        Action::Request { id, to, text, .. } => Some((*id, *to, text.clone())),
+        _ => None,
+    }).collect()
+}
+
+fn reply(actions: &[Action]) -> Option<String> {
+    actions.iter().find_map(|action| match action {
+        Action::Reply { text, .. } => Some(text.clone()),
+        _ => None,
+    })
+}
+
+#[test]
+fn timers_expire_in_deadline_order_across_rotations() {
+    let mut wheel = TimerWheel::new(Duration::from_millis(10), 8);
+    wheel.schedule(Duration::from_millis(30), "b");
+    wheel.schedule(Duration::from_millis(10), "a");
+    wheel.schedule(Duration::from_secs(25), "c");
+
+    assert!(wheel.expire(Duration::from_millis(5)).is_empty());
+    assert_eq!(wheel.expire(Duration::from_millis(30)), vec!["a", "b"]);
+    assert!(wheel.expire(Duration::from_millis(24_990)).is_empty());
+    assert_eq!(wheel.expire(Duration::from_secs(25)), vec!["c"]);
+    assert_eq!(wheel.next_tick(), None);
+}
+
+#[test]
+fn overdue_timer_expires_on_next_tick() {
+    let mut wheel = TimerWheel::new(Duration::from_millis(10), 8);
+    wheel.schedule(Duration::from_millis(200), "primero");
+    assert_eq!(wheel.expire(Duration::from_millis(200)), vec!["primero"]);
+
+    wheel.schedule(Duration::from_millis(50), "atrasado");
+    assert_eq!(wheel.next_tick(), Some(Duration::from_millis(210)));
+    assert_eq!(wheel.expire(Duration::from_millis(210)), vec!["atrasado"]);
+}
+
+#[test]
+fn missed_heartbeat_runs_pre_vote_and_then_bully_election() {
+    let mut node = node(1, &[1, 2, 3]);
+    let started = node.handle(Duration::ZERO, Event::Started);
+    assert_eq!(started, vec![
+        Action::SetTimer { after: HEARTBEAT_INTERVAL, timer: Timer::Heartbeat },
+        Action::SetTimer { after: HEARTBEAT_TIMEOUT, timer: Timer::HeartbeatTimeout(0) },
+    ]);
+
+    let now = HEARTBEAT_TIMEOUT;
+    let pre_vote = requests(&node.handle(now, Event::Timer(Timer::HeartbeatTimeout(0))));
+    assert_eq!(pre_vote.iter().map(|(_, to, text)| (*to, text.as_str())).collect::<Vec<_>>(), vec![(2, "PRE VOTE 1"), (3, "PRE VOTE 1")]);
+
+    // ? con el voto de 2 ya somos mayoria: la eleccion arranca cuando responde el ultimo
+    let granted = node.handle(now, Event::Answer { request: pre_vote[0].0, from: 2, answer: Ok(PRE_VOTE_GRANTED_ANSWER.to_string()) });
+    assert!(granted.is_empty());
+    let election = requests(&node.handle(now, Event::Answer { request: pre_vote[1].0, from: 3, answer: Err("caido".to_string()) }));
+    assert_eq!(election.iter().map(|(_, to, text)| (*to, text.as_str())).collect::<Vec<_>>(), vec![(2, START_ELECTION_MSG), (3, START_ELECTION_MSG)]);
+
+    // ? nadie de mejor rango toma la eleccion: me proclamo lider en un term nuevo
+    assert!(node.handle(now, Event::Answer { request: election[0].0, from: 2, answer: Err("timeout".to_string()) }).is_empty());
+    let announced = node.handle(now, Event::Answer { request: election[1].0, from: 3, answer: Err("timeout".to_string()) });
+    assert_eq!(sends(&announced), vec![(2, "NEW LEADER 1 1".to_string()), (3, "NEW LEADER 1 1".to_string())]);
+    assert_eq!(leader_id(&node), Some(1));
+}
+
+#[test]
+fn election_is_left_to_a_better_node_that_answers() {
+    let mut node = node(2, &[1, 2, 3]);
+
+    let actions = node.handle(Duration::ZERO, message(START_ELECTION_MSG));
+    assert_eq!(reply(&actions), Some(ELECTION_MSG.to_string()));
+    let election = requests(&actions);
+    assert_eq!(election.iter().map(|(_, to, _)| *to).collect::<Vec<_>>(), vec![3]);
+
+    let answered = node.handle(Duration::ZERO, Event::Answer { request: election[0].0, from: 3, answer: Ok(ELECTION_MSG.to_string()) });
+    assert!(answered.is_empty());
+    assert_eq!(leader_id(&node), None);
+
+    let announced = node.handle(Duration::ZERO, message("NEW LEADER 3 1"));
+    assert_eq!(reply(&announced), Some(NEW_LEADER_ANSWER.to_string()));
+    assert_eq!(leader_id(&node), Some(3));
+}
+
+#[test]
+fn leader_proposals_are_confirmed_without_adopting_the_leader() {
+    let mut node = node(1, &[1, 2, 3]);
+
+    let proposed = node.handle(Duration::ZERO, message("PROPOSE LEADER 3 2"));
+    assert_eq!(reply(&proposed), Some(NEW_LEADER_ANSWER.to_string()));
+    assert_eq!(leader_id(&node), None);
+
+    // ? una propuesta de un term que ya paso se rechaza
+    let stale = node.handle(Duration::ZERO, message("PROPOSE LEADER 2 1"));
+    assert_eq!(reply(&stale), Some("STALE TERM 2".to_string()));
+}
+
+#[test]
+fn delayed_announcements_do_not_undo_a_newer_election() {
+    let mut node = node(1, &[1, 2, 3]);
+    node.handle(Duration::ZERO, message("NEW LEADER 3 5"));
+
+    node.handle(Duration::ZERO, message("NEW LEADER 2 4"));
+    assert_eq!(node.leader(), Some((3, Some(5))));
+}
+
+#[test]
+fn heartbeats_carry_the_term_the_leader_was_elected_in() {
+    let mut node = node(1, &[1, 2]);
+    node.handle(Duration::ZERO, message("NEW LEADER 1 1"));
+    // ? un candidato que no gano sube el term del nodo
+    node.handle(Duration::ZERO, message("PROPOSE LEADER 2 3"));
+
+    let actions = node.handle(Duration::ZERO, Event::Timer(Timer::Heartbeat));
+    assert_eq!(sends(&actions), vec![(2, "HEARTBEAT 1 1".to_string())]);
+}
+
+#[test]
+fn heartbeat_makes_earlier_timeout_stale() {
+    let mut node = node(1, &[1, 2]);
+    node.handle(Duration::ZERO, message("NEW LEADER 2 1"));
+
+    let actions = node.handle(Duration::from_secs(10), message("HEARTBEAT 2 1"));
+    assert_eq!(actions, vec![
+        Action::Reply { to: FROM, text: "ok".to_string() },
+        Action::SetTimer { after: HEARTBEAT_TIMEOUT, timer: Timer::HeartbeatTimeout(1) },
+    ]);
+    assert!(node.handle(HEARTBEAT_TIMEOUT, Event::Timer(Timer::HeartbeatTimeout(0))).is_empty());
+}
+
+#[test]
+fn leader_adopts_the_winner_of_a_split_brain() {
+    let mut node = node(1, &[1, 2, 3]);
+    node.handle(Duration::ZERO, message("NEW LEADER 1 1"));
+    assert_eq!(leader_id(&node), Some(1));
+
+    let actions = node.handle(Duration::ZERO, message("HEARTBEAT 3 2"));
+    assert!(actions.contains(&Action::Work(WorkCommand::Resync(3))));
+    assert_eq!(leader_id(&node), Some(3));
+}
+
+#[test]
+fn leader_sends_heartbeats_and_steps_down_on_shutdown() {
+    let mut node = node(3, &[1, 2, 3]);
+    node.handle(Duration::ZERO, message("NEW LEADER 3 1"));
+
+    let heartbeat = node.handle(HEARTBEAT_INTERVAL, Event::Timer(Timer::Heartbeat));
+    assert_eq!(sends(&heartbeat), vec![(1, "HEARTBEAT 3 1".to_string()), (2, "HEARTBEAT 3 1".to_string())]);
+
+    let shutdown = node.handle(HEARTBEAT_INTERVAL, Event::Shutdown);
+    assert_eq!(sends(&shutdown), vec![(1, "STEP DOWN 3".to_string()), (2, "STEP DOWN 3".to_string())]);
+    assert_eq!(shutdown.last(), Some(&Action::Stop));
+}
+
+#[test]
+fn same_events_produce_the_same_actions() {
+    let script = |node: &mut Node| {
+        let mut all = node.handle(Duration::ZERO, Event::Started);
+        all.extend(node.handle(Duration::from_secs(1), message("HEARTBEAT 2 4")));
+        all.extend(node.handle(Duration::from_secs(2), message("STEP DOWN 2")));
+        all.extend(node.handle(Duration::from_secs(3), Event::Answer { request: 1, from: 2, answer: Err("caido".to_string()) }));
+        all.extend(node.handle(Duration::from_secs(4), Event::Timer(Timer::Heartbeat)));
+        all
+    };
+
+    let first = script(&mut node(1, &[1, 2]));
+    let second = script(&mut node(1, &[1, 2]));
+    assert_eq!(first, second);
+    assert!(sends(&first).contains(&(2, "NEW LEADER 1 5".to_string())));
+}
+
+#[test]
+fn malformed_leader_announcements_are_dropped() {
+    let mut node = node(1, &[1, 2]);
+    node.handle(Duration::ZERO, message("NEW LEADER 2 1"));
+
+    assert_eq!(reply(&node.handle(Duration::ZERO, message("NEW LEADER"))), Some("error".to_string()));
+    assert_eq!(reply(&node.handle(Duration::ZERO, message("STEP DOWN dos"))), Some("error".to_string()));
+    assert_eq!(leader_id(&node), Some(2));
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+/// Rueda de timers: agendar y vencer cuestan lo mismo sin importar cuantos timers haya pendientes.
+///
+/// El tiempo se divide en ticks de `tick` y cada tick cae en una de las ranuras de la rueda. Un timer que vence mas
+/// lejos que una vuelta completa lleva la cuenta de cuantas vueltas le faltan. Los timers vencen en orden de tick y,
+/// dentro del mismo tick, en el orden en que se agendaron.
+pub(crate) struct TimerWheel<T> {
+    tick: Duration,
+    // ? cada entrada lleva las vueltas que le faltan antes de vencer
+    slots: Vec<Vec<(u64, T)>>,
+    // ? ultimo tick procesado
+    current: u64,
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub(crate) fn new(tick: Duration, slots: usize) -> TimerWheel<T> {
+        TimerWheel { tick, slots: (0..slots.max(1)).map(|_| Vec::new()).collect(), current: 0, len: 0 }
+    }
+
+    // ? primer tick que cae en `at` o despues
+    fn tick_of(&self, at: Duration) -> u64 {
+        let tick = self.tick.as_nanos().max(1);
+        at.as_nanos().div_ceil(tick) as u64
+    }
+
+    /// Agenda `item` para que venza en `at`. Si `at` ya paso, vence en el proximo tick.
+    pub(crate) fn schedule(&mut self, at: Duration, item: T) {
+        let target = self.tick_of(at).max(self.current + 1);
+        let ahead = target - self.current - 1;
+        let slots = self.slots.len() as u64;
+
+        self.slots[(target % slots) as usize].push((ahead / slots, item));
+        self.len += 1;
+    }
+
+    /// Avanza la rueda hasta `now` y devuelve los timers vencidos.
+    pub(crate) fn expire(&mut self, now: Duration) -> Vec<T> {
+        let until = now.as_nanos() as u64 / self.tick.as_nanos().max(1) as u64;
+        let slots = self.slots.len() as u64;
+        let mut expired = Vec::new();
+
+        while self.current < until && self.len > 0 {
+            self.current += 1;
+            let slot = std::mem::take(&mut self.slots[(self.current % slots) as usize]);
+            for (rounds, item) in slot {
+                if rounds == 0 {
+                    expired.push(item);
+                    self.len -= 1;
+                } else {
+                    self.slots[(self.current % slots) as usize].push((rounds - 1, item));
+                }
+            }
+        }
+        // ? sin timers pendientes no hace falta recorrer las ranuras vacias
+        self.current = self.current.max(until);
+        expired
+    }
+
+    /// Cuando vence el proximo tick, o `None` si no hay timers pendientes.
+    pub(crate) fn next_tick(&self) -> Option<Duration> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos((self.tick.as_nanos() as u64).saturating_mul(self.current + 1)))
+    }
+}
@@ -1,17 +1,19 @@
-use std::io::Write;
-use std::process::exit;
 use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use crate::consts::{HEARTBEAT_MSG, START_ELECTION_MSG};
+use crate::election::{run_pre_vote, send_message, ElectionEnv};
+use crate::journal::{self, Event};
+use crate::metrics::{HEARTBEATS_MISSED, HEARTBEATS_SENT};
 use crate::process::Process;
-use crate::utils::tcp::get_server_connection;
+use crate::shutdown::{is_shutdown_requested, StopSignal};
+use crate::supervisor::spawn_supervised;
 
-fn i_am_leader(processes: &Arc<RwLock<Vec<Process>>>) -> bool {
+pub(crate) fn i_am_leader(processes: &Arc<RwLock<Vec<Process>>>) -> bool {
     let processes_guard = match processes.read() {
         Ok(guard) => guard,
         Err(e) => {
-            eprintln!("Error al obtener el guard de procesos: {}", e);
+            error!("Error al obtener el guard de procesos: {}", e);
             return false;
         }
     };
@@ -26,93 +28,111 @@ fn i_am_leader(processes: &Arc<RwLock<Vec<Process>>>) -> bool {
 }
 
 
-pub fn start_healthcheck_thread(mut rx: std::sync::mpsc::Receiver<String>, mut election_tx: std::sync::mpsc::Sender<String>, other_processes: Arc<RwLock<Vec<Process>>>) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        let mut last_heartbeat_time = Instant::now();
-        let timeout = Duration::from_secs(60);
+// ? cada cuanto el lider manda heartbeats y los subordinados revisan si los recibieron
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+pub(crate) const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn start_healthcheck_thread(mut rx: std::sync::mpsc::Receiver<String>, mut election_tx: std::sync::mpsc::Sender<String>, other_processes: Arc<RwLock<Vec<Process>>>, env: ElectionEnv, stop: StopSignal) -> thread::JoinHandle<()> {
+    spawn_supervised("healthchecker", stop.clone(), move || {
+        let mut last_heartbeat_time = env.clock.now();
 
         loop {
             if i_am_leader(&other_processes) {
                 // ? Si soy lider, envio heartbeat a los demas procesos
-                send_heartbeat(&other_processes);
+                send_heartbeat(&env, &other_processes);
             } else {
                 // ? Si no soy lider, chequeo si recibi heartbeat
-                check_for_heartbeat(&mut rx, &mut last_heartbeat_time, timeout, &mut election_tx);
+                if let Err(e) = check_for_heartbeat(&mut rx, &mut last_heartbeat_time, HEARTBEAT_TIMEOUT, &mut election_tx, &other_processes, &env) {
+                    // ? durante el apagado el listener se cierra antes, por lo que es esperable. Si no, el supervisor
+                    // ? reinicia el hilo o apaga el nodo.
+                    if is_shutdown_requested() {
+                        return Ok(());
+                    }
+                    return Err(e);
+                }
             }
 
             // ? Espero 10 segundos antes de volver a actuar
-            thread::sleep(Duration::from_millis(10000));
+            if stop.wait(HEARTBEAT_INTERVAL) {
+                return Ok(());
+            }
         }
     })
 }
 
-pub fn check_for_heartbeat(rx: &mut std::sync::mpsc::Receiver<String>, last_heartbeat_time: &mut Instant, timeout: Duration, election_tx: &mut std::sync::mpsc::Sender<String>) {
-    println!("Chequeando si recibi heartbeat...");
+pub fn check_for_heartbeat(rx: &mut std::sync::mpsc::Receiver<String>, last_heartbeat_time: &mut Duration, timeout: Duration, election_tx: &mut std::sync::mpsc::Sender<String>, processes: &Arc<RwLock<Vec<Process>>>, env: &ElectionEnv) -> Result<(), String> {
+    trace!("Chequeando si recibi heartbeat...");
 
     // ? intento recibir un mensaje del canal.
     match rx.try_recv() {
         Ok(message) => {
             // ? si hay un mensaje de heartbeat, actualizo la ultima vez que recibi un heartbeat.
             if message == HEARTBEAT_MSG {
-                println!("Heartbeat recibido, actualizando el temporizador.");
-                *last_heartbeat_time = Instant::now();
+                trace!("Heartbeat recibido, actualizando el temporizador.");
+                *last_heartbeat_time = env.clock.now();
             } else {
-                println!("Mensaje inesperado en el canal: {}", message);
+                warn!("Mensaje inesperado en el canal: {}", message);
             }
         }
         Err(std::sync::mpsc::TryRecvError::Empty) => {
             // ? si no hay mensaje, chequeo si paso el tiempo de timeout.
-            if last_heartbeat_time.elapsed() > timeout {
-                // ? si paso tiempo de timeout, envio un mensaje al hilo de eleccion para que inicie un proceso de eleccion.
-                println!("No se recibió heartbeat en el tiempo esperado. TIMEOUT. Iniciando elección de líder...");
-
-                match election_tx.send(START_ELECTION_MSG.to_string()) {
-                    Ok(_) => println!("Mensaje enviado al hilo de elección."),
-                    Err(e) => eprintln!("Error al enviar mensaje al hilo de elección: {}", e)
+            if env.clock.now().saturating_sub(*last_heartbeat_time) > timeout {
+                // ? si paso tiempo de timeout, primero confirmo con el resto que el lider se perdio (pre vote)
+                // ? y recien ahi envio un mensaje al hilo de eleccion para que inicie un proceso de eleccion.
+                warn!("No se recibió heartbeat en el tiempo esperado. TIMEOUT. Consultando si el resto también perdió al líder...");
+                HEARTBEATS_MISSED.increment();
+                journal::record(Event::HeartbeatMissed);
+
+                if run_pre_vote(env.transport.as_ref(), processes) {
+                    info!("La mayoría perdió al líder. Iniciando elección de líder...");
+                    match election_tx.send(START_ELECTION_MSG.to_string()) {
+                        Ok(_) => debug!("Mensaje enviado al hilo de elección."),
+                        Err(e) => error!("Error al enviar mensaje al hilo de elección: {}", e)
+                    }
+                } else {
+                    info!("La mayoría todavía ve al líder. No se inicia una elección.");
                 }
 
-                *last_heartbeat_time = Instant::now();
+                *last_heartbeat_time = env.clock.now();
             }
         }
-        Err(_) => {
-            eprintln!("Error desconocido al recibir mensaje del canal.");
-            exit(1); //TODO manejar error
+        Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+            return Err("El canal de heartbeats se cerró.".to_string());
         }
     }
+
+    Ok(())
 }
 
-pub fn send_heartbeat(other_processes: &Arc<RwLock<Vec<Process>>>) {
-    println!("Enviando heartbeat a los demas procesos...");
+pub fn send_heartbeat(env: &ElectionEnv, other_processes: &Arc<RwLock<Vec<Process>>>) {
+    trace!("Enviando heartbeat a los demas procesos...");
 
     let processes_guard = match other_processes.read() {
         Ok(guard) => guard,
         Err(e) => {
-            eprintln!("Error al obtener el guard de procesos: {}", e);
+            error!("Error al obtener el guard de procesos: {}", e);
             return;
         }
     };
 
+    // ? el heartbeat lleva quien soy y el term en el que me eligieron (no el actual, que puede haber subido por un
+    // ? candidato que no gano), para que otro lider pueda detectar el conflicto
+    let msg = match processes_guard.iter().find(|process| process.me) {
+        Some(me) => format!("{} {} {}", HEARTBEAT_MSG, me.id, me.leader_term.unwrap_or(0)),
+        None => return,
+    };
+
     //TODO Manejar el caso de que no se pueda enviar un heartbeat a un proceso. Definir timeouts
     // ? para cada uno de los procesos que no son yo y no son lider (si llego aca siempre yo y el lider somos uno)
     for process in processes_guard.iter() {
         if !process.leader && !process.me {
-            // ? determino la direccion del proceso
-            let addr = format!("{}:{}", process.ip, process.port);
-
-            // ? me conecto
-            let mut conn = match get_server_connection(&addr) {
-                Ok(conn) => conn,
-                Err(e) => {
-                    eprintln!("Error enviando heartbeat a {}: {}", process.id, e);
-                    continue;
-                }
-            };
-
             // ? envio el mensaje de heartbeat
-            let msg = HEARTBEAT_MSG.to_string();
-            match conn.write(msg.as_bytes()) {
-                Ok(_) => println!("Mensaje enviado a {}", addr),
-                Err(e) => eprintln!("Error al enviar mensaje a {}: {}", addr, e)
+            match send_message(env.transport.as_ref(), process, &msg) {
+                Ok(_) => {
+                    HEARTBEATS_SENT.increment();
+                    trace!("Mensaje enviado a {}", process.addr);
+                }
+                Err(e) => warn!("Error enviando heartbeat a {}: {}", process.id, e)
             }
         }
     }
@@ -0,0 +1,136 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::election::Term;
+use crate::log::format_timestamp;
+use crate::utils::journal_file::{rotated_path, JournalEntry, ROTATED_FILES};
+
+// * Journal de eventos de eleccion y de membresia, para reconstruir que paso despues de una falla.
+// * Se activa con --journal. Cada nodo escribe el suyo y `concurride-ctl journal` los une en una linea de tiempo.
+
+// ? tamaño a partir del cual el archivo se rota
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Eventos que se registran en el journal.
+pub(crate) enum Event<'a> {
+    // ? el nodo arranco con estos procesos en el cluster
+    Started { members: Vec<u32> },
+    ElectionStarted { strategy: &'a str },
+    // ? respuesta de otro nodo a un mensaje de eleccion, voto, pre vote o anuncio
+    AnswerReceived { from: u32, answer: &'a str },
+    // ? None si el lider renuncio
+    LeaderChanged { leader: Option<u32>, term: Option<u64> },
+    HeartbeatMissed,
+    MemberUp { id: u32 },
+    MemberDown { id: u32 },
+}
+
+impl Event<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::Started { .. } => "started",
+            Event::ElectionStarted { .. } => "election_started",
+            Event::AnswerReceived { .. } => "answer_received",
+            Event::LeaderChanged { .. } => "leader_changed",
+            Event::HeartbeatMissed => "heartbeat_missed",
+            Event::MemberUp { .. } => "member_up",
+            Event::MemberDown { .. } => "member_down",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            Event::Started { members } => {
+                let ids: Vec<String> = members.iter().map(u32::to_string).collect();
+                format!("miembros {}", ids.join(","))
+            }
+            Event::ElectionStarted { strategy } => format!("algoritmo {}", strategy),
+            Event::AnswerReceived { from, answer } => format!("de {}: {}", from, answer),
+            Event::LeaderChanged { leader: Some(leader), term } => {
+                format!("lider {} term {}", leader, term.map_or("-".to_string(), |term| term.to_string()))
+            }
+            Event::LeaderChanged { leader: None, .. } => "el lider renuncio".to_string(),
+            Event::HeartbeatMissed => "no se recibio heartbeat a tiempo".to_string(),
+            Event::MemberUp { id } | Event::MemberDown { id } => format!("nodo {}", id),
+        }
+    }
+}
+
+struct Journal {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    node: u32,
+    term: Arc<Term>,
+}
+
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+fn journal() -> MutexGuard<'static, Option<Journal>> {
+    match JOURNAL.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn open_file(path: &Path) -> Result<File, String> {
+    OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("no se pudo abrir {}: {}", path.display(), e))
+}
+
+/// Empieza a escribir el journal del nodo `node` en `path`, agregando al final si ya existe.
+pub(crate) fn open(path: &Path, node: u32, term: Arc<Term>) -> Result<(), String> {
+    let file = open_file(path)?;
+    let size = file.metadata().map_or(0, |metadata| metadata.len());
+    *journal() = Some(Journal { path: path.to_path_buf(), file, size, node, term });
+    Ok(())
+}
+
+// ? journal.log pasa a ser journal.log.1, journal.log.1 pasa a ser journal.log.2, y se descarta el mas viejo
+fn rotate(journal: &mut Journal) -> Result<(), String> {
+    for n in (1..ROTATED_FILES).rev() {
+        let from = rotated_path(&journal.path, n);
+        if from.exists() {
+            fs::rename(&from, rotated_path(&journal.path, n + 1)).map_err(|e| format!("no se pudo rotar {}: {}", from.display(), e))?;
+        }
+    }
+    fs::rename(&journal.path, rotated_path(&journal.path, 1)).map_err(|e| format!("no se pudo rotar {}: {}", journal.path.display(), e))?;
+
+    journal.file = open_file(&journal.path)?;
+    journal.size = 0;
+    Ok(())
+}
+
+fn append(journal: &mut Journal, event: &Event) -> Result<(), String> {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let entry = JournalEntry {
+        unix_millis: since_epoch.as_millis() as u64,
+        ts: format_timestamp(since_epoch),
+        node: journal.node,
+        term: journal.term.current(),
+        event: event.name().to_string(),
+        detail: event.detail(),
+    };
+    let line = format!("{}\n", entry.to_line());
+
+    if journal.size > 0 && journal.size + line.len() as u64 > MAX_FILE_SIZE {
+        rotate(journal)?;
+    }
+    journal.file.write_all(line.as_bytes()).map_err(|e| format!("no se pudo escribir en {}: {}", journal.path.display(), e))?;
+    journal.size += line.len() as u64;
+    Ok(())
+}
+
+/// Agrega `event` al journal. Si no se activo el journal no hace nada.
+pub(crate) fn record(event: Event) {
+    let mut guard = journal();
+    let journal = match guard.as_mut() {
+        Some(journal) => journal,
+        None => return,
+    };
+
+    if let Err(e) = append(journal, &event) {
+        warn!("Error al escribir el journal: {}", e);
+    }
+}
@@ -1,116 +1,472 @@
-use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::thread;
-use std::thread::JoinHandle;
+use std::io::{BufReader, Read};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::{allowlist, auth};
+use crate::election::{answer_leader_proposal, answer_pre_vote, handle_leader_claim, is_leader_alive, ElectionStrategy, LeaderClaim};
+use crate::procceses_list_handler::parse_process_id;
+use crate::metrics::{record_heartbeat_received, CONNECTIONS_SHED};
 use crate::process::Process;
-use crate::utils::tcp::get_tcp_listener_or_kill_process;
-use crate::consts::{NEW_LIDER_MSG, HEARTBEAT_MSG, START_ELECTION_MSG, ELECTION_MSG, NEW_LEADER_ANSWER};
+use crate::shutdown::{request_shutdown, StopSignal};
+use crate::supervisor::spawn_supervised;
+use crate::status::{node_status, record_contact, status_as_text};
+use crate::work_thread::WorkCommand;
+use crate::worker_pool::{serve_connections, WORKERS};
+use crate::log;
+use crate::utils::faults;
+use crate::utils::tcp::{get_peer_addr, get_tcp_listener_or_kill_process, read_frame, write_bytes_to_stream, write_frame};
+use crate::consts::{NEW_LIDER_MSG, PROPOSE_LEADER_MSG, HEARTBEAT_MSG, START_ELECTION_MSG, NEW_LEADER_ANSWER, STEP_DOWN_MSG, SHUTDOWN_MSG, PRE_VOTE_MSG, STATUS_MSG, STATUS_JSON_MSG, TRANSFER_LEADER_MSG, TAKE_OVER_MSG, NOT_LEADER_ANSWER, FAULTS_MSG, LOG_MSG, BUSY_ANSWER, SESSION_MSG, SESSION_ANSWER, UNAUTHORIZED_ANSWER, FORBIDDEN_ANSWER};
 
-fn get_me(processes: &Vec<Process>) -> Result<&Process, String> {
-    for process in processes {
-        if process.me {
-            return Ok(process);
-        }
-    }
+// ? tiempo maximo para leer el mensaje y para escribir la respuesta de una conexion
+pub(crate) const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+// ? sesiones persistentes abiertas a la vez, y tiempo sin mensajes tras el cual se cierran
+pub(crate) const MAX_SESSIONS: usize = 32;
+pub(crate) const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
 
-    Err("No se encontro el proceso actual en la lista de procesos".to_string())
+/// Lo que el listener necesita para atender los mensajes de otros nodos.
+pub(crate) struct ListenerContext {
+    // ? avisa al process handler de nuevos lideres
+    pub(crate) process_handler_tx: Sender<String>,
+    // ? avisa al hilo de heartbeat que se recibio un heartbeat
+    pub(crate) heartbeat_tx: Sender<String>,
+    // ? pide elecciones y deriva mensajes al hilo de eleccion
+    pub(crate) election_tx: Sender<String>,
+    // ? pide al hilo de trabajo que resincronice los trips o que traspase el liderazgo
+    pub(crate) work_tx: Sender<WorkCommand>,
+    pub(crate) processes: Arc<RwLock<Vec<Process>>>,
+    pub(crate) election_strategy: Arc<dyn ElectionStrategy>,
+    // ? ultimo heartbeat recibido segun el reloj de la estrategia, para responder los pre votes
+    pub(crate) last_heartbeat: Mutex<Option<Duration>>,
 }
 
-fn am_i_leader(processes: &Vec<Process>) -> bool {
-    let me = match get_me(processes){
-        Ok(me) => me,
-        Err(e) => {
-            eprintln!("{}", e);
-            return false;
-        }
-    };
+// ? sesiones abiertas: el hilo que atiende cada una y una copia de su socket, para cortarlas al detener el listener
+#[derive(Default)]
+struct Sessions {
+    open: Mutex<Vec<(JoinHandle<()>, TcpStream)>>,
+}
+
+impl Sessions {
+    // ? las sesiones terminadas se descartan al abrir otra o al cerrarlas todas
+    fn lock(&self) -> MutexGuard<'_, Vec<(JoinHandle<()>, TcpStream)>> {
+        let mut open = match self.open.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        open.retain(|(handle, _)| !handle.is_finished());
+        open
+    }
 
-    me.leader
+    // ? corta las sesiones abiertas, lo que destraba la lectura de sus hilos, y espera a que terminen
+    fn close_all(&self) {
+        let open = std::mem::take(&mut *self.lock());
+        for (_, stream) in &open {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        for (handle, _) in open {
+            let _ = handle.join();
+        }
+    }
 }
 
-pub(crate) fn listen_for_process_messages(port: u32, mut process_handler_tx: std::sync::mpsc::Sender<String>, mut heartbeat_tx: std::sync::mpsc::Sender<String>, mut election_tx: std::sync::mpsc::Sender<String>) -> JoinHandle<()>{
+pub(crate) fn listen_for_process_messages(bind_ip: IpAddr, port: u16, context: ListenerContext, stop: StopSignal) -> JoinHandle<()>{
     // ? abre el socket para que otros puedan comunicarse
-    let listener = get_tcp_listener_or_kill_process(port);
-
-    thread::spawn(move || {
-        // ? escucha las conexiones entrantes
-        println!("[Listener]: Escuchando conexiones de otros nodos en el puerto {}", port);
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    // ? para cada conexion, maneja el mensaje
-                    handle_node_message(stream, &mut process_handler_tx, &mut heartbeat_tx, &mut election_tx);
-                }
-                Err(e) => {
-                    eprintln!("[Listener]: Error al aceptar conexión: {}", e)
-                    // ? sigue funcionando el server pero podria romperse todo porque no sabemos que info venia en el mensaje perdido.
-                },
-            }
+    let listener = get_tcp_listener_or_kill_process(bind_ip, port);
+
+    // ? las conexiones las atienden los workers; el hilo del listener solo las acepta y las encola
+    info!("Escuchando conexiones de otros nodos en {} con {} workers", SocketAddr::new(bind_ip, port), WORKERS);
+    let context = Arc::new(context);
+    let sessions = Arc::new(Sessions::default());
+    let worker_stop = stop.clone();
+    let worker_sessions = Arc::clone(&sessions);
+    let mut connections = Some(serve_connections("listener", "listener worker", listener, stop.clone(), move |stream| {
+        handle_node_message(stream, &context, &worker_sessions, &worker_stop)
+    }));
+
+    // ? las sesiones se cortan recien cuando los workers terminaron, para que no se abra ninguna despues
+    spawn_supervised("listener sessions", stop, move || {
+        if let Some(connections) = connections.take() {
+            let _ = connections.join();
         }
+        sessions.close_all();
+        Ok(())
     })
 }
 
-fn handle_node_message(mut stream: TcpStream, tx: &mut std::sync::mpsc::Sender<String>, tx_heartbeat: &mut std::sync::mpsc::Sender<String>, election_tx: &mut std::sync::mpsc::Sender<String>){
+fn handle_node_message(mut stream: TcpStream, context: &Arc<ListenerContext>, sessions: &Sessions, stop: &StopSignal){
+    // ? un nodo que se conecta y no envia nada (o no lee la respuesta) solo ocupa a este worker hasta el timeout
+    if let Err(e) = stream.set_read_timeout(Some(CONNECTION_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(CONNECTION_TIMEOUT))) {
+        error!("Error al configurar timeout en la conexión: {}", e);
+        return;
+    }
+
     // ? convierte el mensaje a un string
     let mut buffer = [0; 1024];
     let bytes_read = match stream.read(&mut buffer) {
         Ok(bytes_read) => bytes_read,
         Err(e) => {
-            eprintln!("Error al leer mensaje: {}", e);
+            let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+            error!("Error al leer mensaje de {}: {}", peer, e);
             return; // ? sigue funcionando el server pero podria romperse todo porque no sabemos que info venia en el mensaje perdido.
         }
     };
     let message = String::from_utf8_lossy(&buffer[..bytes_read]);
 
+    // ? otro nodo pide una sesion persistente: la conexion pasa a tener su propio hilo. Las sesiones son solo entre nodos.
+    let listed = is_listed_peer(&stream, context);
+    if message.trim_end() == SESSION_MSG {
+        if !listed {
+            allowlist::record_refused(&stream, "la sesión");
+            let _ = write_bytes_to_stream(&mut stream, format!("{}\n", FORBIDDEN_ANSWER).as_bytes());
+            return;
+        }
+        start_session(stream, Arc::clone(context), sessions, stop.clone());
+        return;
+    }
+
+    // ? con clave de cluster, un mensaje sin firma valida se rechaza sin procesarlo
+    let message = match auth::open(&message) {
+        Ok(message) => message,
+        Err(e) => {
+            let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+            warn!("Mensaje rechazado de {}: {}", peer, e);
+            let _ = write_bytes_to_stream(&mut stream, UNAUTHORIZED_ANSWER.as_bytes());
+            return;
+        }
+    };
+
+    // ? desde fuera de la lista de procesos solo se atienden los mensajes de cliente
+    if !listed && !allowlist::is_client_message(&message) {
+        allowlist::record_refused(&stream, "el mensaje");
+        let _ = write_bytes_to_stream(&mut stream, FORBIDDEN_ANSWER.as_bytes());
+        return;
+    }
+
     // ? obtiene la respuesta a enviar
-    let answer = process_message(&message, tx, tx_heartbeat);
+    let answer = process_message(&message, context);
 
     // ? envia la respuesta
-    match stream.write(answer.as_bytes()) {
+    match write_bytes_to_stream(&mut stream, answer.as_bytes()) {
         Ok(_) => {},
-        Err(e) => eprintln!("Error al enviar respuesta: {}", e) // ? sigue funcionando el server pero podria romperse todo porque no sabemos que pasa con el mensaje que no se pudo enviar.
+        Err(e) => error!("Error al enviar respuesta: {}", e) // ? sigue funcionando el server pero podria romperse todo porque no sabemos que pasa con el mensaje que no se pudo enviar.
+    }
+
+    after_answer(&message, &answer, context);
+}
+
+fn is_listed_peer(stream: &TcpStream, context: &ListenerContext) -> bool {
+    match context.processes.read() {
+        Ok(guard) => allowlist::is_listed(stream, &guard),
+        Err(poisoned) => allowlist::is_listed(stream, &poisoned.into_inner()),
     }
+}
 
-    // ? chequeo si la rta que devuelvo corresponde a que tengo que detonar una eleccion
-    if answer == ELECTION_MSG {
+// ? lo que queda por hacer una vez respondido el mensaje
+fn after_answer(message: &str, answer: &str, context: &ListenerContext) {
+    // ? si el lider renuncio hay que elegir uno nuevo
+    if message.starts_with(STEP_DOWN_MSG) && answer != "error" {
         // ? envio mensaje de solicitud de inicio de eleccion
-        match election_tx.send(START_ELECTION_MSG.to_string()) {
+        match context.election_tx.send(START_ELECTION_MSG.to_string()) {
             Ok(_) => {},
             Err(e) => {
-                eprintln!("Error al enviar mensaje de eleccion: {}", e) // ? podria romperse todo porque no sabemos que pasa con el mensaje que no se pudo enviar de election.
+                error!("Error al enviar mensaje de eleccion: {}", e) // ? podria romperse todo porque no sabemos que pasa con el mensaje que no se pudo enviar de election.
             }
         }
     } else if answer == "error"{
-        eprintln!("Error al procesar mensaje: {}", message);
+        error!("Error al procesar mensaje: {}", message);
+    }
+}
+
+// ? las sesiones no ocupan a los workers, pero se limitan para que nadie pueda abrir hilos sin fin
+fn start_session(mut stream: TcpStream, context: Arc<ListenerContext>, sessions: &Sessions, stop: StopSignal) {
+    let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+    let mut open = sessions.lock();
+    if open.len() >= MAX_SESSIONS {
+        CONNECTIONS_SHED.increment();
+        warn!("Demasiadas sesiones abiertas, rechazando la de {}", peer);
+        let _ = write_bytes_to_stream(&mut stream, format!("{}\n", BUSY_ANSWER).as_bytes());
+        return;
+    }
+
+    let accepted = stream.set_read_timeout(Some(SESSION_IDLE_TIMEOUT)).map_err(|e| e.to_string())
+        .and_then(|_| stream.try_clone().map_err(|e| e.to_string()))
+        .and_then(|copy| write_bytes_to_stream(&mut stream, format!("{}\n", SESSION_ANSWER).as_bytes()).map(|_| copy));
+    let copy = match accepted {
+        Ok(copy) => copy,
+        Err(e) => {
+            warn!("No se pudo abrir la sesión con {}: {}", peer, e);
+            return;
+        }
+    };
+
+    debug!("Sesión abierta con {}", peer);
+    let handle = thread::spawn(move || {
+        let reason = serve_session(stream, &context, &stop);
+        debug!("Sesión con {} cerrada: {}", peer, reason);
+    });
+    open.push((handle, copy));
+}
+
+// ? atiende los frames de la sesion en orden, respondiendo cada uno con su id, hasta que se cierre. Devuelve el motivo.
+fn serve_session(stream: TcpStream, context: &ListenerContext, stop: &StopSignal) -> String {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => return format!("Error al duplicar la conexión: {}", e),
+    };
+    let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+    let mut reader = BufReader::new(stream);
+
+    while !stop.is_stopped() {
+        let (id, message) = match read_frame(&mut reader) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return "el otro nodo cerró la conexión".to_string(),
+            Err(e) => return e,
+        };
+        let message = match auth::open(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Mensaje rechazado de {}: {}", peer, e);
+                if let Err(e) = write_frame(&mut writer, id, UNAUTHORIZED_ANSWER.as_bytes()) {
+                    return e;
+                }
+                continue;
+            }
+        };
+
+        let answer = process_message(&message, context);
+        if let Err(e) = write_frame(&mut writer, id, answer.as_bytes()) {
+            return e;
+        }
+        after_answer(&message, &answer, context);
+    }
+    "el listener se detuvo".to_string()
+}
+
+fn last_heartbeat(context: &ListenerContext) -> Option<Duration> {
+    match context.last_heartbeat.lock() {
+        Ok(last) => *last,
+        Err(poisoned) => *poisoned.into_inner(),
     }
 }
 
 // ? debe mejorarse el manejo de errores
-pub(crate) fn process_message(message: &str, tx: &mut std::sync::mpsc::Sender<String>, tx_heartbeat: &mut std::sync::mpsc::Sender<String>) -> String {
-    println!("Mensaje recibido: {}", message);
-
-    if message == START_ELECTION_MSG {
-        ELECTION_MSG.to_string()
-    } else if message.starts_with(NEW_LIDER_MSG) {
-        // ? avisa al process handler que setee el nuevo lider
-        return match tx.send(message.to_string()) {
+pub(crate) fn process_message(message: &str, context: &ListenerContext) -> String {
+    debug!("Mensaje recibido: {}", message);
+    let env = context.election_strategy.env();
+
+    // ? primero le damos la oportunidad al algoritmo de eleccion de atender sus propios mensajes
+    if let Some(answer) = context.election_strategy.handle_message(message, &context.processes, &context.election_tx) {
+        return answer;
+    }
+
+    if message.starts_with(PROPOSE_LEADER_MSG) {
+        // ? con --quorum majority el candidato pregunta antes de anunciarse: confirmamos sin adoptarlo
+        answer_leader_proposal(message, &env.term)
+    } else if message.starts_with(NEW_LIDER_MSG) || message.starts_with(STEP_DOWN_MSG) {
+        if parse_process_id(message).is_none() {
+            return "error".to_string();
+        }
+        // ? "NEW LEADER {pid} {term}": nos quedamos con el term si es mas nuevo
+        if let Some(term) = message.split_whitespace().nth(3).and_then(|term| term.parse().ok()) {
+            env.term.observe(term);
+        }
+
+        // ? avisa al process handler que setee el nuevo lider (o que saque al que renuncio)
+        match context.process_handler_tx.send(message.to_string()) {
             Ok(_) => NEW_LEADER_ANSWER.to_string(),
             Err(e) => {
-                eprintln!("Error al enviar mensaje: {}", e);
+                error!("Error al enviar mensaje: {}", e);
                 "error".to_string()
             }
         }
-    } else if message == HEARTBEAT_MSG {
-        match tx_heartbeat.send(HEARTBEAT_MSG.to_string()) {
+    } else if message.starts_with(HEARTBEAT_MSG) {
+        // ? "HEARTBEAT {lider} {term}": si otro nodo tambien se cree lider se resuelve el conflicto
+        let now = env.clock.now();
+        record_heartbeat_received();
+        if let Some(claim) = LeaderClaim::parse(message) {
+            record_contact(claim.leader, true);
+            let leader_alive = is_leader_alive(last_heartbeat(context), now);
+            // ? solo cuentan los heartbeats de nuestro lider: los de otro no deben evitar que detectemos que el nuestro se cayo
+            if !handle_leader_claim(&claim, &env.term, leader_alive, &context.processes, &context.process_handler_tx, &context.work_tx) {
+                return "ok".to_string();
+            }
+        }
+        match context.last_heartbeat.lock() {
+            Ok(mut last) => *last = Some(now),
+            Err(poisoned) => *poisoned.into_inner() = Some(now),
+        }
+
+        match context.heartbeat_tx.send(HEARTBEAT_MSG.to_string()) {
             Ok(_) => {},
             Err(e) => {
-                eprintln!("Error al enviar mensaje de heartbeat: {}", e);
+                error!("Error al enviar mensaje de heartbeat: {}", e);
+                return "error".to_string();
+            }
+        }
+        "ok".to_string()
+    } else if message.starts_with(PRE_VOTE_MSG) {
+        // ? "PRE VOTE {candidato}": otro nodo quiere saber si tambien perdimos al lider
+        answer_pre_vote(&context.processes, last_heartbeat(context), env.clock.now())
+    } else if let Some(target) = message.strip_prefix(TRANSFER_LEADER_MSG) {
+        // ? comando de administracion: "TRANSFER LEADER [pid]"
+        start_leader_transfer(target.trim(), context)
+    } else if message == TAKE_OVER_MSG {
+        // ? el lider nos traspasa el liderazgo: el hilo de eleccion nos anuncia como nuevo lider
+        match context.election_tx.send(TAKE_OVER_MSG.to_string()) {
+            Ok(_) => "ok".to_string(),
+            Err(e) => {
+                error!("Error al enviar mensaje de eleccion: {}", e);
+                "error".to_string()
+            }
+        }
+    } else if message == START_ELECTION_MSG {
+        // ? la estrategia no usa este mensaje entre nodos: lo tomamos como pedido de eleccion
+        match context.election_tx.send(START_ELECTION_MSG.to_string()) {
+            Ok(_) => "ok".to_string(),
+            Err(e) => {
+                error!("Error al enviar mensaje de eleccion: {}", e);
+                "error".to_string()
+            }
+        }
+    } else if message == STATUS_MSG || message == STATUS_JSON_MSG {
+        // ? comando de administracion: lo que este nodo sabe del cluster
+        let status = match context.processes.read() {
+            Ok(guard) => {
+                let since_heartbeat = last_heartbeat(context).map(|last| env.clock.now().saturating_sub(last));
+                node_status(&guard, since_heartbeat, env.term.current())
+            }
+            Err(e) => {
+                error!("Error al obtener el guard de procesos: {}", e);
                 return "error".to_string();
             }
+        };
+
+        if message == STATUS_JSON_MSG {
+            status.to_string()
+        } else {
+            status_as_text(&status)
         }
+    } else if let Some(spec) = message.strip_prefix(FAULTS_MSG) {
+        // ? comando de administracion: "FAULTS" muestra las fallas activas y "FAULTS {fallas}" las reemplaza
+        configure_faults_from(spec.trim(), context)
+    } else if let Some(setting) = message.strip_prefix(LOG_MSG) {
+        // ? comando de administracion: "LOG" muestra la configuracion, "LOG LEVEL {niveles}" y "LOG FORMAT {formato}" la cambian
+        configure_log(setting.trim())
+    } else if message == SHUTDOWN_MSG {
+        // ? comando de administracion: el hilo principal se encarga del apagado ordenado
+        info!("Se recibio un pedido de apagado.");
+        request_shutdown();
         "ok".to_string()
     } else {
-        println!("Mensaje desconocido: {}", message);
+        warn!("Mensaje desconocido: {}", message);
         "error".to_string()
     }
-}
\ No newline at end of file
+}
+
+fn configure_faults_from(spec: &str, context: &ListenerContext) -> String {
+    match context.processes.read() {
+        Ok(guard) => configure_faults(spec, &guard),
+        Err(e) => {
+            error!("Error al obtener el guard de procesos: {}", e);
+            "error".to_string()
+        }
+    }
+}
+
+/// Atiende el comando `FAULTS [fallas]`: sin fallas muestra las activas, con fallas las reemplaza.
+pub(crate) fn configure_faults(spec: &str, processes: &[Process]) -> String {
+    if spec.is_empty() {
+        return faults::current();
+    }
+
+    match faults::configure(spec, processes) {
+        Ok(faults) => {
+            info!("Inyección de fallas: {}", faults);
+            faults
+        }
+        Err(e) => {
+            warn!("Fallas inválidas: {}", e);
+            "error".to_string()
+        }
+    }
+}
+
+/// Atiende el comando `LOG [LEVEL niveles | FORMAT formato]`.
+pub(crate) fn configure_log(setting: &str) -> String {
+    let changed = if setting.is_empty() {
+        return log::current();
+    } else if let Some(spec) = setting.strip_prefix("LEVEL ") {
+        log::set_level(spec)
+    } else if let Some(format) = setting.strip_prefix("FORMAT ") {
+        log::set_format(format)
+    } else {
+        Err(format!("se esperaba LEVEL o FORMAT: {}", setting))
+    };
+
+    match changed {
+        Ok(_) => {
+            let current = log::current();
+            info!("Log: {}", current);
+            current
+        }
+        Err(e) => {
+            warn!("Configuracion de log inválida: {}", e);
+            "error".to_string()
+        }
+    }
+}
+
+fn start_leader_transfer(target: &str, context: &ListenerContext) -> String {
+    let target = match context.processes.read() {
+        Ok(guard) => choose_transfer_target(target, &guard),
+        Err(e) => {
+            error!("Error al obtener el guard de procesos: {}", e);
+            return "error".to_string();
+        }
+    };
+    let target = match target {
+        Ok(target) => target,
+        Err(answer) => return answer,
+    };
+
+    // ? el traspaso lo hace el hilo de trabajo, que deja de atender trips mientras tanto
+    match context.work_tx.send(WorkCommand::TransferLeadership(target)) {
+        Ok(_) => format!("OK {}", target),
+        Err(e) => {
+            error!("Error al pedir el traspaso de liderazgo: {}", e);
+            "error".to_string()
+        }
+    }
+}
+
+/// Nodo al que traspasar el liderazgo pedido con `TRANSFER LEADER [pid]`, o la respuesta de error si no se puede.
+///
+/// Si no se indica a quien, el liderazgo pasa al nodo elegible de mejor rango.
+pub(crate) fn choose_transfer_target(target: &str, processes: &[Process]) -> Result<u32, String> {
+    let requested = match target {
+        "" => None,
+        id => match id.parse::<u32>() {
+            Ok(id) => Some(id),
+            Err(_) => return Err("error".to_string()),
+        },
+    };
+
+    if !processes.iter().any(|process| process.me && process.leader) {
+        let leader = processes.iter().find(|process| process.leader).map_or("?".to_string(), |leader| leader.id.to_string());
+        return Err(format!("{} {}", NOT_LEADER_ANSWER, leader));
+    }
+
+    let candidates = processes.iter().filter(|process| !process.me && !process.is_observer());
+    let target = match requested {
+        Some(id) => candidates.into_iter().find(|process| process.id == id),
+        None => candidates.max_by_key(|process| process.election_rank()),
+    };
+    match target {
+        Some(target) => Ok(target.id),
+        None => {
+            warn!("No hay un nodo elegible al que traspasar el liderazgo");
+            Err("error".to_string())
+        }
+    }
+}
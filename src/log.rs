@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::election::Term;
+use crate::utils::json::JsonValue;
+
+// * Registro de eventos del nodo. Cada linea lleva la hora, el nivel, el id del nodo, su term y el modulo que la
+// * escribio (el target), para poder distinguir la salida de varios nodos intercalada. Se escribe legible o en JSON, y
+// * el nivel se elige por target: `info,election=debug,utils::tcp=warn`. Se configura con --log-level y --log-format
+// * al iniciar o con el comando LOG por el canal de administracion.
+// * Los errores y advertencias van a stderr y el resto a stdout.
+
+/// Nivel de un mensaje, de mas a menos grave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Level, String> {
+        match LEVELS.iter().find(|level| level.name() == name.trim()) {
+            Some(level) => Ok(*level),
+            None => Err(format!("nivel desconocido: {}. Debe ser uno de: error, warn, info, debug, trace", name.trim())),
+        }
+    }
+}
+
+// ? nivel por defecto y excepciones por target. Gana el target mas especifico que coincide.
+struct Filter {
+    default: Level,
+    targets: Vec<(String, Level)>,
+}
+
+impl Filter {
+    fn level_for(&self, target: &str) -> Level {
+        self.targets.iter()
+            .filter(|(prefix, _)| target == prefix || target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.name())?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level.name())?;
+        }
+        Ok(())
+    }
+}
+
+// ? "info,election=debug": un nivel suelto cambia el de por defecto, "target=nivel" agrega una excepcion
+fn parse_filter(spec: &str) -> Result<Filter, String> {
+    let mut filter = Filter { default: Level::Info, targets: Vec::new() };
+    for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('=') {
+            Some((target, level)) => filter.targets.push((target.trim().to_string(), Level::parse(level)?)),
+            None => filter.default = Level::parse(part)?,
+        }
+    }
+    Ok(filter)
+}
+
+struct Context {
+    node: u32,
+    term: Arc<Term>,
+}
+
+static FILTER: RwLock<Filter> = RwLock::new(Filter { default: Level::Info, targets: Vec::new() });
+static JSON: AtomicBool = AtomicBool::new(false);
+static CONTEXT: OnceLock<Context> = OnceLock::new();
+
+fn filter() -> RwLockReadGuard<'static, Filter> {
+    match FILTER.read() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Indica el nodo cuyo id y term se agregan a cada linea. Sin esto (por ejemplo en las pruebas) se muestra `-`.
+pub(crate) fn init(node: u32, term: Arc<Term>) {
+    let _ = CONTEXT.set(Context { node, term });
+}
+
+/// Reemplaza los niveles por los de `spec` y devuelve como quedaron.
+pub(crate) fn set_level(spec: &str) -> Result<String, String> {
+    let parsed = parse_filter(spec)?;
+    let mut filter = match FILTER.write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *filter = parsed;
+    Ok(filter.to_string())
+}
+
+/// Cambia el formato de salida: `human` o `json`.
+pub(crate) fn set_format(format: &str) -> Result<String, String> {
+    match format.trim() {
+        "human" => JSON.store(false, Ordering::SeqCst),
+        "json" => JSON.store(true, Ordering::SeqCst),
+        other => return Err(format!("formato desconocido: {}. Debe ser human o json", other)),
+    }
+    Ok(format.trim().to_string())
+}
+
+/// La configuracion actual, como `level=info,election=debug format=human`.
+pub(crate) fn current() -> String {
+    let format = if JSON.load(Ordering::SeqCst) { "json" } else { "human" };
+    format!("level={} format={}", filter(), format)
+}
+
+// ? el target es la ruta del modulo sin el nombre del crate: "election::raft", "listener"
+fn target(module_path: &str) -> &str {
+    module_path.split_once("::").map_or("main", |(_, target)| target)
+}
+
+pub(crate) fn enabled(level: Level, module_path: &str) -> bool {
+    level <= filter().level_for(target(module_path))
+}
+
+// ? dias desde 1970-01-01 a fecha civil (algoritmo de Howard Hinnant)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Hora UTC en formato RFC 3339 con milisegundos, a partir del tiempo desde 1970.
+pub(crate) fn format_timestamp(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60, since_epoch.subsec_millis()
+    )
+}
+
+fn timestamp() -> String {
+    format_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+/// Escribe un mensaje ya filtrado. Se usa a traves de las macros `error!`, `warn!`, `info!`, `debug!` y `trace!`.
+pub(crate) fn write(level: Level, module_path: &str, args: fmt::Arguments) {
+    let context = CONTEXT.get().map(|context| (context.node, context.term.current()));
+    let line = if JSON.load(Ordering::SeqCst) {
+        let number = |value: Option<u64>| value.map_or(JsonValue::Null, |value| JsonValue::Number(value as f64));
+        JsonValue::Object(vec![
+            ("ts".to_string(), JsonValue::String(timestamp())),
+            ("level".to_string(), JsonValue::String(level.name().to_string())),
+            ("node".to_string(), number(context.map(|(node, _)| node as u64))),
+            ("term".to_string(), number(context.map(|(_, term)| term))),
+            ("target".to_string(), JsonValue::String(target(module_path).to_string())),
+            ("msg".to_string(), JsonValue::String(args.to_string())),
+        ]).to_string()
+    } else {
+        let (node, term) = match context {
+            Some((node, term)) => (node.to_string(), term.to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        format!("{} {:<5} nodo={} term={} {}: {}", timestamp(), level.name().to_uppercase(), node, term, target(module_path), args)
+    };
+
+    // ? una sola escritura por linea, para que no se mezclen las de distintos hilos
+    let _ = if level <= Level::Warn {
+        writeln!(std::io::stderr().lock(), "{}", line)
+    } else {
+        writeln!(std::io::stdout().lock(), "{}", line)
+    };
+}
+
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level, module_path!()) {
+            $crate::log::write($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { log_at!($crate::log::Level::Error, $($arg)+) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { log_at!($crate::log::Level::Warn, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { log_at!($crate::log::Level::Info, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { log_at!($crate::log::Level::Debug, $($arg)+) };
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => { log_at!($crate::log::Level::Trace, $($arg)+) };
+}
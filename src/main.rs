@@ -1,3 +1,5 @@
+#[macro_use]
+mod log;
 mod utils;
 mod listener;
 mod process;
@@ -6,40 +8,161 @@ mod healthchecker;
 mod election;
 mod consts;
 mod work_thread;
+mod trips;
+mod shutdown;
+mod supervisor;
+mod metrics;
+mod journal;
+mod status;
+mod clock;
+mod transport;
+mod connections;
+mod worker_pool;
+mod auth;
+mod allowlist;
+mod event_loop;
+#[cfg(test)]
+mod sim;
 
 use utils::arg_handler;
 use utils::file_handler;
-use arg_handler::{check_args, get_process_id, get_process_port, get_other_processes_filename, get_other_processes, check_pid_and_port};
-use std::sync::{Arc, RwLock};
+use arg_handler::{check_args, get_process_id, get_process_port, get_other_processes_filename, get_other_processes, check_pid_and_port, get_bind_ip, get_advertise_host, get_work_port, get_election_strategy, is_majority_quorum_enabled, get_faults, get_term, get_trips, get_log_level, get_log_format, get_metrics_port, get_journal_path, is_event_loop_runtime, get_cluster_key, get_client_messages};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::process::exit;
+use std::sync::{Arc, Mutex, RwLock};
 use std::sync::mpsc::channel;
-use crate::listener::listen_for_process_messages;
+use std::thread::JoinHandle;
+use crate::journal::Event;
+use crate::listener::{listen_for_process_messages, ListenerContext};
 use crate::process::Process;
+use crate::trips::Trips;
+use crate::utils::peer_addr::{Host, PeerAddr};
+use crate::shutdown::{fail_node, has_node_failed, install_signal_handlers, is_shutdown_requested, StopSignal, STOP_POLL_INTERVAL};
 
-fn push_me(mut other_processes: Vec<Process>, pid: u32, port: u32) -> Vec<Process> {
-    other_processes.push(Process {
-        id: pid,
-        ip: "0.0.0.0".to_string(),
-        port,
-        leader: false,
-        me: true
-    });
+// ? IP que anunciamos si no figuramos en el archivo y no se indico --advertise
+fn default_advertise_host(bind_ip: IpAddr) -> Host {
+    match bind_ip {
+        IpAddr::V4(ip) if ip.is_unspecified() => Host::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        IpAddr::V6(ip) if ip.is_unspecified() => Host::Ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+        ip => Host::Ip(ip),
+    }
+}
+
+// ? si el proceso figura en el archivo lo marcamos como propio, si no lo agregamos
+fn push_me(mut other_processes: Vec<Process>, pid: u32, port: u16, bind_ip: IpAddr, advertise_host: Option<Host>, work_port: Option<u16>) -> Vec<Process> {
+    if let Some(me) = other_processes.iter_mut().find(|process| process.id == pid) {
+        me.me = true;
+        if let Some(host) = advertise_host {
+            me.addr = PeerAddr::new(host, me.addr.port);
+        }
+        if work_port.is_some() {
+            me.work_port = work_port;
+        }
+        return other_processes;
+    }
+
+    let mut me = Process::new(pid, PeerAddr::new(advertise_host.unwrap_or_else(|| default_advertise_host(bind_ip)), port));
+    me.me = true;
+    me.work_port = work_port;
+    other_processes.push(me);
 
     other_processes
 }
 
+// ? detiene un thread y espera a que termine
+fn stop_and_join(name: &str, stop: &StopSignal, handle: JoinHandle<()>) {
+    stop.stop();
+    match handle.join(){
+        Ok(_) => info!("Hilo de {} detenido", name),
+        Err(_) => { error!("Error: El hilo de {} terminó inesperadamente", name); }
+    }
+}
+
+// ? se configura antes que nada, para que todo lo que se escriba respete los niveles pedidos
+fn configure_log() {
+    if let Some(spec) = get_log_level() {
+        if let Err(e) = log::set_level(&spec) {
+            eprintln!("Error: El argumento --log-level es inválido: {}.", e);
+            exit(1);
+        }
+    }
+    if let Some(format) = get_log_format() {
+        if let Err(e) = log::set_format(&format) {
+            eprintln!("Error: El argumento --log-format es inválido: {}.", e);
+            exit(1);
+        }
+    }
+}
+
 fn main() {
 // * Armado de la lista de procesos
     check_args();
+    configure_log();
     let other_processes_filepath = get_other_processes_filename();
     let mut other_processes = get_other_processes(other_processes_filepath.as_ref());
 
     let pid = get_process_id();
     let port = get_process_port();
     check_pid_and_port(pid, port, &other_processes);
-    let work_port; //TODO Recibir puerto de thread work por argumento
+    let bind_ip = get_bind_ip();
+    // ? la red y el reloj reales; el mismo entorno lo comparten la estrategia, el listener y el healthchecker
+    let election_env = election::ElectionEnv::system(get_term());
+    let trips = get_trips();
+    log::init(pid, Arc::clone(&election_env.term));
+    let election_strategy = get_election_strategy(election_env.clone());
+    let event_loop_runtime = is_event_loop_runtime();
+    // ? la maquina del event-loop solo implementa bully sin quorum: no se cae en silencio a otra estrategia
+    if event_loop_runtime && (election_strategy.name() != "bully" || is_majority_quorum_enabled()) {
+        let quorum = if is_majority_quorum_enabled() { "majority" } else { "none" };
+        eprintln!("Error: El runtime event-loop solo admite --election bully y --quorum none (se pidió --election {} y --quorum {}).", election_strategy.name(), quorum);
+        exit(1);
+    }
+    if is_majority_quorum_enabled() {
+        info!("Se exige quorum mayoritario para proclamarse lider.");
+        election::require_majority_quorum();
+    }
+
+    info!("Iniciando proceso con ID: {} y PORT: {}", pid, port);
+    other_processes = push_me(other_processes, pid, port, bind_ip, get_advertise_host(), get_work_port());
+    if let Some(path) = get_journal_path() {
+        if let Err(e) = journal::open(Path::new(&path), pid, Arc::clone(&election_env.term)) {
+            eprintln!("Error: El argumento --journal es inválido: {}.", e);
+            exit(1);
+        }
+        journal::record(Event::Started { members: other_processes.iter().map(|process| process.id).collect() });
+    }
+    if let Some(spec) = get_faults() {
+        match utils::faults::configure(&spec, &other_processes) {
+            Ok(faults) => info!("Inyección de fallas activa: {}", faults),
+            Err(e) => {
+                eprintln!("Error: El argumento --faults es inválido: {}.", e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(spec) = get_client_messages() {
+        match allowlist::configure(&spec) {
+            Ok(messages) => info!("Mensajes atendidos desde fuera de la lista de procesos: {}", messages),
+            Err(e) => {
+                eprintln!("Error: El argumento --client-messages es inválido: {}.", e);
+                exit(1);
+            }
+        }
+    }
+    if let Some(key) = get_cluster_key() {
+        auth::configure(pid, key);
+        info!("Los mensajes entre nodos se firman con la clave del cluster.");
+    } else {
+        // ? la lista de procesos solo mira la IP: sin clave no hay forma de saber que un mensaje viene de un nodo
+        warn!("Sin --cluster-key: cualquier proceso desde una IP de la lista de procesos puede mandar comandos de administración como SHUTDOWN o TRANSFER LEADER.");
+    }
 
-    println!("Iniciando proceso con ID: {} y PORT: {}", pid, port);
-    other_processes = push_me(other_processes, pid, port);
+    if event_loop_runtime {
+        run_event_loop_node(pid, port, bind_ip, other_processes, &election_env, trips);
+        return;
+    }
 
 // * Alocamos los recursos para poder iniciar los threads de liderazgo y subordinacion
     // ? los tx trasmiten al thread de election (son para los threads heartbeat y listener), el rx recibe de los threads de election y heartbeat
@@ -53,64 +176,130 @@ fn main() {
     // ? el tx trasmite al thread de heartbeat, el rx recibe del thread listener
     let (tx_heartbeat_thread, rx_listener_thread) = channel();
 
+    // ? el tx trasmite al thread de trabajo pedidos de resincronizacion y traspaso de liderazgo, el rx recibe del thread listener
+    let (tx_work_commands, rx_work_commands) = channel();
+
     // ? mantenemos referencias de lectura para que los threads puedan saber que procesos hay, sus datos y quien es el lider
     let other_processes_mutex = Arc::new(RwLock::new(other_processes));
     let other_processes1_read_ref = Arc::clone(&other_processes_mutex);
     let other_processes2_read_ref = Arc::clone(&other_processes_mutex);
     let other_processes3_read_ref = Arc::clone(&other_processes_mutex);
+    let other_processes4_read_ref = Arc::clone(&other_processes_mutex);
+    let other_processes5_read_ref = Arc::clone(&other_processes_mutex);
+
+    // ? una señal de corte por thread, para poder detenerlos en orden
+    let listener_stop = StopSignal::new();
+    let heartbeat_stop = StopSignal::new();
+    let election_stop = StopSignal::new();
+    let work_stop = StopSignal::new();
+    let process_list_stop = StopSignal::new();
+    let metrics_stop = StopSignal::new();
+    install_signal_handlers();
 
     //TODO Considerar si es necesario conocer que proceso es lider. Quizas no es necesario y se puede sacar el thread de process_handler para simplificar.
 // * Iniciamos los threads de liderazgo y subordinacion
     // ? iniciamos el thread que gestionara el estado de la lista de procesos. Puede recibir mensajes de:
     //   * listener thread: "new leader {pid}": indica que el proceso con pid es el nuevo lider
     //   * election thread: "new leader {pid}": indica que el proceso con pid es el nuevo lider
-    let process_list_handler = procceses_list_handler::start_process_list_handling(other_processes_mutex, rx_election_listener_thread);
+    let process_list_handler = procceses_list_handler::start_process_list_handling(Arc::clone(&other_processes_mutex), rx_election_listener_thread, process_list_stop.clone());
 
     // ? iniciamos el thread que escuchara y gestionara los mensajes de otros nodos. Se comunica con:
     //   * election thread: "ELECTION": indica que se debe iniciar un proceso de eleccion
     //   * heartbeat thread: "HEARTBEAT": indica que se recibio un heartbeat
-    //   * process list handler: "new leader {pid}": indica que el proceso con pid es el nuevo lider
-    let listener_thread_handler = listen_for_process_messages(port, tx_process_handler, tx_heartbeat_thread, tx_election_thread);
+    //   * process list handler: "new leader {pid} {term}": indica que el proceso con pid es el nuevo lider
+    //   * work thread: resincronizar los trips tras un split brain o traspasar el liderazgo
+    // ? los mensajes propios del algoritmo de eleccion los atiende la estrategia elegida
+    let listener_context = ListenerContext {
+        process_handler_tx: tx_process_handler,
+        heartbeat_tx: tx_heartbeat_thread,
+        election_tx: tx_election_thread,
+        work_tx: tx_work_commands,
+        processes: other_processes4_read_ref,
+        election_strategy: Arc::clone(&election_strategy),
+        last_heartbeat: Mutex::new(None),
+    };
+    let listener_thread_handler = listen_for_process_messages(bind_ip, port, listener_context, listener_stop.clone());
 
     // ? iniciamos el thread que maneja los heartbeats (enviando o esperando recibirlos segun el rol del proceso). Se comunica con:
     //   * election thread: "ELECTION": indica que se debe iniciar un proceso de eleccion
     // ? recibe mensajes de:
     //   * listener thread: "HEARTBEAT": indica que se recibio un heartbeat
-    let heartbeat_thread_handler = healthchecker::start_healthcheck_thread(rx_listener_thread, tx_election_thread1, other_processes1_read_ref);
+    let heartbeat_thread_handler = healthchecker::start_healthcheck_thread(rx_listener_thread, tx_election_thread1, other_processes1_read_ref, election_env.clone(), heartbeat_stop.clone());
 
     // ? iniciamos el thread de eleccion de lider. Se comunica con:
     //   * process list handler: "new leader {pid}": indica que el proceso con pid es el nuevo lider
     // ? recibe mensajes de:
     //   * listener thread: "ELECTION": indica que se debe iniciar un proceso de eleccion
     //   * heartbeat thread: "ELECTION": indica que se debe iniciar un proceso de eleccion
-    let election_thread_handler = election::start_election_thread(other_processes2_read_ref, rx_heartbeat_listener_thread, tx_process_handler1);
+    let election_thread_handler = election::start_election_thread(other_processes2_read_ref, rx_heartbeat_listener_thread, tx_process_handler1, election_strategy, election_stop.clone());
 
     // ? iniciamos el thread de trabajo, que se encarga de comportarse como subordinado o lider segun corresponda. Esto lo sabe por el estado de la lista de procesos.
-    let work_thread_handler = work_thread::start_work_thread(other_processes3_read_ref);
+    let work_thread_handler = work_thread::start_work_thread(other_processes3_read_ref, Arc::clone(&election_env.term), trips, bind_ip, rx_work_commands, work_stop.clone());
+
+    // ? iniciamos el thread que exporta las metricas por HTTP, si se pidio un puerto
+    let metrics_thread_handler = get_metrics_port().map(|metrics_port| {
+        metrics::start_metrics_server(bind_ip, metrics_port, other_processes5_read_ref, Arc::clone(&election_env.term), metrics_stop.clone())
+    });
 
-// * Espera de que los otros threads se cierren
-    match listener_thread_handler.join(){
-        Ok(_) => {},
-        Err(_) => { eprintln!("Error: El hilo de manejo de mensajes terminó inesperadamente"); }
+// * Esperamos un pedido de apagado (SIGINT, SIGTERM, comando SHUTDOWN o falla de un thread)
+    while !is_shutdown_requested() {
+        std::thread::sleep(STOP_POLL_INTERVAL);
     }
 
-    match process_list_handler.join(){
-        Ok(_) => {},
-        Err(_) => { eprintln!("Error: El hilo de manejo de la lista de procesos terminó inesperadamente"); }
+// * Apagado ordenado
+    info!("Apagando el proceso {}...", pid);
+
+    // ? si soy lider aviso que renuncio antes de dejar de atender mensajes
+    election::step_down_if_leader(election_env.transport.as_ref(), &other_processes_mutex);
+
+    // ? primero dejamos de recibir mensajes y luego frenamos a quienes los consumen
+    stop_and_join("manejo de mensajes", &listener_stop, listener_thread_handler);
+    stop_and_join("heartbeat", &heartbeat_stop, heartbeat_thread_handler);
+    stop_and_join("eleccion", &election_stop, election_thread_handler);
+    stop_and_join("trabajo", &work_stop, work_thread_handler);
+    stop_and_join("manejo de la lista de procesos", &process_list_stop, process_list_handler);
+    if let Some(handle) = metrics_thread_handler {
+        stop_and_join("metricas", &metrics_stop, handle);
     }
 
-    match heartbeat_thread_handler.join(){
-        Ok(_) => {},
-        Err(_) => { eprintln!("Error: El hilo de eleccion terminó inesperadamente"); }
+    report_exit(pid);
+}
+
+// ? con el runtime event-loop el protocolo corre en el hilo principal; el trabajo y las metricas siguen en sus hilos
+fn run_event_loop_node(pid: u32, port: u16, bind_ip: IpAddr, processes: Vec<Process>, election_env: &election::ElectionEnv, trips: Trips) {
+    let (tx_work_commands, rx_work_commands) = channel();
+    // ? copia de la lista de procesos que el loop mantiene al dia para los otros hilos
+    let processes_mirror = Arc::new(RwLock::new(processes.clone()));
+    let work_stop = StopSignal::new();
+    let metrics_stop = StopSignal::new();
+    install_signal_handlers();
+
+    procceses_list_handler::print_processes(&processes_mirror);
+    let work_thread_handler = work_thread::start_work_thread(Arc::clone(&processes_mirror), Arc::clone(&election_env.term), trips, bind_ip, rx_work_commands, work_stop.clone());
+    let metrics_thread_handler = get_metrics_port().map(|metrics_port| {
+        metrics::start_metrics_server(bind_ip, metrics_port, Arc::clone(&processes_mirror), Arc::clone(&election_env.term), metrics_stop.clone())
+    });
+
+    // ? vuelve cuando se pide el apagado, despues de renunciar si era lider
+    if let Err(e) = event_loop::run(bind_ip, port, processes, processes_mirror, election_env, tx_work_commands) {
+        error!("Error en el runtime event-loop: {}", e);
+        fail_node();
     }
 
-    match election_thread_handler.join(){
-        Ok(_) => {},
-        Err(_) => { eprintln!("Error: El hilo de eleccion terminó inesperadamente"); }
+    info!("Apagando el proceso {}...", pid);
+    stop_and_join("trabajo", &work_stop, work_thread_handler);
+    if let Some(handle) = metrics_thread_handler {
+        stop_and_join("metricas", &metrics_stop, handle);
     }
 
-    match work_thread_handler.join(){
-        Ok(_) => {},
-        Err(_) => { eprintln!("Error: El hilo de trabajo terminó inesperadamente"); }
+    report_exit(pid);
+}
+
+fn report_exit(pid: u32) {
+    if has_node_failed() {
+        error!("Error: El proceso {} se apagó por una falla irrecuperable", pid);
+        exit(1);
     }
-}
\ No newline at end of file
+
+    info!("Proceso {} apagado correctamente", pid);
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::election::Term;
+use crate::process::Process;
+use crate::shutdown::StopSignal;
+use crate::status::role;
+use crate::supervisor::spawn_supervised;
+use crate::utils::tcp::{accept_until_stopped, get_tcp_listener_or_kill_process, write_bytes_to_stream};
+
+// * Contadores de eventos del nodo. Son globales para poder registrarlos desde cualquier hilo.
+// * Con --metrics-port se exportan en el formato de texto de Prometheus por HTTP, en GET /metrics.
+
+// ? tiempo maximo que se espera el pedido HTTP, para que un cliente lento no trabe el hilo
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const ROLES: [&str; 3] = ["leader", "follower", "observer"];
+// ? conflictos de lider ya contados; se olvidan primero los de terms mas viejos
+const SPLIT_BRAINS_REMEMBERED: usize = 64;
+
+/// Contador que solo crece, con su nombre y descripcion para Prometheus.
+pub(crate) struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Counter {
+        Counter { name, help, value: AtomicU64::new(0) }
+    }
+
+    // ? suma uno y devuelve el total
+    pub(crate) fn increment(&self) -> u64 {
+        self.value.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+pub(crate) static ELECTIONS_STARTED: Counter = Counter::new("concurride_elections_started_total", "Elecciones iniciadas por este nodo.");
+pub(crate) static ELECTIONS_WON: Counter = Counter::new("concurride_elections_won_total", "Elecciones iniciadas por este nodo que terminaron con el como lider.");
+pub(crate) static ELECTIONS_LOST: Counter = Counter::new("concurride_elections_lost_total", "Elecciones iniciadas por este nodo que terminaron con otro lider.");
+pub(crate) static HEARTBEATS_SENT: Counter = Counter::new("concurride_heartbeats_sent_total", "Heartbeats enviados como lider.");
+pub(crate) static HEARTBEATS_RECEIVED: Counter = Counter::new("concurride_heartbeats_received_total", "Heartbeats recibidos.");
+pub(crate) static HEARTBEATS_MISSED: Counter = Counter::new("concurride_heartbeats_missed_total", "Veces que vencio el timeout de heartbeat.");
+pub(crate) static TRIPS_ACCEPTED: Counter = Counter::new("concurride_trips_accepted_total", "Trips creados por este nodo como lider y confirmados al cliente.");
+pub(crate) static TRIPS_COMMITTED: Counter = Counter::new("concurride_trips_committed_total", "Trips que quedaron guardados en la mayoria del cluster.");
+pub(crate) static TRIPS_FAILED: Counter = Counter::new("concurride_trips_failed_total", "Trips rechazados o que no llegaron a la mayoria del cluster.");
+pub(crate) static CONNECTIONS_SHED: Counter = Counter::new("concurride_connections_shed_total", "Conexiones rechazadas por sobrecarga del listener.");
+// ? veces que este nodo detecto a otro lider al mismo tiempo que el
+pub(crate) static SPLIT_BRAINS: Counter = Counter::new("concurride_split_brains_total", "Veces que se detecto a otro lider en simultaneo.");
+// ? mensajes rechazados con --cluster-key: sin firma valida, o repetidos y vencidos
+pub(crate) static MESSAGES_UNAUTHENTICATED: Counter = Counter::new("concurride_messages_unauthenticated_total", "Mensajes rechazados por no tener una firma valida.");
+pub(crate) static MESSAGES_REPLAYED: Counter = Counter::new("concurride_messages_replayed_total", "Mensajes firmados rechazados por repetidos, vencidos o dirigidos a otro nodo.");
+pub(crate) static MESSAGES_REFUSED: Counter = Counter::new("concurride_messages_refused_total", "Mensajes y sesiones rechazados por venir de una direccion fuera de la lista de procesos.");
+
+static COUNTERS: [&Counter; 14] = [
+    &ELECTIONS_STARTED, &ELECTIONS_WON, &ELECTIONS_LOST,
+    &HEARTBEATS_SENT, &HEARTBEATS_RECEIVED, &HEARTBEATS_MISSED,
+    &TRIPS_ACCEPTED, &TRIPS_COMMITTED, &TRIPS_FAILED,
+    &CONNECTIONS_SHED, &SPLIT_BRAINS,
+    &MESSAGES_UNAUTHENTICATED, &MESSAGES_REPLAYED, &MESSAGES_REFUSED,
+];
+
+// ? hay una eleccion iniciada por este nodo que todavia no tiene ganador
+static ELECTION_PENDING: AtomicBool = AtomicBool::new(false);
+// ? errores de conexion por id de nodo
+static CONNECTION_ERRORS: Mutex<BTreeMap<u32, u64>> = Mutex::new(BTreeMap::new());
+static LAST_HEARTBEAT: Mutex<Option<Instant>> = Mutex::new(None);
+// ? (term, lider) de los split brains ya contados: el otro lider manda un heartbeat por intervalo hasta que se resuelve
+static SPLIT_BRAINS_SEEN: Mutex<BTreeSet<(u64, u32)>> = Mutex::new(BTreeSet::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// ? registra un split brain con `claimant` como el otro lider en `term` y devuelve cuantos se detectaron en total.
+// ? Cada conflicto se cuenta una sola vez: si ya se habia registrado devuelve None.
+pub(crate) fn record_split_brain(claimant: u32, term: u64) -> Option<u64> {
+    let mut seen = lock(&SPLIT_BRAINS_SEEN);
+    if !seen.insert((term, claimant)) {
+        return None;
+    }
+    if seen.len() > SPLIT_BRAINS_REMEMBERED {
+        seen.pop_first();
+    }
+    Some(SPLIT_BRAINS.increment())
+}
+
+pub(crate) fn record_election_started() {
+    ELECTIONS_STARTED.increment();
+    ELECTION_PENDING.store(true, Ordering::SeqCst);
+}
+
+// ? si este nodo tenia una eleccion en curso, cuenta si la gano o la perdio
+pub(crate) fn record_leader_elected(i_am_leader: bool) {
+    if !ELECTION_PENDING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    if i_am_leader {
+        ELECTIONS_WON.increment();
+    } else {
+        ELECTIONS_LOST.increment();
+    }
+}
+
+pub(crate) fn record_heartbeat_received() {
+    HEARTBEATS_RECEIVED.increment();
+    *lock(&LAST_HEARTBEAT) = Some(Instant::now());
+}
+
+pub(crate) fn record_connection_error(peer: u32) {
+    *lock(&CONNECTION_ERRORS).entry(peer).or_default() += 1;
+}
+
+fn write_header(text: &mut String, name: &str, kind: &str, help: &str) {
+    text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+}
+
+/// Todas las metricas en el formato de texto de Prometheus. El rol y el term se leen en el momento.
+pub(crate) fn render(processes: &[Process], term: u64) -> String {
+    let mut text = String::new();
+    for counter in COUNTERS {
+        write_header(&mut text, counter.name, "counter", counter.help);
+        text.push_str(&format!("{} {}\n", counter.name, counter.value.load(Ordering::SeqCst)));
+    }
+
+    write_header(&mut text, "concurride_peer_connection_errors_total", "counter", "Errores al comunicarse con cada nodo.");
+    for (peer, errors) in lock(&CONNECTION_ERRORS).iter() {
+        text.push_str(&format!("concurride_peer_connection_errors_total{{peer=\"{}\"}} {}\n", peer, errors));
+    }
+
+    write_header(&mut text, "concurride_term", "gauge", "Term actual del nodo.");
+    text.push_str(&format!("concurride_term {}\n", term));
+
+    // ? un valor por rol posible, en 1 el actual
+    let current_role = processes.iter().find(|process| process.me).map(role);
+    write_header(&mut text, "concurride_role", "gauge", "Rol actual del nodo.");
+    for name in ROLES {
+        text.push_str(&format!("concurride_role{{role=\"{}\"}} {}\n", name, u8::from(current_role == Some(name))));
+    }
+
+    // ? sin heartbeats recibidos no hay valor que informar
+    write_header(&mut text, "concurride_seconds_since_last_heartbeat", "gauge", "Segundos desde el ultimo heartbeat recibido.");
+    if let Some(last) = *lock(&LAST_HEARTBEAT) {
+        text.push_str(&format!("concurride_seconds_since_last_heartbeat {:.3}\n", last.elapsed().as_secs_f64()));
+    }
+
+    text
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body)
+}
+
+fn handle_request(mut stream: TcpStream, processes: &Arc<RwLock<Vec<Process>>>, term: &Term) {
+    if let Err(e) = stream.set_read_timeout(Some(REQUEST_TIMEOUT)) {
+        error!("Error al configurar timeout en la conexión: {}", e);
+        return;
+    }
+
+    // ? solo importa la primera linea: "GET /metrics HTTP/1.1"
+    let mut buffer = [0; 1024];
+    let bytes_read = match stream.read(&mut buffer) {
+        Ok(bytes_read) => bytes_read,
+        Err(e) => {
+            warn!("Error al leer el pedido de metricas: {}", e);
+            return;
+        }
+    };
+    let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => match processes.read() {
+            Ok(guard) => http_response("200 OK", "text/plain; version=0.0.4", &render(&guard, term.current())),
+            Err(e) => {
+                error!("Error al obtener el guard de procesos: {}", e);
+                http_response("500 Internal Server Error", "text/plain", "error\n")
+            }
+        },
+        (Some("GET"), _) => http_response("404 Not Found", "text/plain", "not found\n"),
+        _ => http_response("405 Method Not Allowed", "text/plain", "method not allowed\n"),
+    };
+
+    if let Err(e) = write_bytes_to_stream(&mut stream, response.as_bytes()) {
+        warn!("Error al enviar las metricas: {}", e);
+    }
+}
+
+/// Atiende GET /metrics en `port` hasta que se detenga el hilo.
+pub(crate) fn start_metrics_server(bind_ip: IpAddr, port: u16, processes: Arc<RwLock<Vec<Process>>>, term: Arc<Term>, stop: StopSignal) -> JoinHandle<()> {
+    let listener = get_tcp_listener_or_kill_process(bind_ip, port);
+    if let Err(e) = listener.set_nonblocking(true) {
+        error!("Error al configurar el socket como no bloqueante: {}", e);
+        std::process::exit(1);
+    }
+
+    spawn_supervised("metrics", stop.clone(), move || {
+        info!("Exportando metricas en http://{}/metrics", SocketAddr::new(bind_ip, port));
+        while let Some(stream) = accept_until_stopped(&listener, &stop) {
+            handle_request(stream, &processes, &term);
+        }
+        Ok(())
+    })
+}
@@ -1,58 +1,120 @@
 use std::process::exit;
 use std::sync::{Arc, RwLock};
+use std::sync::mpsc::RecvTimeoutError;
 use std::thread;
+use crate::journal::{self, Event};
+use crate::metrics::record_leader_elected;
 use crate::process::Process;
-use crate::consts::NEW_LIDER_MSG;
+use crate::consts::{NEW_LIDER_MSG, STEP_DOWN_MSG};
+use crate::shutdown::{StopSignal, STOP_POLL_INTERVAL};
+use crate::status::record_leader_change;
+use crate::supervisor::spawn_supervised;
 
-fn print_processes(processes: &Arc<RwLock<Vec<Process>>>) {
+pub(crate) fn print_processes(processes: &Arc<RwLock<Vec<Process>>>) {
     let processes_guard = match processes.read(){
         Ok(guard) => guard,
         Err(e) => {
-            eprintln!("[Process list handler]: Error al obtener el guard de procesos: {}", e);
+            error!("Error al obtener el guard de procesos: {}", e);
             return;
         }
     };
 
     for process in processes_guard.iter() {
-        println!("[Process list handler]: Process ID: {}, IP: {}, PORT: {}", process.id, process.ip, process.port);
+        info!(
+            "Process ID: {}, HOST: {}, PORT: {}, WORK PORT: {}, PRIORITY: {}, ZONE: {}, ELIGIBLE: {}",
+            process.id,
+            process.addr.host,
+            process.addr.port,
+            process.work_port.map_or("-".to_string(), |port| port.to_string()),
+            process.priority,
+            process.zone.as_deref().unwrap_or("-"),
+            process.eligible
+        );
+    }
+}
+
+/// Id del proceso de un aviso `NEW LEADER {pid} {term}` o `STEP DOWN {pid}`, o `None` si el mensaje esta mal formado.
+pub(crate) fn parse_process_id(msg: &str) -> Option<u32> {
+    msg.split_whitespace().nth(2)?.parse().ok()
+}
+
+pub(crate) fn handle_message(processes: &Arc<RwLock<Vec<Process>>>, msg: &str) {
+    if !msg.starts_with(NEW_LIDER_MSG) && !msg.starts_with(STEP_DOWN_MSG) {
+        return;
+    }
+
+    let mut processes_guard = match processes.write() {
+        Ok(processes) => processes,
+        Err(e) => {
+            error!("Error al obtener el guard write de procesos: {}", e);
+            exit(1); // ? falla catastrofica
+        }
+    };
+
+    apply_message(&mut processes_guard, msg);
+}
+
+/// Aplica a la lista de procesos un aviso de nuevo lider (`NEW LEADER {pid} {term}`) o de renuncia (`STEP DOWN {pid}`).
+pub(crate) fn apply_message(processes: &mut [Process], msg: &str) {
+    // ? Llega un mensaje que avisa que hay un nuevo lider o que un lider renuncio
+    let is_new_leader = msg.starts_with(NEW_LIDER_MSG);
+    if !is_new_leader && !msg.starts_with(STEP_DOWN_MSG) {
+        return;
+    }
+
+    // ? un aviso mal formado se descarta: no puede tirar abajo al nodo
+    let id = match parse_process_id(msg) {
+        Some(id) => id,
+        None => {
+            warn!("Descartando aviso de lider mal formado: {}", msg);
+            return;
+        }
+    };
+
+    // ? "NEW LEADER {pid} {term}": el term puede faltar en mensajes de versiones anteriores
+    let term = msg.split_whitespace().nth(3).and_then(|term| term.parse::<u64>().ok());
+    // ? un aviso demorado de una eleccion anterior no puede deshacer una mas nueva
+    let known_term = processes.iter().find(|process| process.leader).and_then(|leader| leader.leader_term);
+    if let (true, Some(term), Some(known_term)) = (is_new_leader, term, known_term) {
+        if term < known_term {
+            warn!("Descartando aviso de lider del term {}: ya conozco un lider del term {}", term, known_term);
+            return;
+        }
+    }
+    if is_new_leader {
+        record_leader_change(Some(id), term);
+        journal::record(Event::LeaderChanged { leader: Some(id), term });
+        record_leader_elected(processes.iter().any(|process| process.me && process.id == id));
+    } else {
+        record_leader_change(None, None);
+        journal::record(Event::LeaderChanged { leader: None, term: None });
+    }
+
+    for process in processes.iter_mut() {
+        if is_new_leader {
+            // ? marcamos al nuevo lider y a los demas como no lider
+            process.leader = process.id == id;
+            process.leader_term = if process.leader { term } else { None };
+        } else if process.id == id {
+            // ? el lider renuncio, nos quedamos sin lider hasta la proxima eleccion
+            process.leader = false;
+            process.leader_term = None;
+        }
     }
 }
 
 // ? recibe mensajes del thread de election y de listener que avisan de nuevos lideres
-pub(crate) fn start_process_list_handling(processes: Arc<RwLock<Vec<Process>>>, rx: std::sync::mpsc::Receiver<String>) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        print_processes(&processes);
-
-        for msg in rx {
-            // ? Llega un mensaje que avisa que hay un nuevo lider
-            if msg.starts_with(NEW_LIDER_MSG) {
-                // ? obtenemos su id
-                let new_leader_id = msg.split_whitespace().collect::<Vec<&str>>()[2];
-                let id = match new_leader_id.parse::<u32>() {
-                    Ok(id) => id,
-                    Err(e) => {
-                        eprintln!("[Process list handler]: Error al parsear ID de nuevo lider: {}", e);
-                        exit(1); // ? falla catastrofica
-                    }
-                };
-
-                // ? marcamos al nuevo lider y a los demas como no lider
-                let mut processes_guard = match processes.write() {
-                    Ok(processes) => processes,
-                    Err(e) => {
-                        eprintln!("[Process list handler]: Error al obtener el guard write de procesos: {}", e);
-                        exit(1); // ? falla catastrofica
-                    }
-                };
-
-                for process in processes_guard.iter_mut() {
-                    if process.id == id {
-                        process.leader = true;
-                    } else {
-                        process.leader = false;
-                    }
-                }
+pub(crate) fn start_process_list_handling(processes: Arc<RwLock<Vec<Process>>>, rx: std::sync::mpsc::Receiver<String>, stop: StopSignal) -> thread::JoinHandle<()> {
+    print_processes(&processes);
+
+    spawn_supervised("process list handler", stop.clone(), move || {
+        while !stop.is_stopped() {
+            match rx.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(msg) => handle_message(&processes, &msg),
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
             }
         }
+        Ok(())
     })
-}
\ No newline at end of file
+}
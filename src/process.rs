@@ -1,7 +1,45 @@
+use crate::utils::peer_addr::PeerAddr;
+
+#[derive(Clone)]
 pub(crate) struct Process {
     pub(crate) id: u32,
-    pub(crate) ip: String,
-    pub(crate) port: u32,
+    pub(crate) addr: PeerAddr,
     pub(crate) leader: bool,
+    // ? term en el que se anuncio el lider, si el aviso lo traia
+    pub(crate) leader_term: Option<u64>,
     pub(crate) me: bool,
-}
\ No newline at end of file
+    // ? puerto en el que el hilo de trabajo atiende trips y actualizaciones de estado
+    pub(crate) work_port: Option<u16>,
+    // ? prioridad para la eleccion de lider
+    pub(crate) priority: u32,
+    // ? region o zona en la que corre el nodo, solo informativa
+    pub(crate) zone: Option<String>,
+    // ? si es false, el nodo es un observador: nunca se proclama lider
+    pub(crate) eligible: bool,
+}
+
+impl Process {
+    pub(crate) fn new(id: u32, addr: PeerAddr) -> Process {
+        Process {
+            id,
+            addr,
+            leader: false,
+            leader_term: None,
+            me: false,
+            work_port: None,
+            priority: 0,
+            zone: None,
+            eligible: true,
+        }
+    }
+
+    // ? orden de preferencia para ser lider: primero la prioridad y, a igual prioridad, el id
+    pub(crate) fn election_rank(&self) -> (u32, u32) {
+        (self.priority, self.id)
+    }
+
+    // ? un observador recibe heartbeats y estado replicado pero nunca es lider
+    pub(crate) fn is_observer(&self) -> bool {
+        !self.eligible
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ? cada cuanto los threads revisan si se les pidio que terminen
+pub(crate) const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// ? se setea desde el handler de señales, el comando SHUTDOWN o el supervisor
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+// ? se setea cuando un thread supera la cantidad de reinicios permitidos
+static NODE_FAILED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn fail_node() {
+    NODE_FAILED.store(true, Ordering::SeqCst);
+    request_shutdown();
+}
+
+pub(crate) fn has_node_failed() -> bool {
+    NODE_FAILED.load(Ordering::SeqCst)
+}
+
+/// Señal de corte individual para un thread.
+///
+/// El coordinador mantiene una por thread para poder detenerlos en orden.
+#[derive(Clone, Default)]
+pub(crate) struct StopSignal {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopSignal {
+    pub(crate) fn new() -> StopSignal {
+        StopSignal::default()
+    }
+
+    pub(crate) fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Duerme `duration` revisando periodicamente la señal.
+    /// Devuelve `true` si se pidio detener el thread antes de que termine la espera.
+    pub(crate) fn wait(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+
+        while !self.is_stopped() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            thread::sleep(STOP_POLL_INTERVAL.min(deadline - now));
+        }
+
+        true
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::os::raw::c_int;
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    // ? solo toca un atomico, por lo que es seguro ejecutarlo dentro de un handler de señales
+    extern "C" fn on_signal(_signum: c_int) {
+        super::request_shutdown();
+    }
+
+    pub(crate) fn install() {
+        unsafe {
+            signal(SIGINT, on_signal);
+            signal(SIGTERM, on_signal);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod signals {
+    pub(crate) fn install() {}
+}
+
+// ? SIGINT y SIGTERM dejan de matar el proceso y pasan a pedir un apagado ordenado
+pub(crate) fn install_signal_handlers() {
+    signals::install();
+}
@@ -0,0 +1,678 @@
+mod election_safety;
+mod linearizability;
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::env;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
+use crate::clock::Clock;
+use crate::consts::{NEW_LIDER_MSG, START_ELECTION_MSG};
+use crate::election::{handle_election_request, run_pre_vote, strategy_from_name, ElectionEnv, ElectionStrategy, Term};
+use crate::healthchecker::{i_am_leader, send_heartbeat, HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT};
+use crate::listener::{process_message, ListenerContext};
+use crate::procceses_list_handler;
+use crate::process::Process;
+use crate::transport::Transport;
+use crate::utils::peer_addr::{Host, PeerAddr};
+use crate::utils::random::Rng;
+use crate::trips::Trips;
+use crate::work_thread::{handle_command, handle_work_message, WorkCommand};
+
+// * Cluster simulado en un solo proceso, para probar la eleccion de lider sin sockets ni esperas reales.
+// * La red y el reloj son virtuales: entregar un mensaje llama directamente al listener del destino y el tiempo
+// * solo avanza con las demoras de la red y los ticks del healthchecker. Los clientes envian trips al puerto de trabajo
+// * de los nodos por la misma red. Todo lo aleatorio (demoras, perdidas, el orden de los mensajes y el de los nodos)
+// * sale de una semilla, por lo que la misma semilla reproduce la misma ejecucion.
+
+// ? puerto ficticio de cada nodo, solo para que los procesos tengan una direccion
+const BASE_PORT: u16 = 7000;
+const BASE_WORK_PORT: u16 = 8000;
+// ? los clientes no son nodos del cluster: nunca quedan del otro lado de una particion
+const CLIENT_ID: u32 = 0;
+
+/// Como se comporta la red simulada.
+pub(crate) struct SimConfig {
+    pub(crate) nodes: u32,
+    pub(crate) strategy: &'static str,
+    // ? probabilidad de perder cada mensaje, en porcentaje
+    pub(crate) drop_percent: u64,
+    pub(crate) min_delay: Duration,
+    pub(crate) max_delay: Duration,
+}
+
+impl Default for SimConfig {
+    fn default() -> SimConfig {
+        SimConfig {
+            nodes: 5,
+            strategy: "bully",
+            drop_percent: 0,
+            min_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+// ? a que parte del nodo va un mensaje: el listener o el hilo de trabajo
+#[derive(Clone, Copy)]
+enum Port {
+    Control,
+    Work,
+}
+
+// ? mensaje que todavia no llego a destino: uno enviado sin esperar respuesta o el pedido de un cliente
+struct InFlight {
+    from: u32,
+    to: u32,
+    port: Port,
+    message: String,
+    client: Option<usize>,
+}
+
+/// Lo que recibe un cliente del cluster.
+pub(crate) enum ClientReply {
+    Answer(String),
+    // ? no se pudo conectar con el nodo: el pedido seguro no se aplico
+    Refused,
+    // ? el pedido salio pero no hubo respuesta: pudo haberse aplicado o no
+    Lost,
+}
+
+pub(crate) struct ClientAnswer {
+    pub(crate) client: usize,
+    pub(crate) reply: ClientReply,
+    pub(crate) at: Duration,
+}
+
+// ? lo que atiende el hilo de trabajo de un nodo: sus trips, con la misma lista de procesos y red que el resto del nodo
+struct SimWorker {
+    processes: Arc<RwLock<Vec<Process>>>,
+    transport: Arc<dyn Transport>,
+    trips: Mutex<Trips>,
+    term: Arc<Term>,
+}
+
+impl SimWorker {
+    fn trips(&self) -> MutexGuard<'_, Trips> {
+        match self.trips.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+struct NetworkState {
+    rng: Rng,
+    now: Duration,
+    drop_percent: u64,
+    min_delay: Duration,
+    max_delay: Duration,
+    crashed: BTreeSet<u32>,
+    // ? pares de nodos que no se ven, con el menor id primero
+    partitions: BTreeSet<(u32, u32)>,
+    listeners: BTreeMap<u32, Arc<ListenerContext>>,
+    workers: BTreeMap<u32, Arc<SimWorker>>,
+    // ? los mensajes sin respuesta se entregan en cualquier orden, como conexiones distintas que compiten
+    in_flight: Vec<InFlight>,
+    trace: Vec<String>,
+    client_answers: Vec<ClientAnswer>,
+}
+
+impl NetworkState {
+    // ? por que no llega un mensaje de `from` a `to`, si no llega
+    fn failure(&mut self, from: u32, to: u32) -> Option<&'static str> {
+        if self.crashed.contains(&to) {
+            Some("caido")
+        } else if self.partitions.contains(&link(from, to)) {
+            Some("particion")
+        } else if self.rng.range(0, 100) < self.drop_percent {
+            Some("perdido")
+        } else {
+            None
+        }
+    }
+
+    fn advance_delay(&mut self) {
+        let (min_delay, max_delay) = (self.min_delay.as_micros() as u64, self.max_delay.as_micros() as u64);
+        let delay = self.rng.range(min_delay, max_delay);
+        self.now += Duration::from_micros(delay);
+    }
+}
+
+/// Red y reloj compartidos por todos los nodos de la simulacion.
+pub(crate) struct SimNetwork {
+    state: Mutex<NetworkState>,
+}
+
+fn link(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+impl SimNetwork {
+    fn state(&self) -> MutexGuard<'_, NetworkState> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // ? numero al azar en [0, n) tomado de la semilla de la simulacion
+    fn random(&self, n: usize) -> usize {
+        self.state().rng.range(0, n as u64) as usize
+    }
+
+    // ? el lock de la red se suelta antes de llamar al listener, que puede volver a usar la red y el reloj
+    fn hand_over(&self, from: u32, to: u32, port: Port, message: &str) -> Result<String, String> {
+        let answer = match port {
+            Port::Control => {
+                let listener = self.state().listeners.get(&to).map(Arc::clone);
+                match listener {
+                    Some(listener) => process_message(message, &listener),
+                    None => return Err(format!("[Sim]: No existe el nodo {}", to)),
+                }
+            }
+            Port::Work => {
+                let worker = self.state().workers.get(&to).map(Arc::clone);
+                match worker {
+                    Some(worker) => handle_work_message(message, &worker.processes, &mut worker.trips(), &worker.term, worker.transport.as_ref()),
+                    None => return Err(format!("[Sim]: No existe el nodo {}", to)),
+                }
+            }
+        };
+        self.state().trace.push(format!("  {} <- {}: {}", from, to, answer));
+        Ok(answer)
+    }
+
+    /// Entrega `message` al listener de `to` y devuelve su respuesta.
+    ///
+    /// Si el destino esta caido, del otro lado de una particion o el mensaje se pierde, el emisor espera `timeout`.
+    fn request(&self, from: u32, to: &Process, port: Port, message: &str, timeout: Duration) -> Result<String, String> {
+        {
+            let mut state = self.state();
+            if let Some(failure) = state.failure(from, to.id) {
+                state.now += timeout;
+                let line = format!("{:?} {} -> {}: {} ({})", state.now, from, to.id, message, failure);
+                state.trace.push(line);
+                return Err(format!("[Sim]: No se pudo entregar el mensaje a {}: {}", to.id, failure));
+            }
+
+            state.advance_delay();
+            let line = format!("{:?} {} -> {}: {}", state.now, from, to.id, message);
+            state.trace.push(line);
+        }
+
+        self.hand_over(from, to.id, port, message)
+    }
+
+    // ? un nodo caido o aislado rechaza la conexion; si no, el mensaje queda en viaje y se entrega mas tarde
+    fn post(&self, from: u32, to: &Process, message: &str) -> Result<(), String> {
+        let mut state = self.state();
+        if state.crashed.contains(&to.id) || state.partitions.contains(&link(from, to.id)) {
+            let timeout = state.max_delay;
+            state.now += timeout;
+            let line = format!("{:?} {} -> {}: {} (rechazado)", state.now, from, to.id, message);
+            state.trace.push(line);
+            return Err(format!("[Sim]: No se pudo conectar con {}", to.id));
+        }
+
+        state.in_flight.push(InFlight { from, to: to.id, port: Port::Control, message: message.to_string(), client: None });
+        Ok(())
+    }
+
+    // ? el pedido de un cliente viaja como cualquier mensaje; su respuesta se anota al entregarlo
+    fn submit(&self, client: usize, to: u32, message: &str) {
+        let mut state = self.state();
+        if state.crashed.contains(&to) {
+            let at = state.now;
+            state.client_answers.push(ClientAnswer { client, reply: ClientReply::Refused, at });
+            return;
+        }
+        state.in_flight.push(InFlight { from: CLIENT_ID, to, port: Port::Work, message: message.to_string(), client: Some(client) });
+    }
+
+    // ? entrega un mensaje en viaje elegido al azar. Se pierde si mientras tanto el destino se cayo o quedo aislado.
+    fn deliver_in_flight(&self) {
+        let in_flight = {
+            let mut state = self.state();
+            if state.in_flight.is_empty() {
+                return;
+            }
+            let pending = state.in_flight.len() as u64;
+            let index = state.rng.range(0, pending) as usize;
+            let in_flight = state.in_flight.swap_remove(index);
+
+            if let Some(failure) = state.failure(in_flight.from, in_flight.to) {
+                let line = format!("{:?} {} -> {}: {} ({})", state.now, in_flight.from, in_flight.to, in_flight.message, failure);
+                state.trace.push(line);
+                if let Some(client) = in_flight.client {
+                    let at = state.now;
+                    state.client_answers.push(ClientAnswer { client, reply: ClientReply::Lost, at });
+                }
+                return;
+            }
+            state.advance_delay();
+            let line = format!("{:?} {} -> {}: {}", state.now, in_flight.from, in_flight.to, in_flight.message);
+            state.trace.push(line);
+            in_flight
+        };
+
+        let answer = self.hand_over(in_flight.from, in_flight.to, in_flight.port, &in_flight.message);
+        if let Some(client) = in_flight.client {
+            let reply = answer.map_or(ClientReply::Lost, ClientReply::Answer);
+            let mut state = self.state();
+            let at = state.now;
+            state.client_answers.push(ClientAnswer { client, reply, at });
+        }
+    }
+}
+
+struct SimTransport {
+    from: u32,
+    network: Arc<SimNetwork>,
+}
+
+impl Transport for SimTransport {
+    fn send(&self, to: &Process, message: &str) -> Result<(), String> {
+        self.network.post(self.from, to, message)
+    }
+
+    fn request(&self, to: &Process, message: &str, timeout: Duration) -> Result<String, String> {
+        self.network.request(self.from, to, Port::Control, message, timeout)
+    }
+
+    // ? el hilo de trabajo no pone timeout: se usa la demora maxima de la red
+    fn request_work(&self, to: &Process, message: &str) -> Result<String, String> {
+        let timeout = self.network.state().max_delay;
+        self.network.request(self.from, to, Port::Work, message, timeout)
+    }
+}
+
+struct SimClock {
+    network: Arc<SimNetwork>,
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Duration {
+        self.network.state().now
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.network.state().now += duration;
+    }
+}
+
+// ? un nodo con los mismos canales que arma el main, pero atendidos por el planificador de la simulacion
+struct SimNode {
+    id: u32,
+    processes: Arc<RwLock<Vec<Process>>>,
+    strategy: Arc<dyn ElectionStrategy>,
+    listener: Arc<ListenerContext>,
+    worker: Arc<SimWorker>,
+    handler_tx: Sender<String>,
+    handler_rx: Receiver<String>,
+    election_rx: Receiver<String>,
+    heartbeat_rx: Receiver<String>,
+    work_rx: Receiver<WorkCommand>,
+    pending_elections: VecDeque<String>,
+    // ? como en el healthchecker: ultima vez que se recibio un heartbeat o se reviso el timeout
+    last_heartbeat: Duration,
+}
+
+impl SimNode {
+    // ? hace lo que harian los hilos de manejo de procesos y de trabajo con lo que quedo en los canales.
+    // ? Cada "NEW LEADER {yo} {term}" propio se anota en `claims`.
+    fn dispatch(&mut self, claims: &mut BTreeMap<u64, BTreeSet<u32>>) {
+        while let Ok(message) = self.handler_rx.try_recv() {
+            let parts: Vec<&str> = message.strip_prefix(NEW_LIDER_MSG).unwrap_or("").split_whitespace().collect();
+            if let [id, term] = parts.as_slice() {
+                if let (Ok(id), Ok(term)) = (id.parse::<u32>(), term.parse::<u64>()) {
+                    if id == self.id {
+                        claims.entry(term).or_default().insert(id);
+                    }
+                }
+            }
+            procceses_list_handler::handle_message(&self.processes, &message);
+        }
+        while let Ok(message) = self.election_rx.try_recv() {
+            self.pending_elections.push_back(message);
+        }
+        while self.heartbeat_rx.try_recv().is_ok() {}
+        while let Ok(command) = self.work_rx.try_recv() {
+            handle_command(command, &self.processes, &self.worker.trips, &self.worker.term, self.worker.transport.as_ref());
+        }
+    }
+
+    fn known_leader(&self) -> Option<u32> {
+        match self.processes.read() {
+            Ok(guard) => guard.iter().find(|process| process.leader).map(|leader| leader.id),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Cluster de nodos que corren la eleccion de lider sobre la red simulada.
+pub(crate) struct SimCluster {
+    network: Arc<SimNetwork>,
+    clock: Arc<SimClock>,
+    strategy: &'static str,
+    nodes: Vec<SimNode>,
+    // ? nodos que se proclamaron lideres en cada term
+    claims: BTreeMap<u64, BTreeSet<u32>>,
+    // ? cuando le toca a cada nodo revisar los heartbeats, como el intervalo del healthchecker
+    next_tick: Duration,
+}
+
+fn cluster_processes(nodes: u32, me: u32) -> Vec<Process> {
+    (1..=nodes).map(|id| {
+        let mut process = Process::new(id, PeerAddr::new(Host::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)), BASE_PORT + id as u16));
+        process.me = id == me;
+        process.work_port = Some(BASE_WORK_PORT + id as u16);
+        process
+    }).collect()
+}
+
+// ? un nodo recien iniciado: sin lider y con canales vacios. El term y los trips son los que tenia guardados.
+fn start_node(network: &Arc<SimNetwork>, clock: &Arc<SimClock>, strategy: &str, nodes: u32, id: u32, term: Arc<Term>, trips: Trips) -> SimNode {
+    let transport: Arc<dyn Transport> = Arc::new(SimTransport { from: id, network: Arc::clone(network) });
+    let env = ElectionEnv {
+        transport: Arc::clone(&transport),
+        clock: Arc::clone(clock) as Arc<dyn Clock>,
+        term: Arc::clone(&term),
+        seed: network.state().rng.next_u64(),
+    };
+    let strategy = match strategy_from_name(strategy, env) {
+        Some(strategy) => strategy,
+        None => panic!("[Sim]: Algoritmo de eleccion desconocido: {}", strategy),
+    };
+
+    let processes = Arc::new(RwLock::new(cluster_processes(nodes, id)));
+    let (handler_tx, handler_rx) = channel();
+    let (election_tx, election_rx) = channel();
+    let (heartbeat_tx, heartbeat_rx) = channel();
+    let (work_tx, work_rx) = channel();
+    let listener = Arc::new(ListenerContext {
+        process_handler_tx: handler_tx.clone(),
+        heartbeat_tx,
+        election_tx,
+        work_tx,
+        processes: Arc::clone(&processes),
+        election_strategy: Arc::clone(&strategy),
+        last_heartbeat: Mutex::new(None),
+    });
+    let worker = Arc::new(SimWorker { processes: Arc::clone(&processes), transport, trips: Mutex::new(trips), term });
+    {
+        let mut state = network.state();
+        state.listeners.insert(id, Arc::clone(&listener));
+        state.workers.insert(id, Arc::clone(&worker));
+    }
+
+    SimNode {
+        id,
+        processes,
+        strategy,
+        listener,
+        worker,
+        handler_tx,
+        handler_rx,
+        election_rx,
+        heartbeat_rx,
+        work_rx,
+        pending_elections: VecDeque::new(),
+        last_heartbeat: clock.now(),
+    }
+}
+
+impl SimCluster {
+    pub(crate) fn new(seed: u64, config: SimConfig) -> SimCluster {
+        let network = Arc::new(SimNetwork {
+            state: Mutex::new(NetworkState {
+                rng: Rng::new(seed),
+                now: Duration::ZERO,
+                drop_percent: config.drop_percent,
+                min_delay: config.min_delay,
+                max_delay: config.max_delay,
+                crashed: BTreeSet::new(),
+                partitions: BTreeSet::new(),
+                listeners: BTreeMap::new(),
+                workers: BTreeMap::new(),
+                in_flight: Vec::new(),
+                trace: Vec::new(),
+                client_answers: Vec::new(),
+            }),
+        });
+        let clock = Arc::new(SimClock { network: Arc::clone(&network) });
+        let nodes = (1..=config.nodes).map(|id| start_node(&network, &clock, config.strategy, config.nodes, id, Arc::new(Term::new()), Trips::new())).collect();
+
+        SimCluster { network, clock, strategy: config.strategy, nodes, claims: BTreeMap::new(), next_tick: HEARTBEAT_INTERVAL }
+    }
+
+    pub(crate) fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    pub(crate) fn node_ids(&self) -> Vec<u32> {
+        self.nodes.iter().map(|node| node.id).collect()
+    }
+
+    pub(crate) fn set_drop_percent(&self, drop_percent: u64) {
+        self.network.state().drop_percent = drop_percent;
+    }
+
+    // ? un nodo caido no responde ni actua, pero conserva su estado
+    pub(crate) fn crash(&self, id: u32) {
+        self.network.state().crashed.insert(id);
+    }
+
+    // ? el nodo vuelve con el estado que tenia al caerse, como tras una pausa larga
+    pub(crate) fn recover(&mut self, id: u32) {
+        let now = self.now();
+        self.network.state().crashed.remove(&id);
+        if let Some(node) = self.nodes.iter_mut().find(|node| node.id == id) {
+            node.last_heartbeat = now;
+        }
+    }
+
+    // ? el nodo vuelve como un proceso nuevo: pierde el lider y lo que estaba en viaje hacia el, pero conserva el term, el
+    // ? voto y los trips como un nodo iniciado con --state-file
+    pub(crate) fn restart(&mut self, id: u32) {
+        let index = match self.nodes.iter().position(|node| node.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        {
+            let mut state = self.network.state();
+            state.crashed.remove(&id);
+            let now = state.now;
+            let (lost, in_flight): (Vec<InFlight>, Vec<InFlight>) = std::mem::take(&mut state.in_flight).into_iter().partition(|in_flight| in_flight.to == id);
+            state.in_flight = in_flight;
+            for client in lost.into_iter().filter_map(|in_flight| in_flight.client) {
+                state.client_answers.push(ClientAnswer { client, reply: ClientReply::Lost, at: now });
+            }
+        }
+        let term = Arc::clone(&self.nodes[index].strategy.env().term);
+        let trips = self.nodes[index].worker.trips().restarted();
+        self.nodes[index] = start_node(&self.network, &self.clock, self.strategy, self.nodes.len() as u32, id, term, trips);
+    }
+
+    // ? corta la comunicacion entre cada nodo de `side` y los que no estan en `side`
+    pub(crate) fn partition(&self, side: &[u32]) {
+        let mut state = self.network.state();
+        for node in self.nodes.iter().filter(|node| !side.contains(&node.id)) {
+            for id in side {
+                state.partitions.insert(link(*id, node.id));
+            }
+        }
+    }
+
+    pub(crate) fn heal(&self) {
+        self.network.state().partitions.clear();
+    }
+
+    pub(crate) fn is_crashed(&self, id: u32) -> bool {
+        self.network.state().crashed.contains(&id)
+    }
+
+    /// Nodos en pie que se creen lideres, con su term.
+    pub(crate) fn leaders(&self) -> Vec<(u32, u64)> {
+        self.nodes.iter()
+            .filter(|node| !self.is_crashed(node.id) && i_am_leader(&node.processes))
+            .map(|node| (node.id, node.strategy.env().term.current()))
+            .collect()
+    }
+
+    /// Lider que conoce cada nodo en pie.
+    pub(crate) fn known_leaders(&self) -> BTreeMap<u32, Option<u32>> {
+        self.nodes.iter()
+            .filter(|node| !self.is_crashed(node.id))
+            .map(|node| (node.id, node.known_leader()))
+            .collect()
+    }
+
+    /// Nodos que se proclamaron lideres en cada term desde que empezo la simulacion.
+    pub(crate) fn claims(&self) -> &BTreeMap<u64, BTreeSet<u32>> {
+        &self.claims
+    }
+
+    /// Mensajes entregados y perdidos hasta ahora, en orden.
+    pub(crate) fn trace(&self) -> Vec<String> {
+        self.network.state().trace.clone()
+    }
+
+    /// Envia `message` al puerto de trabajo de `to` en nombre de `client`. La respuesta se obtiene con `take_client_answers`.
+    pub(crate) fn submit(&self, client: usize, to: u32, message: &str) {
+        self.network.submit(client, to, message);
+    }
+
+    /// Respuestas a los clientes desde la ultima llamada, en el orden en que llegaron.
+    pub(crate) fn take_client_answers(&mut self) -> Vec<ClientAnswer> {
+        std::mem::take(&mut self.network.state().client_answers)
+    }
+
+    pub(crate) fn run_for(&mut self, duration: Duration) {
+        let deadline = self.now() + duration;
+        while self.now() < deadline {
+            self.step();
+        }
+    }
+
+    /// Atiende un pedido de eleccion pendiente o entrega un mensaje en viaje, elegidos al azar.
+    /// Si llego la hora del tick del healthchecker, o no hay nada que atender, hace el tick de todos los nodos.
+    pub(crate) fn step(&mut self) {
+        self.dispatch_all();
+
+        if self.now() < self.next_tick {
+            let ready: Vec<usize> = (0..self.nodes.len())
+                .filter(|&index| !self.nodes[index].pending_elections.is_empty() && !self.is_crashed(self.nodes[index].id))
+                .collect();
+            let in_flight = self.network.state().in_flight.len();
+            if !ready.is_empty() || in_flight > 0 {
+                let choice = self.network.random(ready.len() + in_flight);
+                if choice >= ready.len() {
+                    self.network.deliver_in_flight();
+                    return;
+                }
+
+                let node = &mut self.nodes[ready[choice]];
+                if let Some(message) = node.pending_elections.pop_front() {
+                    handle_election_request(&message, node.strategy.as_ref(), &node.processes, &mut node.handler_tx);
+                }
+                return;
+            }
+
+            let idle = self.next_tick - self.now();
+            self.clock.sleep(idle);
+        }
+
+        self.next_tick = self.now() + HEARTBEAT_INTERVAL;
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        for i in (1..order.len()).rev() {
+            order.swap(i, self.network.random(i + 1));
+        }
+        for index in order {
+            if !self.is_crashed(self.nodes[index].id) {
+                self.tick(index);
+                self.dispatch_all();
+            }
+        }
+    }
+
+    // ? lo mismo que hace el healthchecker en cada intervalo
+    fn tick(&mut self, index: usize) {
+        let now = self.now();
+        let node = &mut self.nodes[index];
+        let env = node.strategy.env();
+
+        if i_am_leader(&node.processes) {
+            send_heartbeat(env, &node.processes);
+            return;
+        }
+
+        let received = match node.listener.last_heartbeat.lock() {
+            Ok(last) => *last,
+            Err(poisoned) => *poisoned.into_inner(),
+        };
+        let last = received.map_or(node.last_heartbeat, |received| received.max(node.last_heartbeat));
+        if now.saturating_sub(last) > HEARTBEAT_TIMEOUT {
+            if run_pre_vote(env.transport.as_ref(), &node.processes) {
+                node.pending_elections.push_back(START_ELECTION_MSG.to_string());
+            }
+            node.last_heartbeat = now;
+        }
+    }
+
+    fn dispatch_all(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.dispatch(&mut self.claims);
+        }
+    }
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+/// Semillas de una prueba de propiedades: las de `regression` y `fixed` mas derivadas de `base`, las mismas en cada
+/// corrida. Con SIM_SEED=<semilla> se corre solo esa, y SIM_SEEDS=<n> agrega n semillas nuevas al azar.
+pub(crate) fn property_seeds(regression: Vec<u64>, base: u64, fixed: usize) -> Vec<u64> {
+    if let Some(seed) = env_number("SIM_SEED") {
+        return vec![seed];
+    }
+
+    let mut seeds = regression;
+    let mut rng = Rng::new(base);
+    seeds.extend((0..fixed).map(|_| rng.next_u64()));
+
+    if let Some(count) = env_number("SIM_SEEDS") {
+        let mut rng = Rng::from_entropy();
+        seeds.extend((0..count).map(|_| rng.next_u64()));
+    }
+    seeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64, strategy: &'static str) -> (Vec<String>, Vec<(u32, u64)>) {
+        let mut cluster = SimCluster::new(seed, SimConfig { strategy, drop_percent: 10, ..SimConfig::default() });
+        cluster.run_for(Duration::from_secs(120));
+        cluster.crash(5);
+        cluster.partition(&[1, 2]);
+        cluster.run_for(Duration::from_secs(300));
+        cluster.heal();
+        cluster.recover(5);
+        cluster.run_for(Duration::from_secs(300));
+        (cluster.trace(), cluster.leaders())
+    }
+
+    #[test]
+    fn same_seed_reproduces_the_same_run() {
+        for strategy in ["bully", "ring", "raft"] {
+            let (trace, leaders) = run(7, strategy);
+            assert!(!trace.is_empty());
+            assert_eq!((trace, leaders), run(7, strategy), "{} no es reproducible", strategy);
+        }
+    }
+}
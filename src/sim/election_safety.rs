@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::healthchecker::{HEARTBEAT_INTERVAL, HEARTBEAT_TIMEOUT};
+use crate::sim::{property_seeds, SimCluster, SimConfig};
+use crate::utils::random::Rng;
+
+// * Pruebas de propiedades de la eleccion de lider sobre el cluster simulado. Cada semilla arma un escenario al azar
+// * (caidas, reinicios, particiones y mensajes perdidos o desordenados) y verifica los invariantes:
+// *   - a lo sumo un lider por term
+// *   - cuando la red sana, todos los nodos en pie terminan siguiendo al mismo lider
+// *   - ese lider no es un nodo caido
+// * Raft cumple el primero en todos los escenarios. Bully y ring no votan: un nodo que no vio las ultimas elecciones,
+// * por una particion, mensajes perdidos o porque estaba caido, pasa al term siguiente del que conocia y puede
+// * proclamarse en un term que ya tiene lider; el conflicto se resuelve despues por heartbeat. Para ellos se verifica
+// * una variante mas debil: con la red sana y solo caidas y reinicios de nodos, dos lideres del mismo term no conviven
+// * mas que lo que tardan en enterarse uno del otro.
+// * Las semillas son siempre las mismas. Para repetir una: SIM_SEED=<semilla> cargo test. SIM_SEEDS=<n> agrega n
+// * semillas nuevas al azar.
+
+// ? semillas que alguna vez fallaron, con su algoritmo: se corren siempre, ademas de las nuevas
+const REGRESSION_SEEDS: &[(&str, u64)] = &[
+    // ? un seguidor ignoraba los heartbeats del lider vivo porque recordaba a otro con un term mayor
+    ("bully", 1293177656032468465),
+    ("ring", 6880378348624487983),
+    // ? un nodo reiniciado perdia el term y el voto, y raft elegia dos lideres en el mismo term
+    ("raft", 15144705749812447699),
+];
+// ? de donde salen las semillas fijas, y cuantas se prueban por algoritmo
+const BASE_SEED: u64 = 0x5eed_e1ec_7104_0001;
+const FIXED_SEEDS: usize = 20;
+const FAULT_PHASES: u64 = 12;
+const MAX_PHASE_SECS: u64 = 180;
+const MAX_DROP_PERCENT: u64 = 10;
+// ? con la red sana, dos lideres del mismo term se enteran uno del otro por los heartbeats antes de esto
+const MAX_TERM_CONFLICT: Duration = HEARTBEAT_TIMEOUT;
+// ? tiempo sin fallas nuevas que se le da al cluster para ponerse de acuerdo
+const SETTLE_TIME: Duration = Duration::from_secs(900);
+
+fn pick(rng: &mut Rng, ids: &[u32]) -> Option<u32> {
+    if ids.is_empty() {
+        return None;
+    }
+    Some(ids[rng.range(0, ids.len() as u64) as usize])
+}
+
+// ? caidas, reinicios y particiones al azar, con tiempo de por medio para que el cluster reaccione
+fn inject_faults(cluster: &mut SimCluster, rng: &mut Rng) {
+    let ids = cluster.node_ids();
+    for _ in 0..FAULT_PHASES {
+        let alive: Vec<u32> = ids.iter().copied().filter(|id| !cluster.is_crashed(*id)).collect();
+        let crashed: Vec<u32> = ids.iter().copied().filter(|id| cluster.is_crashed(*id)).collect();
+
+        match rng.range(0, 5) {
+            0 => if let Some(id) = pick(rng, &alive) {
+                cluster.crash(id);
+            },
+            1 => if let Some(id) = pick(rng, &crashed) {
+                cluster.recover(id);
+            },
+            2 => if let Some(id) = pick(rng, &ids) {
+                cluster.restart(id);
+            },
+            3 => {
+                let side: Vec<u32> = ids.iter().copied().filter(|_| rng.range(0, 2) == 0).collect();
+                cluster.heal();
+                cluster.partition(&side);
+            }
+            _ => cluster.heal(),
+        }
+
+        cluster.run_for(Duration::from_secs(rng.range(1, MAX_PHASE_SECS)));
+    }
+}
+
+// ? sana la red y deja en pie a una mayoria: los caidos de mas vuelven, algunos con su estado y otros reiniciados
+fn stabilize(cluster: &mut SimCluster, rng: &mut Rng) {
+    cluster.heal();
+    cluster.set_drop_percent(0);
+
+    let ids = cluster.node_ids();
+    let mut allowed_down = (ids.len() - 1) / 2;
+    for id in ids {
+        if !cluster.is_crashed(id) {
+            continue;
+        }
+        if allowed_down > 0 {
+            allowed_down -= 1;
+        } else if rng.range(0, 2) == 0 {
+            cluster.recover(id);
+        } else {
+            cluster.restart(id);
+        }
+    }
+}
+
+fn check_one_leader_per_term(cluster: &SimCluster) -> Result<(), String> {
+    match cluster.claims().iter().find(|(_, leaders)| leaders.len() > 1) {
+        Some((term, leaders)) => Err(format!("más de un líder en el term {}: {:?}", term, leaders)),
+        None => Ok(()),
+    }
+}
+
+fn check_agreement(cluster: &SimCluster) -> Result<(), String> {
+    let known = cluster.known_leaders();
+    let leader = match known.values().next() {
+        Some(Some(leader)) => *leader,
+        _ => return Err(format!("no hay acuerdo sobre el líder: {:?}", known)),
+    };
+    if known.values().any(|known| *known != Some(leader)) {
+        return Err(format!("no hay acuerdo sobre el líder: {:?}", known));
+    }
+    if cluster.is_crashed(leader) {
+        return Err(format!("el líder acordado {} está caído", leader));
+    }
+
+    let leaders = cluster.leaders();
+    if leaders.len() != 1 || leaders[0].0 != leader {
+        return Err(format!("los nodos siguen a {} pero se creen líderes {:?}", leader, leaders));
+    }
+    Ok(())
+}
+
+fn run_scenario(strategy: &'static str, seed: u64) -> Result<(), String> {
+    let mut rng = Rng::new(seed);
+    let config = SimConfig {
+        nodes: rng.range(3, 6) as u32,
+        strategy,
+        drop_percent: rng.range(0, MAX_DROP_PERCENT + 1),
+        ..SimConfig::default()
+    };
+    let mut cluster = SimCluster::new(rng.next_u64(), config);
+
+    inject_faults(&mut cluster, &mut rng);
+    stabilize(&mut cluster, &mut rng);
+    cluster.run_for(SETTLE_TIME);
+
+    if strategy == "raft" {
+        check_one_leader_per_term(&cluster)?;
+    }
+    check_agreement(&cluster)
+}
+
+// ? avanza `duration` revisando que ningun term tenga dos lideres en pie por mas de MAX_TERM_CONFLICT. `since` guarda
+// ? desde cuando hay conflicto en cada term.
+fn run_resolving_conflicts(cluster: &mut SimCluster, duration: Duration, since: &mut BTreeMap<u64, Duration>) -> Result<(), String> {
+    let deadline = cluster.now() + duration;
+    while cluster.now() < deadline {
+        cluster.run_for(HEARTBEAT_INTERVAL);
+
+        let mut by_term: BTreeMap<u64, Vec<u32>> = BTreeMap::new();
+        for (id, term) in cluster.leaders() {
+            by_term.entry(term).or_default().push(id);
+        }
+        let now = cluster.now();
+        since.retain(|term, _| by_term.get(term).is_some_and(|leaders| leaders.len() > 1));
+        for (term, leaders) in by_term.iter().filter(|(_, leaders)| leaders.len() > 1) {
+            let start = *since.entry(*term).or_insert(now);
+            if now - start > MAX_TERM_CONFLICT {
+                return Err(format!("{:?} líderes en el term {} durante {:?}", leaders, term, now - start));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ? sin particiones ni mensajes perdidos: solo caen y vuelven nodos, sobre todo el lider
+fn run_crash_scenario(strategy: &'static str, seed: u64) -> Result<(), String> {
+    let mut rng = Rng::new(seed);
+    let config = SimConfig { nodes: rng.range(3, 6) as u32, strategy, ..SimConfig::default() };
+    let mut cluster = SimCluster::new(rng.next_u64(), config);
+
+    let ids = cluster.node_ids();
+    let mut since = BTreeMap::new();
+    for _ in 0..FAULT_PHASES {
+        let alive: Vec<u32> = ids.iter().copied().filter(|id| !cluster.is_crashed(*id)).collect();
+        let crashed: Vec<u32> = ids.iter().copied().filter(|id| cluster.is_crashed(*id)).collect();
+        let leaders: Vec<u32> = cluster.leaders().iter().map(|(id, _)| *id).collect();
+
+        match rng.range(0, 4) {
+            0 | 1 => if let Some(id) = pick(&mut rng, &leaders).or_else(|| pick(&mut rng, &alive)) {
+                cluster.crash(id);
+            },
+            2 => if let Some(id) = pick(&mut rng, &crashed) {
+                cluster.recover(id);
+            },
+            _ => if let Some(id) = pick(&mut rng, &ids) {
+                cluster.restart(id);
+            },
+        }
+
+        let phase = Duration::from_secs(rng.range(1, MAX_PHASE_SECS));
+        run_resolving_conflicts(&mut cluster, phase, &mut since)?;
+    }
+
+    stabilize(&mut cluster, &mut rng);
+    run_resolving_conflicts(&mut cluster, SETTLE_TIME, &mut since)?;
+    check_agreement(&cluster)
+}
+
+fn seeds(strategy: &str) -> Vec<u64> {
+    let regression = REGRESSION_SEEDS.iter().filter(|(name, _)| *name == strategy).map(|(_, seed)| *seed).collect();
+    property_seeds(regression, BASE_SEED, FIXED_SEEDS)
+}
+
+fn check_strategy(strategy: &'static str) {
+    let failures: Vec<String> = seeds(strategy).into_iter()
+        .filter_map(|seed| run_scenario(strategy, seed).and_then(|_| run_crash_scenario(strategy, seed)).err().map(|e| format!("  semilla {}: {}", seed, e)))
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "La elección {} violó un invariante. Agregar las semillas a REGRESSION_SEEDS:\n{}",
+        strategy,
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn bully_election_is_safe() {
+    check_strategy("bully");
+}
+
+#[test]
+fn ring_election_is_safe() {
+    check_strategy("ring");
+}
+
+#[test]
+fn raft_election_is_safe() {
+    check_strategy("raft");
+}
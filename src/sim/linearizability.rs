@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use crate::consts::{NOT_COMMITTED_ANSWER, NOT_FOUND_ANSWER, NOT_LEADER_ANSWER, TRIP_MSG, GET_TRIP_MSG};
+use crate::sim::{property_seeds, ClientReply, SimCluster, SimConfig};
+use crate::utils::random::Rng;
+
+// * Verificador de linealizabilidad del servicio de trips, al estilo de Jepsen. Varios clientes crean y consultan trips
+// * a la vez contra el cluster simulado mientras se hace caer al lider o se reinician nodos, y se anota cuando empezo y termino cada pedido y
+// * que respondio. La historia es linealizable si existe un orden de las operaciones que respeta esos intervalos (una
+// * operacion que termino antes de que otra empiece va primero) y en el que un solo nodo que atiende de a un pedido
+// * habria dado las mismas respuestas.
+// * Las semillas son siempre las mismas. Para repetir una: SIM_SEED=<semilla> cargo test. SIM_SEEDS=<n> agrega n
+// * semillas nuevas al azar.
+
+/// Lo que pidio un cliente.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Call {
+    Create(String),
+    Read(u64),
+}
+
+/// Lo que respondio el servicio.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Outcome {
+    Created(u64),
+    Found(String),
+    NotFound,
+}
+
+/// Una operacion de la historia.
+///
+/// `completed` es `None` si el cliente no recibio respuesta: la operacion pudo haberse aplicado en cualquier momento
+/// desde que se invoco, o nunca.
+#[derive(Debug)]
+pub(crate) struct Operation {
+    pub(crate) client: usize,
+    pub(crate) call: Call,
+    pub(crate) invoked: Duration,
+    pub(crate) completed: Option<(Duration, Outcome)>,
+}
+
+// ? el servicio de trips secuencial: un solo nodo que numera los trips desde 1, sin huecos.
+// ? Cada trip se guarda como el indice de la operacion que lo creo, para que copiar y comparar estados sea barato.
+// ? Los trips sin respuesta que ninguna consulta vio son `None`: solo importa que ocupan un id.
+#[derive(Clone, Default, Hash, PartialEq, Eq)]
+struct Model {
+    trips: Vec<Option<usize>>,
+}
+
+// ? operaciones ya ordenadas, un bit por operacion
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct Done {
+    bits: Vec<u64>,
+}
+
+impl Done {
+    fn new(operations: usize) -> Done {
+        Done { bits: vec![0; operations.div_ceil(64)] }
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    fn with(&self, index: usize) -> Done {
+        let mut done = self.clone();
+        done.bits[index / 64] |= 1 << (index % 64);
+        done
+    }
+}
+
+// ? estado de la busqueda: que operaciones ya se ordenaron y como quedo el modelo
+#[derive(Clone, Hash, PartialEq, Eq)]
+struct Step {
+    done: Done,
+    model: Model,
+}
+
+struct Search<'a> {
+    // ? ordenadas por invocacion
+    operations: Vec<&'a Operation>,
+    // ? altas sin respuesta cuyo trip ninguna consulta vio. Son intercambiables entre si.
+    unseen: Vec<bool>,
+    visited: HashSet<Step>,
+    // ? el estado en el que se ordenaron mas operaciones completas, para explicar el error
+    deepest: (usize, Done),
+}
+
+fn completed_at(operation: &Operation) -> Option<Duration> {
+    operation.completed.as_ref().map(|(at, _)| *at)
+}
+
+fn describe(operation: &Operation) -> String {
+    let completed = match &operation.completed {
+        Some((at, outcome)) => format!("respondió {:?} a los {:?}", outcome, at),
+        None => "sin respuesta".to_string(),
+    };
+    format!("cliente {}: {:?} invocada a los {:?}, {}", operation.client, operation.call, operation.invoked, completed)
+}
+
+impl Search<'_> {
+    // ? lo que hubiera respondido un solo nodo a la operacion `index`. `None` si es un trip que nadie vio.
+    fn outcome(&self, model: &Model, index: usize) -> Option<Outcome> {
+        match &self.operations[index].call {
+            Call::Create(_) => Some(Outcome::Created(model.trips.len() as u64 + 1)),
+            Call::Read(id) => {
+                let trip = (*id as usize).checked_sub(1).and_then(|position| model.trips.get(position));
+                match trip {
+                    Some(Some(creator)) => match &self.operations[*creator].call {
+                        Call::Create(description) => Some(Outcome::Found(description.clone())),
+                        Call::Read(_) => None,
+                    },
+                    Some(None) => None,
+                    None => Some(Outcome::NotFound),
+                }
+            }
+        }
+    }
+
+    // ? busqueda en profundidad de Wing y Gong: en cada paso se puede ordenar cualquier operacion que empezo antes de
+    // ? que termine la primera de las que faltan. Los estados ya visitados se saltean, y de las altas que nadie vio
+    // ? solo se prueba la primera: cualquier orden que use otra vale tambien con esa.
+    fn linearize(&mut self, step: Step, ordered: usize) -> bool {
+        let first_completion = self.operations.iter().enumerate()
+            .filter(|(index, _)| !step.done.contains(*index))
+            .filter_map(|(_, operation)| completed_at(operation))
+            .min();
+        let first_completion = match first_completion {
+            Some(first) => first,
+            None => return true,
+        };
+
+        if ordered > self.deepest.0 {
+            self.deepest = (ordered, step.done.clone());
+        }
+        if !self.visited.insert(step.clone()) {
+            return false;
+        }
+
+        let mut unseen_tried = false;
+        for index in 0..self.operations.len() {
+            let operation = self.operations[index];
+            if operation.invoked > first_completion {
+                break;
+            }
+            if step.done.contains(index) {
+                continue;
+            }
+            if self.unseen[index] {
+                if unseen_tried {
+                    continue;
+                }
+                unseen_tried = true;
+            }
+
+            if let Some((_, observed)) = &operation.completed {
+                if Some(observed) != self.outcome(&step.model, index).as_ref() {
+                    continue;
+                }
+            }
+
+            let mut model = step.model.clone();
+            if let Call::Create(_) = operation.call {
+                model.trips.push(Some(index).filter(|&index| !self.unseen[index]));
+            }
+            let ordered = ordered + operation.completed.is_some() as usize;
+            if self.linearize(Step { done: step.done.with(index), model }, ordered) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Verifica que la historia sea linealizable contra el servicio de trips secuencial.
+///
+/// Si no lo es, el error cuenta cuantas operaciones completas se pudieron ordenar y cuales quedaron sin lugar.
+pub(crate) fn check(history: &[Operation]) -> Result<(), String> {
+    // ? una consulta sin respuesta no cambia nada: da lo mismo que se haya aplicado o no
+    let mut operations: Vec<&Operation> = history.iter()
+        .filter(|operation| operation.completed.is_some() || matches!(operation.call, Call::Create(_)))
+        .collect();
+    operations.sort_by_key(|operation| operation.invoked);
+
+    let seen: HashSet<&String> = operations.iter()
+        .filter_map(|operation| match &operation.completed {
+            Some((_, Outcome::Found(description))) => Some(description),
+            _ => None,
+        })
+        .collect();
+    let unseen = operations.iter()
+        .map(|operation| match (&operation.call, &operation.completed) {
+            (Call::Create(description), None) => !seen.contains(description),
+            _ => false,
+        })
+        .collect();
+
+    let total = operations.iter().filter(|operation| operation.completed.is_some()).count();
+    let mut search = Search { visited: HashSet::new(), deepest: (0, Done::new(operations.len())), unseen, operations };
+    let start = Step { done: Done::new(search.operations.len()), model: Model::default() };
+    if search.linearize(start, 0) {
+        return Ok(());
+    }
+
+    let (ordered, done) = &search.deepest;
+    let mut stuck: Vec<&&Operation> = search.operations.iter().enumerate()
+        .filter(|(index, operation)| !done.contains(*index) && operation.completed.is_some())
+        .map(|(_, operation)| operation)
+        .collect();
+    stuck.sort_by_key(|operation| completed_at(operation));
+    let stuck: Vec<String> = stuck.iter().take(3).map(|operation| format!("    {}", describe(operation))).collect();
+    Err(format!("la historia no es linealizable: se pudieron ordenar {} de {} operaciones. Las siguientes no encajan:\n{}", ordered, total, stuck.join("\n")))
+}
+
+// ? semillas que alguna vez fallaron, con su algoritmo y falla: se corren siempre, ademas de las nuevas.
+// ? Las de Restart perdian trips confirmados cuando el lider nuevo no juntaba los de la mayoria.
+const REGRESSION_SEEDS: &[(&str, Fault, u64)] = &[
+    ("bully", Fault::Restart, 17629300977676214310),
+    ("ring", Fault::Restart, 9763931921147927164),
+    ("raft", Fault::Restart, 9163666688834512931),
+];
+// ? de donde salen las semillas fijas, y cuantas se prueban por algoritmo y falla
+const BASE_SEED: u64 = 0x5eed_71e5_0000_0001;
+const FIXED_SEEDS: usize = 5;
+const CLIENTS: usize = 4;
+const OPERATIONS_PER_CLIENT: usize = 60;
+// ? cada cuantas operaciones completas se provoca una falla
+const OPERATIONS_BETWEEN_FAULTS: usize = 40;
+// ? perdida de mensajes con la que se reinician los nodos, en porcentaje
+const RESTART_DROP_PERCENT: u64 = 5;
+// ? lo que espera un cliente antes de reintentar cuando no hay lider o no recibio respuesta
+const CLIENT_BACKOFF: Duration = Duration::from_secs(1);
+const ELECTION_TIME: Duration = Duration::from_secs(120);
+const MAX_RUN_TIME: Duration = Duration::from_secs(3600);
+
+/// Que falla se provoca cada tanto.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Fault {
+    // ? el lider se cae y no vuelve
+    Crash,
+    // ? el lider se cae y vuelve mas tarde con su estado, como tras una pausa larga
+    Pause,
+    // ? un subordinado se reinicia con lo que tenia guardado y el lider se pausa, con mensajes que se pierden: el
+    // ? proximo lider tiene que juntar los trips de nodos que no vieron todos
+    Restart,
+}
+
+struct Client {
+    // ? nodo al que se le envian los pedidos: el ultimo lider conocido
+    target: u32,
+    pending: Option<(Call, Duration)>,
+    ready_at: Duration,
+    finished: usize,
+}
+
+// ? respuesta del nodo: el resultado, o `Err` con el lider que indico si el pedido se rechazo sin aplicarse
+fn parse_answer(call: &Call, answer: &str) -> Result<Outcome, Option<u32>> {
+    if let Some(leader) = answer.strip_prefix(NOT_LEADER_ANSWER) {
+        return Err(leader.trim().parse().ok());
+    }
+
+    match call {
+        Call::Create(_) => answer.strip_prefix("OK ").and_then(|id| id.trim().parse().ok()).map(Outcome::Created).ok_or(None),
+        Call::Read(_) if answer == NOT_FOUND_ANSWER => Ok(Outcome::NotFound),
+        Call::Read(id) => {
+            let prefix = format!("{} {} ", TRIP_MSG, id);
+            answer.strip_prefix(&prefix).map(|description| Outcome::Found(description.to_string())).ok_or(None)
+        }
+    }
+}
+
+fn next_call(rng: &mut Rng, client: usize, count: usize, highest_id: u64) -> Call {
+    if rng.range(0, 2) == 0 {
+        Call::Create(format!("cliente{}-{}", client, count))
+    } else {
+        Call::Read(rng.range(1, highest_id + 3))
+    }
+}
+
+fn message(call: &Call) -> String {
+    match call {
+        Call::Create(description) => format!("{} {}", TRIP_MSG, description),
+        Call::Read(id) => format!("{} {}", GET_TRIP_MSG, id),
+    }
+}
+
+fn inject_fault(cluster: &mut SimCluster, rng: &mut Rng, fault: Fault, paused: &mut Vec<u32>) {
+    for id in paused.drain(..) {
+        cluster.recover(id);
+    }
+
+    let leader = match cluster.leaders().first() {
+        Some((leader, _)) => *leader,
+        None => return,
+    };
+    // ? con caidas definitivas queda en pie una mayoria
+    let alive: Vec<u32> = cluster.node_ids().into_iter().filter(|id| !cluster.is_crashed(*id)).collect();
+    if fault == Fault::Crash && alive.len() <= cluster.node_ids().len() / 2 + 1 {
+        return;
+    }
+
+    if fault == Fault::Restart {
+        let followers: Vec<u32> = alive.into_iter().filter(|id| *id != leader).collect();
+        if !followers.is_empty() {
+            cluster.restart(followers[rng.range(0, followers.len() as u64) as usize]);
+        }
+    }
+    cluster.crash(leader);
+    if fault != Fault::Crash {
+        paused.push(leader);
+    }
+}
+
+/// Corre clientes concurrentes contra el cluster provocando la falla cada tanto, y devuelve la historia.
+fn run_workload(strategy: &'static str, fault: Fault, seed: u64) -> Result<Vec<Operation>, String> {
+    let mut rng = Rng::new(seed);
+    let mut cluster = SimCluster::new(rng.next_u64(), SimConfig { strategy, ..SimConfig::default() });
+    cluster.run_for(ELECTION_TIME);
+    if fault == Fault::Restart {
+        cluster.set_drop_percent(RESTART_DROP_PERCENT);
+    }
+
+    let ids = cluster.node_ids();
+    let mut clients: Vec<Client> = (0..CLIENTS)
+        .map(|_| Client { target: ids[rng.range(0, ids.len() as u64) as usize], pending: None, ready_at: cluster.now(), finished: 0 })
+        .collect();
+    let mut history = Vec::new();
+    let mut highest_id = 0;
+    let mut paused = Vec::new();
+    let mut since_fault = 0;
+
+    while clients.iter().any(|client| client.finished < OPERATIONS_PER_CLIENT || client.pending.is_some()) {
+        if cluster.now() > MAX_RUN_TIME {
+            return Err(format!("los clientes no terminaron en {:?}: {} operaciones completas", MAX_RUN_TIME, history.len()));
+        }
+
+        let now = cluster.now();
+        for (index, client) in clients.iter_mut().enumerate() {
+            if client.pending.is_none() && client.finished < OPERATIONS_PER_CLIENT && client.ready_at <= now {
+                let call = next_call(&mut rng, index, client.finished, highest_id);
+                cluster.submit(index, client.target, &message(&call));
+                client.pending = Some((call, now));
+            }
+        }
+
+        cluster.step();
+
+        for answer in cluster.take_client_answers() {
+            let client = &mut clients[answer.client];
+            let (call, invoked) = match client.pending.take() {
+                Some(pending) => pending,
+                None => continue,
+            };
+
+            let parsed = match &answer.reply {
+                // ? el lider deshizo el alta, pero pudo haber quedado en alguno de los nodos a los que se replico
+                ClientReply::Answer(text) if text == NOT_COMMITTED_ANSWER => None,
+                ClientReply::Answer(text) => Some(parse_answer(&call, text)),
+                ClientReply::Refused => Some(Err(None)),
+                ClientReply::Lost => None,
+            };
+            match parsed {
+                Some(Ok(outcome)) => {
+                    if let Outcome::Created(id) = outcome {
+                        highest_id = highest_id.max(id);
+                    }
+                    history.push(Operation { client: answer.client, call, invoked, completed: Some((answer.at, outcome)) });
+                    client.finished += 1;
+                    since_fault += 1;
+                }
+                // ? rechazado sin aplicarse: no entra en la historia
+                Some(Err(leader)) => {
+                    client.target = leader.unwrap_or(ids[rng.range(0, ids.len() as u64) as usize]);
+                    client.ready_at = answer.at + CLIENT_BACKOFF;
+                }
+                None => {
+                    history.push(Operation { client: answer.client, call, invoked, completed: None });
+                    client.finished += 1;
+                    client.target = ids[rng.range(0, ids.len() as u64) as usize];
+                    client.ready_at = answer.at + CLIENT_BACKOFF;
+                }
+            }
+        }
+
+        if since_fault >= OPERATIONS_BETWEEN_FAULTS {
+            since_fault = 0;
+            inject_fault(&mut cluster, &mut rng, fault, &mut paused);
+        }
+    }
+
+    Ok(history)
+}
+
+fn seeds(strategy: &str, fault: Fault) -> Vec<u64> {
+    let regression = REGRESSION_SEEDS.iter()
+        .filter(|(name, regression_fault, _)| *name == strategy && *regression_fault == fault)
+        .map(|(_, _, seed)| *seed)
+        .collect();
+    property_seeds(regression, BASE_SEED, FIXED_SEEDS)
+}
+
+fn check_strategy(strategy: &'static str, fault: Fault) {
+    let failures: Vec<String> = seeds(strategy, fault).into_iter()
+        .filter_map(|seed| run_workload(strategy, fault, seed).and_then(|history| check(&history)).err().map(|e| format!("  semilla {}: {}", seed, e)))
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "El servicio de trips con elección {} y fallas {:?} no fue linealizable. Agregar las semillas a REGRESSION_SEEDS:\n{}",
+        strategy,
+        fault,
+        failures.join("\n")
+    );
+}
+
+fn operation(client: usize, call: Call, invoked: u64, completed: Option<(u64, Outcome)>) -> Operation {
+    Operation {
+        client,
+        call,
+        invoked: Duration::from_millis(invoked),
+        completed: completed.map(|(at, outcome)| (Duration::from_millis(at), outcome)),
+    }
+}
+
+fn create(description: &str) -> Call {
+    Call::Create(description.to_string())
+}
+
+fn found(description: &str) -> Outcome {
+    Outcome::Found(description.to_string())
+}
+
+#[test]
+fn concurrent_operations_can_take_effect_in_any_order() {
+    let history = [
+        operation(0, create("a"), 0, Some((10, Outcome::Created(2)))),
+        operation(1, create("b"), 1, Some((9, Outcome::Created(1)))),
+        operation(2, Call::Read(1), 2, Some((5, found("b")))),
+        operation(2, Call::Read(2), 11, Some((12, found("a")))),
+    ];
+    assert!(check(&history).is_ok());
+}
+
+#[test]
+fn stale_read_is_not_linearizable() {
+    let history = [
+        operation(0, create("a"), 0, Some((5, Outcome::Created(1)))),
+        operation(1, Call::Read(1), 6, Some((7, Outcome::NotFound))),
+    ];
+    assert!(check(&history).is_err());
+}
+
+#[test]
+fn reused_id_is_not_linearizable() {
+    let history = [
+        operation(0, create("a"), 0, Some((5, Outcome::Created(1)))),
+        operation(1, create("b"), 6, Some((7, Outcome::Created(1)))),
+    ];
+    assert!(check(&history).is_err());
+}
+
+#[test]
+fn unanswered_create_may_or_may_not_take_effect() {
+    let applied = [
+        operation(0, create("a"), 0, None),
+        operation(1, Call::Read(1), 6, Some((7, found("a")))),
+    ];
+    assert!(check(&applied).is_ok());
+
+    let lost = [
+        operation(0, create("a"), 0, None),
+        operation(1, create("b"), 6, Some((7, Outcome::Created(1)))),
+    ];
+    assert!(check(&lost).is_ok());
+
+    // ? el alta sin respuesta explica el id salteado
+    let gap = [
+        operation(0, create("a"), 0, None),
+        operation(1, create("b"), 6, Some((7, Outcome::Created(2)))),
+    ];
+    assert!(check(&gap).is_ok());
+}
+
+#[test]
+fn bully_trips_survive_leader_crashes() {
+    check_strategy("bully", Fault::Crash);
+}
+
+#[test]
+fn ring_trips_survive_leader_crashes() {
+    check_strategy("ring", Fault::Crash);
+}
+
+#[test]
+fn raft_trips_survive_leader_crashes() {
+    check_strategy("raft", Fault::Crash);
+}
+
+#[test]
+fn trips_survive_leader_pauses() {
+    for strategy in ["bully", "ring", "raft"] {
+        check_strategy(strategy, Fault::Pause);
+    }
+}
+
+#[test]
+fn trips_survive_follower_restarts_on_a_lossy_network() {
+    for strategy in ["bully", "ring", "raft"] {
+        check_strategy(strategy, Fault::Restart);
+    }
+}
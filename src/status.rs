@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::connections::connection_state;
+use crate::journal::{self, Event};
+use crate::process::Process;
+use crate::utils::json::JsonValue;
+
+// * Lo que este nodo sabe del cluster, para responder el comando STATUS.
+
+// ? cantidad de cambios de lider que se recuerdan
+const HISTORY_SIZE: usize = 20;
+
+// ? ultima vez que pudimos (o no) comunicarnos con cada nodo
+#[derive(Default)]
+struct Contact {
+    last_ok: Option<Instant>,
+    last_error: Option<Instant>,
+}
+
+struct LeaderChange {
+    // ? None si el lider renuncio
+    leader: Option<u32>,
+    term: Option<u64>,
+    unix_time: u64,
+}
+
+static CONTACTS: Mutex<BTreeMap<u32, Contact>> = Mutex::new(BTreeMap::new());
+static HISTORY: Mutex<VecDeque<LeaderChange>> = Mutex::new(VecDeque::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// ? registra el resultado de comunicarse con un nodo. Si cambia de "up" a "down" o viceversa, queda en el journal.
+pub(crate) fn record_contact(id: u32, reachable: bool) {
+    let mut contacts = lock(&CONTACTS);
+    let contact = contacts.entry(id).or_default();
+    let before = liveness(Some(contact));
+    if reachable {
+        contact.last_ok = Some(Instant::now());
+    } else {
+        contact.last_error = Some(Instant::now());
+    }
+
+    match (before, liveness(Some(contact))) {
+        (before, "up") if before != "up" => journal::record(Event::MemberUp { id }),
+        (before, "down") if before != "down" => journal::record(Event::MemberDown { id }),
+        _ => {}
+    }
+}
+
+// ? registra un cambio de lider (o una renuncia si `leader` es None) en el historial de elecciones
+pub(crate) fn record_leader_change(leader: Option<u32>, term: Option<u64>) {
+    let unix_time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+
+    let mut history = lock(&HISTORY);
+    if history.len() == HISTORY_SIZE {
+        history.pop_front();
+    }
+    history.push_back(LeaderChange { leader, term, unix_time });
+}
+
+pub(crate) fn role(process: &Process) -> &'static str {
+    if process.leader {
+        "leader"
+    } else if process.is_observer() {
+        "observer"
+    } else {
+        "follower"
+    }
+}
+
+// ? "up" si el ultimo contacto funciono, "down" si fallo, "unknown" si nunca nos comunicamos
+fn liveness(contact: Option<&Contact>) -> &'static str {
+    match contact {
+        Some(Contact { last_ok: Some(ok), last_error: Some(error) }) if error > ok => "down",
+        Some(Contact { last_ok: Some(_), .. }) => "up",
+        Some(Contact { last_error: Some(_), .. }) => "down",
+        _ => "unknown",
+    }
+}
+
+fn secs_since(instant: Option<Instant>) -> JsonValue {
+    instant.map_or(JsonValue::Null, |instant| JsonValue::Number(instant.elapsed().as_secs() as f64))
+}
+
+fn optional_number(number: Option<u64>) -> JsonValue {
+    number.map_or(JsonValue::Null, |number| JsonValue::Number(number as f64))
+}
+
+/// Estado del nodo: id, rol, lider, term, procesos con su estado, ultimo heartbeat e historial de elecciones.
+pub(crate) fn node_status(processes: &[Process], since_last_heartbeat: Option<Duration>, term: u64) -> JsonValue {
+    let me = processes.iter().find(|process| process.me);
+    let leader = processes.iter().find(|process| process.leader).map(|leader| leader.id as u64);
+
+    let contacts = lock(&CONTACTS);
+    let members = processes.iter().map(|process| {
+        let contact = contacts.get(&process.id);
+        JsonValue::Object(vec![
+            ("id".to_string(), JsonValue::Number(process.id as f64)),
+            ("addr".to_string(), JsonValue::String(process.addr.to_string())),
+            ("role".to_string(), JsonValue::String(role(process).to_string())),
+            ("liveness".to_string(), JsonValue::String(if process.me { "self" } else { liveness(contact) }.to_string())),
+            ("last_contact_secs".to_string(), secs_since(contact.and_then(|contact| contact.last_ok))),
+            ("connection".to_string(), JsonValue::String(if process.me { "self" } else { connection_state(process.id) }.to_string())),
+        ])
+    }).collect();
+
+    let history = lock(&HISTORY).iter().map(|change| JsonValue::Object(vec![
+        ("leader".to_string(), optional_number(change.leader.map(u64::from))),
+        ("term".to_string(), optional_number(change.term)),
+        ("unix_time".to_string(), JsonValue::Number(change.unix_time as f64)),
+    ])).collect();
+
+    JsonValue::Object(vec![
+        ("id".to_string(), optional_number(me.map(|me| me.id as u64))),
+        ("role".to_string(), JsonValue::String(me.map_or("unknown", role).to_string())),
+        ("leader".to_string(), optional_number(leader)),
+        ("term".to_string(), JsonValue::Number(term as f64)),
+        ("last_heartbeat_secs".to_string(), optional_number(since_last_heartbeat.map(|elapsed| elapsed.as_secs()))),
+        ("processes".to_string(), JsonValue::Array(members)),
+        ("elections".to_string(), JsonValue::Array(history)),
+    ])
+}
+
+fn text_field(value: Option<&JsonValue>) -> String {
+    match value {
+        Some(JsonValue::Number(number)) => number.to_string(),
+        Some(JsonValue::String(text)) => text.clone(),
+        _ => "-".to_string(),
+    }
+}
+
+/// El mismo estado que `node_status`, en texto para leerlo desde una terminal.
+pub(crate) fn status_as_text(status: &JsonValue) -> String {
+    let mut text = format!(
+        "Nodo: {}\nRol: {}\nLider: {}\nTerm: {}\nSegundos desde el ultimo heartbeat: {}\nProcesos:\n",
+        text_field(status.get("id")),
+        text_field(status.get("role")),
+        text_field(status.get("leader")),
+        text_field(status.get("term")),
+        text_field(status.get("last_heartbeat_secs")),
+    );
+
+    for process in status.get("processes").and_then(JsonValue::as_array).into_iter().flatten() {
+        let last_contact = match process.get("last_contact_secs") {
+            Some(JsonValue::Number(secs)) => format!(" (ultimo contacto hace {}s)", secs),
+            _ => String::new(),
+        };
+        text.push_str(&format!(
+            "  {} {} {} {} conexion {}{}\n",
+            text_field(process.get("id")),
+            text_field(process.get("addr")),
+            text_field(process.get("role")),
+            text_field(process.get("liveness")),
+            text_field(process.get("connection")),
+            last_contact,
+        ));
+    }
+
+    text.push_str("Historial de elecciones:\n");
+    for change in status.get("elections").and_then(JsonValue::as_array).into_iter().flatten() {
+        text.push_str(&format!(
+            "  [{}] lider {} term {}\n",
+            text_field(change.get("unix_time")),
+            text_field(change.get("leader")),
+            text_field(change.get("term")),
+        ));
+    }
+
+    text
+}
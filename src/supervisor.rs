@@ -0,0 +1,55 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::shutdown::{fail_node, StopSignal};
+
+// ? cantidad de veces que se reinicia un thread antes de dar por caido al nodo
+const MAX_RESTARTS: u32 = 3;
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+// ? tiempo funcionando sin fallar tras el cual se olvidan los reinicios anteriores
+const HEALTHY_UPTIME: Duration = Duration::from_secs(60);
+
+/// Lanza un thread cuyo cuerpo se vuelve a ejecutar si devuelve un error o entra en panic.
+///
+/// `body` se llama en loop mientras falle y no se haya pedido detener el thread.
+/// Si devuelve `Ok`, el thread termina. Si supera `MAX_RESTARTS` reinicios,
+/// se marca al nodo como fallido para que el coordinador lo apague. Los reinicios se cuentan de nuevo
+/// cuando el cuerpo llega a funcionar `HEALTHY_UPTIME` sin fallar, para que fallas aisladas no se acumulen.
+pub(crate) fn spawn_supervised<F>(name: &'static str, stop: StopSignal, mut body: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Result<(), String> + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut restarts = 0;
+
+        loop {
+            let started = Instant::now();
+            let failure = match panic::catch_unwind(AssertUnwindSafe(&mut body)) {
+                Ok(Ok(_)) => return,
+                Ok(Err(e)) => e,
+                Err(_) => "panic".to_string(),
+            };
+            if stop.is_stopped() {
+                return;
+            }
+
+            if started.elapsed() >= HEALTHY_UPTIME {
+                restarts = 0;
+            }
+
+            if restarts >= MAX_RESTARTS {
+                error!("El hilo {} fallo {} veces. Apagando el nodo.", name, restarts + 1);
+                fail_node();
+                return;
+            }
+
+            restarts += 1;
+            error!("El hilo {} terminó inesperadamente: {}. Reiniciando ({}/{})...", name, failure, restarts, MAX_RESTARTS);
+
+            if stop.wait(RESTART_DELAY) {
+                return;
+            }
+        }
+    })
+}
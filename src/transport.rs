@@ -0,0 +1,45 @@
+use std::time::Duration;
+use crate::auth;
+use crate::connections;
+use crate::process::Process;
+use crate::utils::peer_addr::PeerAddr;
+use crate::utils::tcp::{get_response_from_server_as_string, send_to_peer};
+
+// ? tiempo maximo para conectarse al puerto de trabajo de otro nodo, y para enviarle el pedido y leer la respuesta
+const WORK_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Por donde un nodo le habla a otro. Los nodos reales usan TCP; la simulacion entrega los mensajes en memoria.
+pub(crate) trait Transport: Send + Sync {
+    /// Envia `message` sin esperar la respuesta.
+    fn send(&self, to: &Process, message: &str) -> Result<(), String>;
+
+    /// Envia `message` y espera la respuesta hasta `timeout`.
+    fn request(&self, to: &Process, message: &str, timeout: Duration) -> Result<String, String>;
+
+    /// Envia `message` al puerto de trabajo de `to` y espera la respuesta un tiempo acotado.
+    fn request_work(&self, to: &Process, message: &str) -> Result<String, String>;
+}
+
+// ? los mensajes de control viajan por la sesion persistente con cada nodo; los de trabajo, uno por conexion.
+// ? Ambos van firmados si hay clave de cluster.
+pub(crate) struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn send(&self, to: &Process, message: &str) -> Result<(), String> {
+        connections::send(to, message)
+    }
+
+    fn request(&self, to: &Process, message: &str, timeout: Duration) -> Result<String, String> {
+        connections::request(to, message, timeout)
+    }
+
+    fn request_work(&self, to: &Process, message: &str) -> Result<String, String> {
+        let work_port = match to.work_port {
+            Some(port) => port,
+            None => return Err(format!("{} no tiene puerto de trabajo", to.id)),
+        };
+
+        let mut conn = send_to_peer(&PeerAddr::new(to.addr.host.clone(), work_port), auth::seal(to.id, message).as_bytes(), WORK_REQUEST_TIMEOUT)?;
+        get_response_from_server_as_string(&mut conn)
+    }
+}
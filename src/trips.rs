@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use crate::consts::{LAST_TRIP_MSG, LEADER_CHECK_MSG, NOT_COMMITTED_ANSWER, NOT_FOUND_ANSWER, NOT_LEADER_ANSWER, PULL_TRIPS_MSG, REPLICATE_TRIP_MSG, STALE_TERM_ANSWER, STEP_DOWN_MSG, SYNC_TRIPS_MSG, TRIPS_ANSWER, TRIP_MSG};
+use crate::election::{snapshot_processes, Term};
+use crate::metrics::{TRIPS_ACCEPTED, TRIPS_COMMITTED, TRIPS_FAILED};
+use crate::process::Process;
+use crate::transport::Transport;
+use crate::utils::file_handler::write_file_synced;
+
+// * Protocolo de trips del puerto de trabajo. El lider crea los trips ("TRIP {descripcion}") y los replica al resto
+// * ("REPLICATE TRIP {term} {lider} {id} {descripcion}"); los demas los reciben y se los pasan al proximo lider. Un nodo
+// * que quedo desfasado se pone al dia pidiendo paginas ("SYNC TRIPS {id}") o recibiendo los que le faltan ("LAST TRIP").
+// * Un trip se confirma recien cuando lo guardo la mayoria, y una consulta cuando la mayoria confirma que el lider sigue
+// * vigente ("LEADER CHECK {term} {lider}"). Un nodo que ya conoce un term mayor rechaza al lider con "STALE TERM {term}":
+// * asi un lider que volvio de una pausa no confirma trips ni lecturas viejas hasta enterarse del lider nuevo.
+// * Antes de atender como lider, un nodo junta los trips de la mayoria ("PULL TRIPS {term} {lider} {id}"): alguno de los
+// * que responden tiene cada trip confirmado, aunque este nodo no lo haya recibido. Cada trip guarda el term del lider
+// * que lo replico, y de dos trips con el mismo id vale el del term mayor.
+// * Sin quorum, bully y ring pueden elegir dos lideres en el mismo term. Por eso cada nodo atiende los trips de un solo
+// * lider por term, el primero que le escribe, y se lo anota como voto del term; al resto le responde "NOT LEADER ?".
+// * Un lider que no puede seguir en su term (otro lo atiende, ya paso, o un trip no llego a la mayoria) renuncia con
+// * "STEP DOWN": el lider que sigue atiende en un term nuevo y nunca vuelve a usar un id en el mismo term.
+
+// ? cantidad de trips por respuesta de SYNC TRIPS y PULL TRIPS, para que entren en una sola lectura
+const SYNC_PAGE_SIZE: usize = 8;
+// ? marca de un trip borrado en el archivo de trips
+const REMOVED: &str = "-";
+
+/// Term en el que un nodo atiende los trips como lider, y su id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Leadership {
+    pub(crate) term: u64,
+    pub(crate) leader: u32,
+}
+
+impl fmt::Display for Leadership {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.term, self.leader)
+    }
+}
+
+/// Un trip y el term del lider que lo replico.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Trip {
+    pub(crate) term: u64,
+    pub(crate) description: String,
+}
+
+/// Trips conocidos por el nodo, indexados por id. El lider los crea y los subordinados los reciben replicados.
+///
+/// Si se indica un archivo, cada cambio se agrega en el antes de confirmarlo. Un nodo que se reinicia sin sus trips
+/// podria dejar un trip confirmado en menos de la mayoria, y el proximo lider no lo encontraria al juntar los trips.
+#[derive(Default)]
+pub(crate) struct Trips {
+    entries: BTreeMap<u64, Trip>,
+    // ? term en el que este nodo ya junto los trips de la mayoria para atender como lider
+    collected_term: Option<u64>,
+    // ? term en el que este nodo dejo de atender como lider: lo atendia otro lider o un trip no llego a la mayoria
+    abandoned_term: Option<u64>,
+    file: Option<PathBuf>,
+}
+
+// ? una linea del archivo de trips: "{id} {term} {descripcion}", o "{id} -" si se borro
+fn parse_line(line: &str) -> Option<(u64, Option<Trip>)> {
+    let mut parts = line.splitn(3, ' ');
+    let id = parts.next()?.parse().ok()?;
+    match (parts.next()?, parts.next()) {
+        (REMOVED, None) => Some((id, None)),
+        (term, Some(description)) => Some((id, Some(Trip { term: term.parse().ok()?, description: description.to_string() }))),
+        _ => None,
+    }
+}
+
+fn format_trip(id: u64, trip: &Trip) -> String {
+    format!("{} {} {}", id, trip.term, trip.description)
+}
+
+impl Trips {
+    pub(crate) fn new() -> Trips {
+        Trips::default()
+    }
+
+    /// Trips guardados en `file`. Si el archivo no existe se empieza sin trips y se crea en el primer cambio.
+    pub(crate) fn persisted(file: &Path) -> Result<Trips, String> {
+        let mut entries = BTreeMap::new();
+        match fs::read_to_string(file) {
+            Ok(content) => {
+                for line in content.lines() {
+                    match parse_line(line).ok_or(format!("el archivo {} tiene un trip inválido: {}", file.display(), line))? {
+                        (id, Some(trip)) => entries.insert(id, trip),
+                        (id, None) => entries.remove(&id),
+                    };
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("no se pudo leer {}: {}", file.display(), e)),
+        }
+
+        Ok(Trips { entries, file: Some(file.to_path_buf()), ..Trips::default() })
+    }
+
+    /// Copia de los trips, sin el archivo ni lo que sabe como lider, para leerlos sin tener el lock.
+    pub(crate) fn snapshot(&self) -> Trips {
+        Trips { entries: self.entries.clone(), ..Trips::default() }
+    }
+
+    /// Lo que conserva el nodo al reiniciarse: los trips guardados, sin lo que sabia como lider.
+    #[cfg(test)]
+    pub(crate) fn restarted(&self) -> Trips {
+        Trips { entries: self.entries.clone(), file: self.file.clone(), ..Trips::default() }
+    }
+
+    pub(crate) fn last_id(&self) -> u64 {
+        self.entries.keys().next_back().copied().unwrap_or(0)
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<&Trip> {
+        self.entries.get(&id)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // ? trips con id mayor a `id`, en orden
+    fn after(&self, id: u64) -> impl Iterator<Item = (&u64, &Trip)> {
+        self.entries.range(id + 1..)
+    }
+
+    // ? agrega la linea al archivo, si hay. Sin error, queda en disco aunque el nodo se caiga enseguida.
+    fn append(&self, line: &str) -> Result<(), String> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        OpenOptions::new().create(true).append(true).open(file)
+            .and_then(|mut out| out.write_all(format!("{}\n", line).as_bytes()).and_then(|_| out.sync_data()))
+            .map_err(|e| format!("Error al guardar los trips en {}: {}", file.display(), e))
+    }
+
+    /// Guarda el trip `id`, reemplazando al que hubiera con ese id.
+    ///
+    /// # Errors
+    /// Si no se puede guardar en el archivo el trip no se agrega: no hay que confirmarlo.
+    pub(crate) fn insert(&mut self, id: u64, trip: Trip) -> Result<(), String> {
+        self.append(&format_trip(id, &trip))?;
+        self.entries.insert(id, trip);
+        Ok(())
+    }
+
+    // ? si no se puede guardar el borrado, el trip vuelve tras un reinicio: como no se confirmo, da lo mismo
+    fn remove(&mut self, id: u64) {
+        if let Err(e) = self.append(&format!("{} {}", id, REMOVED)) {
+            error!("{}", e);
+        }
+        self.entries.remove(&id);
+    }
+
+    /// Agrega los trips de `entries` que este nodo no tiene o tiene de un term menor. Devuelve cuantos cambiaron.
+    pub(crate) fn merge(&mut self, entries: BTreeMap<u64, Trip>) -> Result<usize, String> {
+        let mut changed = 0;
+        for (id, trip) in entries {
+            if self.entries.get(&id).is_some_and(|known| known.term >= trip.term) {
+                continue;
+            }
+            self.insert(id, trip)?;
+            changed += 1;
+        }
+        if changed > 0 {
+            self.compact();
+        }
+        Ok(changed)
+    }
+
+    // ? tras juntar muchos trips se reescribe el archivo, para que no crezca con cada cambio
+    fn compact(&self) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+
+        let content: String = self.entries.iter().map(|(id, trip)| format!("{}\n", format_trip(*id, trip))).collect();
+        if let Err(e) = write_file_synced(file, &content) {
+            warn!("No se pudo compactar el archivo de trips {}: {}", file.display(), e);
+        }
+    }
+}
+
+// ? "GET TRIP {id}": solo si la mayoria confirma que seguimos siendo el lider, para no responder con trips viejos
+pub(crate) fn get_trip(id: &str, processes: &Arc<RwLock<Vec<Process>>>, trips: &Trips, leadership: Leadership, transport: &dyn Transport) -> String {
+    let check = format!("{} {}", LEADER_CHECK_MSG, leadership);
+    if !confirmed_by_majority(&check, processes, transport) {
+        return format!("{} ?", NOT_LEADER_ANSWER);
+    }
+
+    let trip = id.parse::<u64>().ok().and_then(|id| trips.get(id).map(|trip| (id, trip)));
+
+    match trip {
+        Some((id, trip)) => format!("{} {} {}", TRIP_MSG, id, trip.description),
+        None => NOT_FOUND_ANSWER.to_string(),
+    }
+}
+
+// ? "TRIP {descripcion}": lo crea el lider con el id siguiente al ultimo. Si no llega a la mayoria se deshace y se
+// ? responde NOT COMMITTED: el cliente no sabe si quedo en algun otro nodo y puede reintentar. El lider deja el term,
+// ? porque en el no puede volver a usar ese id.
+pub(crate) fn create_trip(description: &str, processes: &Arc<RwLock<Vec<Process>>>, trips: &mut Trips, leadership: Leadership, transport: &dyn Transport) -> String {
+    // ? cada trip ocupa una linea al resincronizar
+    if description.is_empty() || description.contains('\n') {
+        TRIPS_FAILED.increment();
+        return "error".to_string();
+    }
+
+    let id = trips.last_id() + 1;
+    if let Err(e) = trips.insert(id, Trip { term: leadership.term, description: description.to_string() }) {
+        error!("{}", e);
+        TRIPS_FAILED.increment();
+        return "error".to_string();
+    }
+
+    // ? replicamos el trip para que el proximo lider lo conozca
+    if !confirmed_by_majority(&replicate_message(leadership, id, description), processes, transport) {
+        trips.remove(id);
+        abandon_term(trips, leadership, processes, transport);
+        TRIPS_FAILED.increment();
+        return NOT_COMMITTED_ANSWER.to_string();
+    }
+
+    TRIPS_ACCEPTED.increment();
+    TRIPS_COMMITTED.increment();
+    info!("trip {} creado: {}", id, description);
+    format!("OK {}", id)
+}
+
+fn replicate_message(leadership: Leadership, id: u64, description: &str) -> String {
+    format!("{} {} {} {}", REPLICATE_TRIP_MSG, leadership, id, description)
+}
+
+// ? envia `message` al hilo de trabajo de los demas nodos y devuelve si lo confirmo la mayoria del cluster, contando este nodo
+fn confirmed_by_majority(message: &str, processes: &Arc<RwLock<Vec<Process>>>, transport: &dyn Transport) -> bool {
+    let snapshot = match snapshot_processes(processes) {
+        Some(snapshot) => snapshot,
+        None => return false,
+    };
+
+    let mut confirmed = 1;
+    for process in snapshot.iter().filter(|process| !process.me && process.work_port.is_some()) {
+        match request_ok(transport, process, message) {
+            Ok(_) => confirmed += 1,
+            Err(e) => warn!("{} no confirmó {}: {}", process.id, message, e),
+        }
+    }
+    if confirmed * 2 <= snapshot.len() {
+        warn!("{} confirmado solo por {} de {} nodos", message, confirmed, snapshot.len());
+        return false;
+    }
+    true
+}
+
+fn request_ok(transport: &dyn Transport, process: &Process, message: &str) -> Result<(), String> {
+    match transport.request_work(process, message)?.as_str() {
+        "OK" => Ok(()),
+        answer => Err(answer.to_string()),
+    }
+}
+
+// ? rechaza los mensajes de un lider con un term menor al que ya conocemos, o de otro lider del term que ya atendemos
+fn check_leadership(term: &str, leader: &str, current: &Term) -> Result<Leadership, String> {
+    let leadership = match (term.parse(), leader.parse()) {
+        (Ok(term), Ok(leader)) => Leadership { term, leader },
+        _ => return Err("error".to_string()),
+    };
+    match current.grant_vote(leadership.term, leadership.leader) {
+        (true, _) => Ok(leadership),
+        (false, known) if known > leadership.term => Err(format!("{} {}", STALE_TERM_ANSWER, known)),
+        (false, _) => Err(format!("{} ?", NOT_LEADER_ANSWER)),
+    }
+}
+
+// ? "LEADER CHECK {term} {lider}"
+pub(crate) fn check_leader(message: &str, term: &Term) -> String {
+    let (leader_term, leader) = match message[LEADER_CHECK_MSG.len()..].trim().split_once(' ') {
+        Some(parts) => parts,
+        None => return "error".to_string(),
+    };
+    match check_leadership(leader_term, leader, term) {
+        Ok(_) => "OK".to_string(),
+        Err(answer) => answer,
+    }
+}
+
+// ? "REPLICATE TRIP {term} {lider} {id} {descripcion}"
+pub(crate) fn apply_replicated_trip(message: &str, trips: &mut Trips, term: &Term) -> String {
+    let rest = message[REPLICATE_TRIP_MSG.len()..].trim();
+    let (leader_term, leader, id, description) = match rest.splitn(4, ' ').collect::<Vec<&str>>().as_slice() {
+        [leader_term, leader, id, description] => (*leader_term, *leader, *id, *description),
+        _ => return "error".to_string(),
+    };
+    let id = match id.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return "error".to_string(),
+    };
+    let leadership = match check_leadership(leader_term, leader, term) {
+        Ok(leadership) => leadership,
+        Err(answer) => return answer,
+    };
+
+    match trips.insert(id, Trip { term: leadership.term, description: description.to_string() }) {
+        Ok(_) => "OK".to_string(),
+        Err(e) => {
+            error!("{}", e);
+            "error".to_string()
+        }
+    }
+}
+
+// ? "SYNC TRIPS {id}": responde "TRIPS" y los siguientes trips con id mayor, uno por linea con su term
+pub(crate) fn get_trips_page(after: &str, trips: &Trips) -> String {
+    let after = match after.parse::<u64>() {
+        Ok(after) => after,
+        Err(_) => return "error".to_string(),
+    };
+
+    let mut page = TRIPS_ANSWER.to_string();
+    for (id, trip) in trips.after(after).take(SYNC_PAGE_SIZE) {
+        page.push_str(&format!("\n{}", format_trip(*id, trip)));
+    }
+    page
+}
+
+// ? "PULL TRIPS {term} {lider} {id}": como SYNC TRIPS, pero lo atiende cualquier nodo despues de anotar al nuevo lider
+pub(crate) fn get_pulled_trips_page(message: &str, trips: &Trips, term: &Term) -> String {
+    let (leader_term, leader, after) = match message[PULL_TRIPS_MSG.len()..].trim().splitn(3, ' ').collect::<Vec<&str>>().as_slice() {
+        [leader_term, leader, after] => (*leader_term, *leader, *after),
+        _ => return "error".to_string(),
+    };
+    match check_leadership(leader_term, leader, term) {
+        Ok(_) => get_trips_page(after, trips),
+        Err(answer) => answer,
+    }
+}
+
+fn fetch_trips_page(transport: &dyn Transport, process: &Process, message: &str) -> Result<Vec<(u64, Trip)>, String> {
+    let answer = transport.request_work(process, message)?;
+
+    let mut lines = answer.lines();
+    if lines.next() != Some(TRIPS_ANSWER) {
+        return Err(answer);
+    }
+
+    lines
+        .map(|line| match parse_line(line) {
+            Some((id, Some(trip))) => Ok((id, trip)),
+            _ => Err(format!("trip inválido: {}", line)),
+        })
+        .collect()
+}
+
+// ? todos los trips de `process`, pidiendo paginas con `message(id)` hasta que llegue una vacia
+fn fetch_all_trips(transport: &dyn Transport, process: &Process, message: impl Fn(u64) -> String) -> Result<BTreeMap<u64, Trip>, String> {
+    let mut fetched = BTreeMap::new();
+    loop {
+        let after = fetched.keys().next_back().copied().unwrap_or(0);
+        let page = fetch_trips_page(transport, process, &message(after))?;
+        if page.is_empty() {
+            return Ok(fetched);
+        }
+        fetched.extend(page);
+    }
+}
+
+/// Copia los trips del hilo de trabajo de `leader`, para juntarlos con los locales con `Trips::merge`.
+pub(crate) fn fetch_leader_trips(leader: &Process, transport: &dyn Transport) -> Result<BTreeMap<u64, Trip>, String> {
+    fetch_all_trips(transport, leader, |after| format!("{} {}", SYNC_TRIPS_MSG, after))
+}
+
+/// Junta los trips de la mayoria del cluster, contando este nodo, antes de atender como lider en `leadership`.
+///
+/// Cada trip confirmado quedo en la mayoria, asi que lo tiene alguno de los nodos que responden. Los nodos responden
+/// despues de anotar al nuevo lider, por lo que un lider anterior ya no puede confirmar trips que no esten en las
+/// respuestas. Los trips juntados que no tiene la mayoria se vuelven a replicar antes de atender: una consulta podria
+/// verlos y el proximo lider no encontrarlos. Devuelve si se juntaron; si no, el nodo no debe atender como lider.
+pub(crate) fn collect_trips(processes: &Arc<RwLock<Vec<Process>>>, trips: &mut Trips, leadership: Leadership, term: &Term, transport: &dyn Transport) -> bool {
+    // ? un lider de un term que ya paso no puede atender: sus subordinados rechazan sus trips con STALE TERM
+    if term.current() > leadership.term || trips.abandoned_term == Some(leadership.term) {
+        abandon_term(trips, leadership, processes, transport);
+        return false;
+    }
+    if trips.collected_term == Some(leadership.term) {
+        return true;
+    }
+    let snapshot = match snapshot_processes(processes) {
+        Some(snapshot) => snapshot,
+        None => return false,
+    };
+
+    // ? este nodo tambien atiende a un solo lider por term: si ya respondio a otro, el term es de ese
+    let voted = term.grant_vote(leadership.term, leadership.leader).0;
+    let mut taken = !voted;
+    let mut responses = vec![trips.entries.clone()];
+    for process in snapshot.iter().filter(|process| voted && !process.me && process.work_port.is_some()) {
+        match fetch_all_trips(transport, process, |after| format!("{} {} {}", PULL_TRIPS_MSG, leadership, after)) {
+            Ok(entries) => responses.push(entries),
+            Err(e) => {
+                // ? un nodo con un term mayor sigue a este lider, pero el term no se puede atender: se pasa a uno nuevo
+                if let Some(known) = e.strip_prefix(STALE_TERM_ANSWER).and_then(|known| known.trim().parse().ok()) {
+                    term.observe(known);
+                    taken = true;
+                }
+                taken |= e.starts_with(NOT_LEADER_ANSWER);
+                warn!("No se pudieron juntar los trips de {}: {}", process.id, e);
+            }
+        }
+    }
+    if taken {
+        abandon_term(trips, leadership, processes, transport);
+        return false;
+    }
+    if responses.len() * 2 <= snapshot.len() {
+        warn!("Trips juntados solo de {} de {} nodos, todavía no atiendo como lider", responses.len(), snapshot.len());
+        return false;
+    }
+
+    let mut changed = 0;
+    for entries in responses.iter().skip(1) {
+        match trips.merge(entries.clone()) {
+            Ok(merged) => changed += merged,
+            Err(e) => {
+                error!("{}", e);
+                return false;
+            }
+        }
+    }
+
+    let unconfirmed: Vec<(u64, String)> = trips.entries.iter()
+        .filter(|(id, trip)| responses.iter().filter(|entries| entries.get(id) == Some(trip)).count() * 2 <= snapshot.len())
+        .map(|(id, trip)| (*id, trip.description.clone()))
+        .collect();
+    for (id, description) in unconfirmed {
+        let replicated = confirmed_by_majority(&replicate_message(leadership, id, &description), processes, transport);
+        let stored = replicated && trips.insert(id, Trip { term: leadership.term, description }).map_err(|e| error!("{}", e)).is_ok();
+        if !stored {
+            abandon_term(trips, leadership, processes, transport);
+            return false;
+        }
+        changed += 1;
+    }
+
+    trips.collected_term = Some(leadership.term);
+    info!("Trips de la mayoría juntados para el term {}: {} nuevos, {} en total", leadership.term, changed, trips.len());
+    true
+}
+
+// ? deja de atender como lider en el term de `leadership`: lo atiende otro lider, ya paso, o un trip pudo quedar en
+// ? algunos nodos y su id no se puede volver a usar en este term. El nodo renuncia: al dejar de mandar heartbeats los
+// ? subordinados eligen un lider nuevo, que atiende en un term nuevo. Hasta que renuncie se le vuelve a pedir.
+fn abandon_term(trips: &mut Trips, leadership: Leadership, processes: &Arc<RwLock<Vec<Process>>>, transport: &dyn Transport) {
+    trips.collected_term = None;
+    trips.abandoned_term = Some(leadership.term);
+
+    let me = match snapshot_processes(processes).and_then(|snapshot| snapshot.into_iter().find(|process| process.me)) {
+        Some(me) => me,
+        None => return,
+    };
+    info!("Dejo de atender trips en el term {}. Renuncio como lider...", leadership.term);
+    if let Err(e) = transport.send(&me, &format!("{} {}", STEP_DOWN_MSG, me.id)) {
+        warn!("Error renunciando como lider: {}", e);
+    }
+}
+
+// ? "LAST TRIP" -> "LAST TRIP {id}"
+fn fetch_last_trip_id(transport: &dyn Transport, process: &Process) -> Result<u64, String> {
+    let answer = transport.request_work(process, LAST_TRIP_MSG)?;
+
+    answer.strip_prefix(LAST_TRIP_MSG)
+        .and_then(|id| id.trim().parse().ok())
+        .ok_or(format!("respuesta inesperada: {}", answer))
+}
+
+/// Le envia al hilo de trabajo de `process` los trips que le faltan y confirma que quedo al dia.
+pub(crate) fn catch_up(transport: &dyn Transport, process: &Process, trips: &Trips, leadership: Leadership) -> Result<(), String> {
+    let their_last = fetch_last_trip_id(transport, process)?;
+    for (id, trip) in trips.after(their_last) {
+        request_ok(transport, process, &replicate_message(leadership, *id, &trip.description)).map_err(|answer| format!("no confirmó el trip {}: {}", id, answer))?;
+    }
+
+    match fetch_last_trip_id(transport, process)? {
+        last if last == trips.last_id() => Ok(()),
+        last => Err(format!("quedó en el trip {} y el último es {}", last, trips.last_id())),
+    }
+}
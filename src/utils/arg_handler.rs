@@ -1,17 +1,209 @@
 use std::env;
-use std::io::BufRead;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::Path;
-use crate::{file_handler};
+use std::sync::Arc;
+use crate::election::{self, ElectionEnv, ElectionStrategy, Term};
 use crate::process::Process;
+use crate::trips::Trips;
+use crate::utils::peer_addr::Host;
+use crate::utils::{hmac, peers_file};
 use crate::consts::ARGS_EXPECTED;
 
+const USAGE: &str = "Uso: cargo run -- <pid> <port> <other_processes_filename> [--bind <ip>] [--advertise <ip>] [--work-port <port>] [--election bully|ring|raft] [--quorum none|majority] [--faults <fallas>] [--state-file <archivo>] [--log-level <niveles>] [--log-format human|json] [--metrics-port <port>] [--journal <archivo>] [--runtime threads|event-loop] [--cluster-key <archivo>] [--client-messages <tipos>]";
+const OPTIONAL_FLAGS: [&str; 14] = ["--bind", "--advertise", "--work-port", "--election", "--quorum", "--faults", "--state-file", "--log-level", "--log-format", "--metrics-port", "--journal", "--runtime", "--cluster-key", "--client-messages"];
+
 pub(crate) fn check_args() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != ARGS_EXPECTED {
-        eprintln!("Error en args. Uso: cargo run -- <pid> <port> <other_processes_filename>");
+    if args.len() < ARGS_EXPECTED {
+        eprintln!("Error en args. {}", USAGE);
+        std::process::exit(1);
+    }
+
+    // ? los argumentos opcionales vienen de a pares: --flag valor
+    let optional_args = &args[ARGS_EXPECTED..];
+    if !optional_args.len().is_multiple_of(2) {
+        eprintln!("Error en args: falta el valor de un argumento opcional. {}", USAGE);
         std::process::exit(1);
     }
+
+    for flag in optional_args.iter().step_by(2) {
+        if !OPTIONAL_FLAGS.contains(&flag.as_str()) {
+            eprintln!("Error en args: argumento desconocido {}. {}", flag, USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_optional_arg(flag: &str) -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    args[ARGS_EXPECTED..]
+        .chunks(2)
+        .find(|pair| pair[0] == flag)
+        .map(|pair| pair[1].clone())
+}
+
+// ? IP en la que se abre el socket del nodo. Por defecto escucha en todas las interfaces.
+pub(crate) fn get_bind_ip() -> IpAddr {
+    let bind = match get_optional_arg("--bind") {
+        Some(bind) => bind,
+        None => return IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+
+    match Host::parse(&bind) {
+        Ok(Host::Ip(ip)) => ip,
+        _ => {
+            eprintln!("Error: El argumento --bind debe ser una IPv4 o IPv6.");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? host con el que los demas nodos se comunican con este. Si no se indica, se usa el del archivo de procesos.
+pub(crate) fn get_advertise_host() -> Option<Host> {
+    let advertise = get_optional_arg("--advertise")?;
+
+    match Host::parse(&advertise) {
+        Ok(host) => Some(host),
+        Err(e) => {
+            eprintln!("Error: El argumento --advertise es inválido: {}.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? puerto del hilo de trabajo. Si no se indica, se usa el del archivo de procesos (si lo tiene).
+pub(crate) fn get_work_port() -> Option<u16> {
+    let work_port = get_optional_arg("--work-port")?;
+
+    match work_port.parse() {
+        Ok(port) => Some(port),
+        Err(_) => {
+            eprintln!("Error: El argumento --work-port debe ser un número entero entre 0 y 65535.");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? puerto HTTP donde se exportan las metricas en formato Prometheus. Si no se indica, no se exportan.
+pub(crate) fn get_metrics_port() -> Option<u16> {
+    let metrics_port = get_optional_arg("--metrics-port")?;
+
+    match metrics_port.parse() {
+        Ok(port) => Some(port),
+        Err(_) => {
+            eprintln!("Error: El argumento --metrics-port debe ser un número entero entre 0 y 65535.");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? algoritmo de eleccion de lider. Por defecto, bully.
+pub(crate) fn get_election_strategy(env: ElectionEnv) -> Arc<dyn ElectionStrategy> {
+    let name = get_optional_arg("--election").unwrap_or_else(|| "bully".to_string());
+
+    match election::strategy_from_name(&name, env) {
+        Some(strategy) => strategy,
+        None => {
+            eprintln!("Error: El argumento --election debe ser uno de: {}.", election::ELECTION_STRATEGIES.join(", "));
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? si es "majority", un nodo solo se proclama lider si la mayoria del cluster confirma el anuncio. Por defecto, none.
+pub(crate) fn is_majority_quorum_enabled() -> bool {
+    match get_optional_arg("--quorum").as_deref() {
+        None | Some("none") => false,
+        Some("majority") => true,
+        Some(_) => {
+            eprintln!("Error: El argumento --quorum debe ser uno de: {}.", election::QUORUM_POLICIES.join(", "));
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? como corre el protocolo: "threads" (un hilo por tarea) o "event-loop" (un solo loop con una maquina de estados). Por defecto, threads.
+pub(crate) fn is_event_loop_runtime() -> bool {
+    match get_optional_arg("--runtime").as_deref() {
+        None | Some("threads") => false,
+        Some("event-loop") => true,
+        Some(_) => {
+            eprintln!("Error: El argumento --runtime debe ser uno de: threads, event-loop.");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? fallas a inyectar en los mensajes salientes, por ejemplo "drop=10,latency=200,corrupt=5,blackhole=2+3". Por defecto, ninguna.
+pub(crate) fn get_faults() -> Option<String> {
+    get_optional_arg("--faults")
+}
+
+// ? archivo donde se guardan el term y el voto para sobrevivir a un reinicio. Sin el, el term arranca en 0 en cada inicio.
+pub(crate) fn get_term() -> Term {
+    let file = match get_optional_arg("--state-file") {
+        Some(file) => file,
+        None => return Term::new(),
+    };
+
+    match Term::persisted(Path::new(&file)) {
+        Ok(term) => term,
+        Err(e) => {
+            eprintln!("Error: El argumento --state-file es inválido: {}.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? los trips se guardan junto al term, en el mismo archivo con extension .trips. Sin --state-file se pierden en cada reinicio.
+pub(crate) fn get_trips() -> Trips {
+    let file = match get_optional_arg("--state-file") {
+        Some(file) => Path::new(&file).with_extension("trips"),
+        None => return Trips::new(),
+    };
+
+    match Trips::persisted(&file) {
+        Ok(trips) => trips,
+        Err(e) => {
+            eprintln!("Error: El argumento --state-file es inválido: {}.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? niveles del log por modulo, por ejemplo "info,election=debug,utils::tcp=warn". Por defecto, info.
+pub(crate) fn get_log_level() -> Option<String> {
+    get_optional_arg("--log-level")
+}
+
+// ? formato del log: human o json. Por defecto, human.
+pub(crate) fn get_log_format() -> Option<String> {
+    get_optional_arg("--log-format")
+}
+
+// ? archivo donde se registran los eventos de eleccion y membresia. Si no se indica, no se registran.
+pub(crate) fn get_journal_path() -> Option<String> {
+    get_optional_arg("--journal")
+}
+
+// ? archivo con la clave compartida del cluster para firmar y verificar los mensajes entre nodos. Sin ella, no se firman.
+pub(crate) fn get_cluster_key() -> Option<Vec<u8>> {
+    let file = get_optional_arg("--cluster-key")?;
+
+    match hmac::load_key(Path::new(&file)) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            eprintln!("Error: El argumento --cluster-key es inválido: {}.", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ? mensajes que se atienden desde direcciones fuera de la lista de procesos, por ejemplo "TRIP,GET TRIP,STATUS" o "none". Por defecto, TRIP y GET TRIP.
+pub(crate) fn get_client_messages() -> Option<String> {
+    get_optional_arg("--client-messages")
 }
 
 pub(crate) fn get_process_id() -> u32 {
@@ -28,13 +220,13 @@ pub(crate) fn get_process_id() -> u32 {
     pid
 }
 
-pub(crate) fn get_process_port() -> u32 {
+pub(crate) fn get_process_port() -> u16 {
     let args: Vec<String> = env::args().collect();
 
-    let port: u32 = match args[2].parse() {
+    let port: u16 = match args[2].parse() {
         Ok(port) => port,
         Err(_) => {
-            eprintln!("Error: El argumento port debe ser un número entero.");
+            eprintln!("Error: El argumento port debe ser un número entero entre 0 y 65535.");
             std::process::exit(1);
         }
     };
@@ -48,61 +240,38 @@ pub(crate) fn get_other_processes_filename() -> String {
 }
 
 pub(crate) fn get_other_processes(filepath: &Path) -> Vec<Process> {
-    let mut other_processes: Vec<Process> = Vec::new();
-    let reader = file_handler::get_reader_for_file_or_kill_process(filepath);
-
-    for line in reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(_) => {
-                eprintln!("Error: No se pudo leer una línea del archivo de procesos.");
-                std::process::exit(1);
-            }
-        };
-        let parts: Vec<&str> = line.split(";").collect();
-
-        if parts.len() != 3 {
-            eprintln!("Error: Cada línea del archivo de procesos debe tener 3 partes separadas por ';'.");
-            std::process::exit(1);
-        }
-
-        let id: u32 = match parts[0].parse() {
-            Ok(id) => id,
-            Err(_) => {
-                eprintln!("Error: El ID del proceso debe ser un número entero.");
-                std::process::exit(1);
-            }
-        };
-
-        let ip = parts[1].to_string();
+    peers_file::load_processes_or_kill_process(filepath)
+}
 
-        let port: u32 = match parts[2].parse() {
-            Ok(port) => port,
-            Err(_) => {
-                eprintln!("Error: El puerto del proceso debe ser un número entero.");
-                std::process::exit(1);
-            }
-        };
-        let leader = false;
-        let me = false;
+pub(crate) fn check_pid_and_port(pid: u32, port: u16, other_processes: &[Process]) {
+    // ? si el proceso no figura en el archivo, se corre localmente y el puerto no puede estar repetido
+    if other_processes.iter().all(|process| process.id != pid) && other_processes.iter().any(|process| process.addr.port == port) {
+        eprintln!("Error: El puerto del proceso debe ser único cuando se corre localmente.");
+        std::process::exit(1);
+    }
 
-        other_processes.push(Process { id, ip, port, leader, me });
+    // ? si figura, los demas nodos le escriben al puerto del archivo: tiene que ser el mismo en el que escucha
+    if let Some(me) = other_processes.iter().find(|process| process.id == pid && process.addr.port != port) {
+        eprintln!("Error: El proceso {} figura en el archivo con el puerto {} pero se indicó el puerto {}.", pid, me.addr.port, port);
+        std::process::exit(1);
     }
 
-    other_processes
+    check_processes_are_unique(other_processes);
 }
 
-pub(crate) fn check_pid_and_port(pid: u32, port: u32, other_processes: &Vec<Process>) {
-    for process in other_processes.iter() {
-        if process.id == pid {
-            eprintln!("Error: El ID del proceso debe ser único.");
-            std::process::exit(1);
-        }
+// ? dos procesos no pueden compartir ID ni direccion, pero si el puerto si estan en hosts distintos
+fn check_processes_are_unique(processes: &[Process]) {
+    for (i, process) in processes.iter().enumerate() {
+        for other in processes[i + 1..].iter() {
+            if process.id == other.id {
+                eprintln!("Error: El ID {} figura más de una vez en el archivo de procesos.", process.id);
+                std::process::exit(1);
+            }
 
-        // ? Si se corre localmente, el puerto debe ser único. Si no, comentar esta validacion.
-        if process.port == port {
-            eprintln!("Error: El puerto del proceso debe ser único cuando se corre localmente.");
-            std::process::exit(1);
+            if process.addr == other.addr {
+                eprintln!("Error: Los procesos {} y {} tienen la misma dirección {}.", process.id, other.id, process.addr);
+                std::process::exit(1);
+            }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,140 @@
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use crate::process::Process;
+use crate::utils::peer_addr::PeerAddr;
+use crate::utils::random::Rng;
+
+// * Inyeccion de fallas en los mensajes que este nodo envia a otros, para ensayar particiones y enlaces inestables
+// * en una sola maquina. Se configura con --faults al iniciar o con el comando FAULTS por el canal de administracion.
+
+const DISABLED: &str = "off";
+
+/// Fallas activas. Se describen como `drop=10,latency=200,corrupt=5,blackhole=2+3`.
+struct Faults {
+    // ? porcentaje de mensajes que se descartan
+    drop_percent: u64,
+    latency: Duration,
+    // ? porcentaje de mensajes a los que se les altera un byte
+    corrupt_percent: u64,
+    // ? nodos a los que no les llega ningun mensaje, con su direccion
+    blackholed: Vec<(u32, String)>,
+    rng: Rng,
+}
+
+static FAULTS: Mutex<Option<Faults>> = Mutex::new(None);
+
+fn faults() -> MutexGuard<'static, Option<Faults>> {
+    match FAULTS.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn parse_percent(name: &str, value: &str) -> Result<u64, String> {
+    match value.trim().parse() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(format!("{} debe ser un porcentaje entre 0 y 100: {}", name, value)),
+    }
+}
+
+fn parse(spec: &str, processes: &[Process]) -> Result<Option<Faults>, String> {
+    if spec.trim() == DISABLED {
+        return Ok(None);
+    }
+
+    let mut faults = Faults { drop_percent: 0, latency: Duration::ZERO, corrupt_percent: 0, blackholed: Vec::new(), rng: Rng::from_entropy() };
+    for setting in spec.split(',') {
+        let (name, value) = match setting.split_once('=') {
+            Some(pair) => pair,
+            None => return Err(format!("falta el valor de {}", setting.trim())),
+        };
+
+        match name.trim() {
+            "drop" => faults.drop_percent = parse_percent("drop", value)?,
+            "corrupt" => faults.corrupt_percent = parse_percent("corrupt", value)?,
+            "latency" => match value.trim().parse() {
+                Ok(millis) => faults.latency = Duration::from_millis(millis),
+                Err(_) => return Err(format!("latency debe ser una cantidad de milisegundos: {}", value)),
+            },
+            "blackhole" => for id in value.split('+') {
+                let process = id.trim().parse::<u32>().ok().and_then(|id| processes.iter().find(|process| process.id == id));
+                match process {
+                    Some(process) => faults.blackholed.push((process.id, process.addr.to_string())),
+                    None => return Err(format!("blackhole: no hay un proceso con id {}", id)),
+                }
+            },
+            other => return Err(format!("falla desconocida: {}", other)),
+        }
+    }
+
+    Ok(Some(faults))
+}
+
+fn describe(faults: &Option<Faults>) -> String {
+    let faults = match faults {
+        Some(faults) => faults,
+        None => return DISABLED.to_string(),
+    };
+
+    let mut description = format!("drop={},latency={},corrupt={}", faults.drop_percent, faults.latency.as_millis(), faults.corrupt_percent);
+    if !faults.blackholed.is_empty() {
+        let ids: Vec<String> = faults.blackholed.iter().map(|(id, _)| id.to_string()).collect();
+        description.push_str(&format!(",blackhole={}", ids.join("+")));
+    }
+    description
+}
+
+/// Reemplaza las fallas activas por las de `spec` (u `off` para desactivarlas) y devuelve como quedaron.
+///
+/// Los nodos de `blackhole` se indican por id y se buscan en `processes`.
+pub(crate) fn configure(spec: &str, processes: &[Process]) -> Result<String, String> {
+    let parsed = parse(spec, processes)?;
+    let mut faults = faults();
+    *faults = parsed;
+    Ok(describe(&faults))
+}
+
+/// Las fallas activas, con el mismo formato que acepta `configure`.
+pub(crate) fn current() -> String {
+    describe(&faults())
+}
+
+/// Aplica las fallas activas a un mensaje que se va a enviar a `addr`.
+///
+/// Devuelve el mensaje a enviar (alterado si toca corromperlo), o un error si el mensaje se descarta.
+/// La latencia se espera sin tomar el lock, para no demorar los envios de otros hilos.
+pub(crate) fn apply(addr: &PeerAddr, message: &[u8]) -> Result<Vec<u8>, String> {
+    let (message, latency) = inject(addr, message)?;
+    if !latency.is_zero() {
+        thread::sleep(latency);
+    }
+    Ok(message)
+}
+
+/// Como `apply`, pero sin esperar: devuelve junto al mensaje la latencia con la que hay que enviarlo.
+///
+/// Lo usa el runtime event-loop, que no puede dormir sin frenar al nodo entero.
+pub(crate) fn inject(addr: &PeerAddr, message: &[u8]) -> Result<(Vec<u8>, Duration), String> {
+    let mut message = message.to_vec();
+    let mut guard = faults();
+    let faults = match guard.as_mut() {
+        Some(faults) => faults,
+        None => return Ok((message, Duration::ZERO)),
+    };
+
+    let destination = addr.to_string();
+    if let Some((id, _)) = faults.blackholed.iter().find(|(_, blackholed)| *blackholed == destination) {
+        return Err(format!("[Fallas]: mensaje a {} descartado: el nodo está aislado", id));
+    }
+    if faults.rng.range(0, 100) < faults.drop_percent {
+        debug!("Descartando mensaje a {}", destination);
+        return Err(format!("[Fallas]: mensaje a {} descartado", destination));
+    }
+    if !message.is_empty() && faults.rng.range(0, 100) < faults.corrupt_percent {
+        let index = faults.rng.range(0, message.len() as u64) as usize;
+        message[index] ^= faults.rng.range(1, 256) as u8;
+        debug!("Corrompiendo el byte {} del mensaje a {}", index, destination);
+    }
+    Ok((message, faults.latency))
+}
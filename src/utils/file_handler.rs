@@ -15,3 +15,22 @@ pub(crate) fn get_reader_for_file_or_kill_process(filepath: &Path) -> std::io::B
     std::io::BufReader::new(file)
 }
 
+
+/// Reemplaza el contenido de `filepath` por `content` de forma atomica.
+///
+/// Se escribe en un archivo temporal, se sincroniza y se renombra, para no dejar el archivo a medio escribir si el
+/// proceso se cae. El rename queda en disco recien cuando se sincroniza el directorio.
+pub(crate) fn write_file_synced(filepath: &Path, content: &str) -> std::io::Result<()> {
+    let temporary = filepath.with_extension("tmp");
+
+    let mut out = std::fs::File::create(&temporary)?;
+    std::io::Write::write_all(&mut out, content.as_bytes())?;
+    out.sync_all()?;
+    std::fs::rename(&temporary, filepath)?;
+
+    let dir = match filepath.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(dir)?.sync_all()
+}
@@ -0,0 +1,166 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::consts::AUTH_MSG;
+
+// * SHA-256 y HMAC-SHA256 (FIPS 180-4 y RFC 2104) para firmar los mensajes entre nodos, y el sobre en el que viajan:
+// * "AUTH {destino} {timestamp} {nonce} {mac}\n{mensaje}". El mac cubre tambien el id del nodo destino, para que un
+// * mensaje firmado para un nodo no se pueda reenviar a otro. Lo comparten el nodo y concurride-ctl.
+
+// ? largo minimo de la clave del cluster, para que no se pueda adivinar probando
+pub(crate) const MIN_KEY_LEN: usize = 16;
+const BLOCK_SIZE: usize = 64;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+// ? procesa un bloque de 64 bytes
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut schedule = [0u32; 64];
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..64 {
+        let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+        let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+        schedule[i] = schedule[i - 16].wrapping_add(s0).wrapping_add(schedule[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (k, word) in K.iter().zip(schedule.iter()) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(*k).wrapping_add(*word);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(majority);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    // ? relleno: un bit en 1, ceros hasta 8 bytes antes del fin de bloque y el largo en bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    let mut state = INITIAL_STATE;
+    for block in message.chunks_exact(BLOCK_SIZE) {
+        compress(&mut state, block);
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    // ? una clave mas larga que el bloque se reemplaza por su hash
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// ? compara sin cortar en la primera diferencia, para no revelar por el tiempo cuantos caracteres del mac se acertaron
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn mac(key: &[u8], to: u32, timestamp: u64, nonce: &str, payload: &str) -> String {
+    to_hex(&hmac_sha256(key, format!("{}\n{}\n{}\n{}", to, timestamp, nonce, payload).as_bytes()))
+}
+
+/// Milisegundos desde el epoch segun el reloj del sistema, para el timestamp de los sobres.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Arma el sobre firmado de `payload` para el nodo `to`. El nonce no debe repetirse: el que recibe rechaza los repetidos.
+pub(crate) fn seal(key: &[u8], to: u32, timestamp: u64, nonce: &str, payload: &str) -> String {
+    format!("{} {} {} {} {}\n{}", AUTH_MSG, to, timestamp, nonce, mac(key, to, timestamp, nonce, payload), payload)
+}
+
+/// Sobre de un mensaje firmado, todavia sin verificar.
+pub(crate) struct Envelope<'a> {
+    pub(crate) to: u32,
+    pub(crate) timestamp: u64,
+    pub(crate) nonce: &'a str,
+    mac: &'a str,
+    pub(crate) payload: &'a str,
+}
+
+impl<'a> Envelope<'a> {
+    /// Separa el sobre de `message`, o `None` si no tiene la forma de un mensaje firmado.
+    pub(crate) fn parse(message: &'a str) -> Option<Envelope<'a>> {
+        let (header, payload) = message.split_once('\n')?;
+        let mut fields = header.split(' ');
+        if fields.next() != Some(AUTH_MSG) {
+            return None;
+        }
+
+        let to = fields.next()?.parse().ok()?;
+        let timestamp = fields.next()?.parse().ok()?;
+        let nonce = fields.next().filter(|nonce| !nonce.is_empty())?;
+        let mac = fields.next()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Envelope { to, timestamp, nonce, mac, payload })
+    }
+
+    pub(crate) fn is_authentic(&self, key: &[u8]) -> bool {
+        constant_time_eq(mac(key, self.to, self.timestamp, self.nonce, self.payload).as_bytes(), self.mac.as_bytes())
+    }
+}
+
+/// Lee la clave del cluster de `path`. Se ignoran los espacios y saltos de linea del final.
+///
+/// # Errors
+/// Devuelve el motivo si no se puede leer el archivo o si la clave tiene menos de `MIN_KEY_LEN` bytes.
+pub(crate) fn load_key(path: &Path) -> Result<Vec<u8>, String> {
+    let content = std::fs::read(path).map_err(|e| format!("no se pudo leer {}: {}", path.display(), e))?;
+    let len = content.iter().rposition(|byte| !byte.is_ascii_whitespace()).map_or(0, |last| last + 1);
+    if len < MIN_KEY_LEN {
+        return Err(format!("la clave de {} debe tener al menos {} bytes", path.display(), MIN_KEY_LEN));
+    }
+    Ok(content[..len].to_vec())
+}
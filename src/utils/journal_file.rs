@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::utils::json::{self, JsonValue};
+
+// * Formato del journal de eventos: un objeto JSON por linea. Lo escribe cada nodo y lo lee concurride-ctl para
+// * unir los journals de varios nodos en una sola linea de tiempo.
+
+// ? cantidad de archivos rotados que se conservan: journal.1 es el mas reciente
+pub(crate) const ROTATED_FILES: u32 = 3;
+
+/// Un evento del journal.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct JournalEntry {
+    // ? milisegundos desde 1970, para ordenar eventos de distintos nodos
+    pub(crate) unix_millis: u64,
+    // ? la misma hora en RFC 3339, para leerla
+    pub(crate) ts: String,
+    pub(crate) node: u32,
+    pub(crate) term: u64,
+    pub(crate) event: String,
+    pub(crate) detail: String,
+}
+
+impl JournalEntry {
+    pub(crate) fn to_line(&self) -> String {
+        JsonValue::Object(vec![
+            ("unix_millis".to_string(), JsonValue::Number(self.unix_millis as f64)),
+            ("ts".to_string(), JsonValue::String(self.ts.clone())),
+            ("node".to_string(), JsonValue::Number(self.node as f64)),
+            ("term".to_string(), JsonValue::Number(self.term as f64)),
+            ("event".to_string(), JsonValue::String(self.event.clone())),
+            ("detail".to_string(), JsonValue::String(self.detail.clone())),
+        ]).to_string()
+    }
+
+    pub(crate) fn parse(line: &str) -> Result<JournalEntry, String> {
+        let value = json::parse(line)?;
+        let number = |key: &str| value.get(key).and_then(JsonValue::as_u64).ok_or(format!("falta el campo {}", key));
+        let text = |key: &str| value.get(key).and_then(JsonValue::as_str).map(str::to_string).ok_or(format!("falta el campo {}", key));
+
+        Ok(JournalEntry {
+            unix_millis: number("unix_millis")?,
+            ts: text("ts")?,
+            node: number("node")? as u32,
+            term: number("term")?,
+            event: text("event")?,
+            detail: text("detail")?,
+        })
+    }
+}
+
+/// Ruta del archivo rotado numero `n` del journal `path` (`journal.log.1`, `journal.log.2`, ...).
+pub(crate) fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", n));
+    PathBuf::from(rotated)
+}
+
+/// Lee el journal `path` junto con sus archivos rotados, del evento mas viejo al mas nuevo.
+///
+/// Las lineas que no se pueden interpretar (por ejemplo, una escritura cortada por una caida) se saltean.
+pub(crate) fn read_journal(path: &Path) -> Result<Vec<JournalEntry>, String> {
+    let mut files: Vec<PathBuf> = (1..=ROTATED_FILES).rev().map(|n| rotated_path(path, n)).filter(|file| file.exists()).collect();
+    if !path.exists() && files.is_empty() {
+        return Err(format!("no existe el journal {}", path.display()));
+    }
+    files.push(path.to_path_buf());
+
+    let mut entries = Vec::new();
+    for file in files.iter().filter(|file| file.exists()) {
+        let content = fs::read_to_string(file).map_err(|e| format!("no se pudo leer {}: {}", file.display(), e))?;
+        entries.extend(content.lines().filter_map(|line| JournalEntry::parse(line).ok()));
+    }
+    Ok(entries)
+}
+
+/// Une los journals de varios nodos en una linea de tiempo. A igual hora se respeta el orden de cada journal.
+pub(crate) fn merge(journals: Vec<Vec<JournalEntry>>) -> Vec<JournalEntry> {
+    let mut timeline: Vec<JournalEntry> = journals.into_iter().flatten().collect();
+    timeline.sort_by_key(|entry| entry.unix_millis);
+    timeline
+}
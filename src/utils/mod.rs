@@ -1,3 +1,14 @@
 pub(crate) mod tcp;
 pub(crate) mod arg_handler;
-pub(crate) mod file_handler;
\ No newline at end of file
+pub(crate) mod file_handler;
+pub(crate) mod peer_addr;
+pub(crate) mod json;
+pub(crate) mod peers_file;
+pub(crate) mod random;
+pub(crate) mod faults;
+pub(crate) mod hmac;
+// ? el nodo solo escribe el journal; la lectura la usa concurride-ctl
+#[allow(dead_code)]
+pub(crate) mod journal_file;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+
+/// Host de un nodo: una IP literal o un nombre que hay que resolver por DNS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Host {
+    Ip(IpAddr),
+    Name(String),
+}
+
+impl Host {
+    /// Interpreta un host del archivo de procesos o de los argumentos.
+    ///
+    /// Acepta IPv4 (`127.0.0.1`), IPv6 con o sin corchetes (`[::1]`, `::1`) y nombres de host (`nodo-1.local`).
+    pub(crate) fn parse(host: &str) -> Result<Host, String> {
+        let host = host.trim();
+
+        if let Some(inner) = host.strip_prefix('[') {
+            return match inner.strip_suffix(']').map(str::parse::<IpAddr>) {
+                Some(Ok(ip @ IpAddr::V6(_))) => Ok(Host::Ip(ip)),
+                _ => Err(format!("'{}' no es una dirección IPv6 válida", host)),
+            };
+        }
+
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(Host::Ip(ip));
+        }
+
+        if is_valid_hostname(host) {
+            Ok(Host::Name(host.to_string()))
+        } else {
+            Err(format!("'{}' no es una IP ni un nombre de host válido", host))
+        }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Ip(IpAddr::V6(ip)) => write!(f, "[{}]", ip),
+            Host::Ip(ip) => write!(f, "{}", ip),
+            Host::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+// ? letras, digitos, guiones y puntos, sin etiquetas vacias ni que empiecen o terminen en guion
+fn is_valid_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Direccion de un nodo del cluster.
+///
+/// Si el host es un nombre, la resolucion se cachea hasta que falle una conexion,
+/// momento en que se vuelve a resolver en el proximo intento. Las copias comparten la cache:
+/// si una conexion desde una copia falla, todas vuelven a resolver.
+#[derive(Clone, Debug)]
+pub(crate) struct PeerAddr {
+    pub(crate) host: Host,
+    pub(crate) port: u16,
+    resolved: Arc<Mutex<Vec<SocketAddr>>>,
+}
+
+impl PeerAddr {
+    pub(crate) fn new(host: Host, port: u16) -> PeerAddr {
+        PeerAddr { host, port, resolved: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    /// Devuelve las direcciones a las que conectarse, resolviendo el nombre si no hay una resolucion cacheada.
+    pub(crate) fn socket_addrs(&self) -> Result<Vec<SocketAddr>, String> {
+        let name = match &self.host {
+            Host::Ip(ip) => return Ok(vec![SocketAddr::new(*ip, self.port)]),
+            Host::Name(name) => name,
+        };
+
+        let mut resolved = match self.resolved.lock() {
+            Ok(resolved) => resolved,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if resolved.is_empty() {
+            *resolved = match (name.as_str(), self.port).to_socket_addrs() {
+                Ok(addrs) => addrs.collect(),
+                Err(e) => return Err(format!("Error al resolver {}: {}", name, e)),
+            };
+        }
+
+        if resolved.is_empty() {
+            return Err(format!("El nombre {} no resolvió a ninguna dirección", name));
+        }
+
+        Ok(resolved.clone())
+    }
+
+    /// Descarta la resolucion cacheada para que la proxima conexion vuelva a consultar el DNS.
+    pub(crate) fn forget_resolution(&self) {
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.clear();
+        }
+    }
+}
+
+impl PartialEq for PeerAddr {
+    fn eq(&self, other: &Self) -> bool {
+        self.host == other.host && self.port == other.port
+    }
+}
+
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
@@ -0,0 +1,271 @@
+use std::io::Read;
+use std::path::Path;
+use crate::file_handler;
+use crate::process::Process;
+use crate::utils::json::{self, JsonValue};
+use crate::utils::peer_addr::{Host, PeerAddr};
+
+/// Carga la lista de procesos del archivo indicado. El formato se elige segun la extension:
+///
+/// - `.json`: un array de nodos, o un objeto con un array `nodes`.
+/// - `.toml`: una tabla `[[node]]` por nodo con lineas `clave = valor`.
+/// - cualquier otra: una linea `id;host;port` por nodo.
+///
+/// En JSON y TOML cada nodo tiene `id`, `host` y `port`, y opcionalmente `work_port`,
+/// `priority`, `zone` y `eligible` (si puede ser lider, por defecto `true`).
+///
+/// Si hay entradas invalidas se reportan todas y se termina el proceso.
+pub(crate) fn load_processes_or_kill_process(filepath: &Path) -> Vec<Process> {
+    let extension = filepath.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+    let content = read_file_or_kill_process(filepath);
+    let result = match extension {
+        "json" => parse_json(&content),
+        "toml" => parse_toml(&content),
+        _ => parse_csv(&content),
+    };
+
+    match result {
+        Ok(processes) => processes,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("Error en el archivo de procesos {}: {}.", filepath.display(), error);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_file_or_kill_process(filepath: &Path) -> String {
+    let mut content = String::new();
+    let mut reader = file_handler::get_reader_for_file_or_kill_process(filepath);
+
+    if let Err(e) = reader.read_to_string(&mut content) {
+        eprintln!("Error: No se pudo leer el archivo {}: {}", filepath.display(), e);
+        std::process::exit(1);
+    }
+
+    content
+}
+
+fn collect_errors(results: Vec<Result<Process, String>>) -> Result<Vec<Process>, Vec<String>> {
+    let mut processes = Vec::new();
+    let mut errors = Vec::new();
+
+    for result in results {
+        match result {
+            Ok(process) => processes.push(process),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(processes)
+    } else {
+        Err(errors)
+    }
+}
+
+// * Formato CSV: id;host;port
+
+fn parse_csv_line(line: &str) -> Result<Process, String> {
+    let parts: Vec<&str> = line.split(";").collect();
+
+    if parts.len() != 3 {
+        return Err("Cada línea del archivo de procesos debe tener 3 partes separadas por ';'".to_string());
+    }
+
+    let id: u32 = match parts[0].trim().parse() {
+        Ok(id) => id,
+        Err(_) => return Err("El ID del proceso debe ser un número entero".to_string()),
+    };
+
+    let host = Host::parse(parts[1])?;
+
+    let port: u16 = match parts[2].trim().parse() {
+        Ok(port) => port,
+        Err(_) => return Err("El puerto del proceso debe ser un número entero entre 0 y 65535".to_string()),
+    };
+
+    Ok(Process::new(id, PeerAddr::new(host, port)))
+}
+
+pub(crate) fn parse_csv(content: &str) -> Result<Vec<Process>, Vec<String>> {
+    let results = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_csv_line(line).map_err(|e| format!("línea {} ({}): {}", i + 1, line, e)))
+        .collect();
+
+    collect_errors(results)
+}
+
+// * Campos de un nodo en JSON o TOML
+
+fn get_required<'a>(node: &'a JsonValue, key: &str) -> Result<&'a JsonValue, String> {
+    node.get(key).ok_or_else(|| format!("falta el campo '{}'", key))
+}
+
+fn get_optional_u64(node: &JsonValue, key: &str, max: u64) -> Result<Option<u64>, String> {
+    match node.get(key) {
+        None => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(n) if n <= max => Ok(Some(n)),
+            _ => Err(format!("el campo '{}' debe ser un entero entre 0 y {}", key, max)),
+        },
+    }
+}
+
+fn process_from_node(node: &JsonValue) -> Result<Process, String> {
+    if !matches!(node, JsonValue::Object(_)) {
+        return Err("cada nodo debe ser un objeto".to_string());
+    }
+
+    let id = match get_required(node, "id")?.as_u64() {
+        Some(id) if id <= u32::MAX as u64 => id as u32,
+        _ => return Err("el campo 'id' debe ser un número entero".to_string()),
+    };
+
+    let host = match get_required(node, "host")?.as_str() {
+        Some(host) => Host::parse(host)?,
+        None => return Err("el campo 'host' debe ser un string".to_string()),
+    };
+
+    let port = match get_optional_u64(node, "port", u16::MAX as u64)? {
+        Some(port) => port as u16,
+        None => return Err("falta el campo 'port'".to_string()),
+    };
+
+    let mut process = Process::new(id, PeerAddr::new(host, port));
+    process.work_port = get_optional_u64(node, "work_port", u16::MAX as u64)?.map(|port| port as u16);
+    process.priority = get_optional_u64(node, "priority", u32::MAX as u64)?.unwrap_or(0) as u32;
+
+    process.zone = match node.get("zone") {
+        None => None,
+        Some(zone) => match zone.as_str() {
+            Some(zone) => Some(zone.to_string()),
+            None => return Err("el campo 'zone' debe ser un string".to_string()),
+        },
+    };
+
+    process.eligible = match node.get("eligible") {
+        None => true,
+        Some(eligible) => match eligible.as_bool() {
+            Some(eligible) => eligible,
+            None => return Err("el campo 'eligible' debe ser true o false".to_string()),
+        },
+    };
+
+    Ok(process)
+}
+
+// * Formato JSON
+
+pub(crate) fn parse_json(content: &str) -> Result<Vec<Process>, Vec<String>> {
+    let document = json::parse(content).map_err(|e| vec![e])?;
+
+    let nodes = match document.as_array().or_else(|| document.get("nodes").and_then(JsonValue::as_array)) {
+        Some(nodes) => nodes,
+        None => return Err(vec!["se esperaba un array de nodos o un objeto con el campo 'nodes'".to_string()]),
+    };
+
+    let results = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| process_from_node(node).map_err(|e| format!("nodo #{}: {}", i + 1, e)))
+        .collect();
+
+    collect_errors(results)
+}
+
+// * Formato TOML (subconjunto): tablas [[node]] con claves enteras, strings o booleanas
+
+fn parse_toml_value(value: &str) -> Result<JsonValue, String> {
+    let value = value.trim();
+
+    if let Some(inner) = value.strip_prefix('"') {
+        return match inner.strip_suffix('"') {
+            Some(s) if !s.contains('"') => Ok(JsonValue::String(s.to_string())),
+            _ => Err(format!("string inválido {}", value)),
+        };
+    }
+
+    match value {
+        "true" => return Ok(JsonValue::Bool(true)),
+        "false" => return Ok(JsonValue::Bool(false)),
+        _ => {}
+    }
+
+    match value.replace('_', "").parse::<i64>() {
+        Ok(n) => Ok(JsonValue::Number(n as f64)),
+        Err(_) => Err(format!("valor inválido {}", value)),
+    }
+}
+
+// ? saca los comentarios, ignorando los '#' que esten dentro de un string
+fn strip_toml_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+pub(crate) fn parse_toml(content: &str) -> Result<Vec<Process>, Vec<String>> {
+    // ? (linea donde empieza la tabla, campos)
+    let mut tables: Vec<(usize, Vec<(String, JsonValue)>)> = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_toml_comment(line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == "[[node]]" || line == "[[nodes]]" {
+            tables.push((line_number, Vec::new()));
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some(pair) => pair,
+            None => {
+                errors.push(format!("línea {}: se esperaba '[[node]]' o 'clave = valor'", line_number));
+                continue;
+            }
+        };
+
+        let fields = match tables.last_mut() {
+            Some((_, fields)) => fields,
+            None => {
+                errors.push(format!("línea {}: la clave está fuera de una tabla [[node]]", line_number));
+                continue;
+            }
+        };
+
+        match parse_toml_value(value) {
+            Ok(value) => fields.push((key.trim().to_string(), value)),
+            Err(e) => errors.push(format!("línea {}: {}", line_number, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let results = tables
+        .into_iter()
+        .map(|(line_number, fields)| {
+            process_from_node(&JsonValue::Object(fields)).map_err(|e| format!("nodo de la línea {}: {}", line_number, e))
+        })
+        .collect();
+
+    collect_errors(results)
+}
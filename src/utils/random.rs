@@ -0,0 +1,37 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Generador pseudoaleatorio xorshift64*. No es criptografico, alcanza para timeouts y simulaciones.
+///
+/// Con la misma semilla genera siempre la misma secuencia.
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        // ? el estado nunca puede ser 0 porque xorshift se queda en 0 para siempre
+        Rng { state: seed ^ 0x9E37_79B9_7F4A_7C15 | 1 }
+    }
+
+    // ? semilla distinta en cada ejecucion y en cada proceso
+    pub(crate) fn from_entropy() -> Rng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Rng::new(nanos ^ ((std::process::id() as u64) << 32))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Numero en el rango `[low, high)`. Si el rango es vacio devuelve `low`.
+    pub(crate) fn range(&mut self, low: u64, high: u64) -> u64 {
+        if high <= low {
+            return low;
+        }
+        low + self.next_u64() % (high - low)
+    }
+}
@@ -1,37 +1,136 @@
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::io::{BufRead, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
 use std::process::exit;
+use crate::shutdown::{StopSignal, STOP_POLL_INTERVAL};
+use crate::utils::faults;
+use crate::utils::peer_addr::PeerAddr;
 
-pub(crate) fn get_tcp_listener_or_kill_process(port: u32) -> TcpListener {
-    match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+// ? tamaño maximo del mensaje de un frame, igual al de la respuesta que se lee en una conexion de un solo mensaje
+pub(crate) const MAX_FRAME_SIZE: usize = 16384;
+
+pub(crate) fn get_tcp_listener_or_kill_process(ip: IpAddr, port: u16) -> TcpListener {
+    match TcpListener::bind(SocketAddr::new(ip, port)) {
         Ok(listener) => listener,
         Err(e) => {
-            eprintln!("Error al abrir el puerto {}: {}", port, e);
+            error!("Error al abrir el puerto {} en {}: {}", port, ip, e);
             exit(1)
         }
     }
 }
 
-/// Establish a TCP connection to a server.
+/// Accept the next connection on a listener, giving up when the thread is asked to stop.
+///
+/// The listener must be in non-blocking mode so that `stop` can be checked periodically.
+/// Accepted connections are switched back to blocking mode. Errors accepting a connection
+/// are reported and the listener keeps waiting for the next one.
+///
+/// # Arguments
+/// - `listener`: A non-blocking `TcpListener`.
+/// - `stop`: The stop signal of the thread that owns the listener.
+///
+/// # Returns
+/// Returns `Some(TcpStream)` with the accepted connection, or `None` once `stop` is signaled.
+pub(crate) fn accept_until_stopped(listener: &TcpListener, stop: &StopSignal) -> Option<TcpStream> {
+    while !stop.is_stopped() {
+        match try_accept(listener) {
+            Some(stream) => return Some(stream),
+            None => {
+                stop.wait(STOP_POLL_INTERVAL);
+            }
+        }
+    }
+
+    None
+}
+
+/// Accept a pending connection on a non-blocking listener without waiting.
+///
+/// Accepted connections are switched back to blocking mode. Errors are reported and
+/// treated as if no connection was pending.
+///
+/// # Arguments
+/// - `listener`: A non-blocking `TcpListener`.
+///
+/// # Returns
+/// Returns `Some(TcpStream)` if a connection was pending, or `None` otherwise.
+pub(crate) fn try_accept(listener: &TcpListener) -> Option<TcpStream> {
+    match listener.accept() {
+        Ok((stream, _)) => match stream.set_nonblocking(false) {
+            Ok(_) => Some(stream),
+            Err(e) => {
+                error!("Error al configurar la conexión como bloqueante: {}", e);
+                None
+            }
+        },
+        Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+        Err(e) => {
+            error!("Error al aceptar conexión: {}", e);
+            None
+        }
+    }
+}
+
+/// Establish a TCP connection to another node of the cluster, giving up on each
+/// resolved address after `timeout`.
 ///
-/// This function takes a string `address` representing the server's address and attempts
-/// to establish a TCP connection. It returns a `Result` containing a `TcpStream` if the
-/// connection is successful or an error message as a `String` if an error occurs.
+/// This function tries every address the peer resolves to until one accepts the
+/// connection, so an unreachable host cannot block the caller for longer than
+/// `timeout` per address. If none does, the cached resolution is discarded so that
+/// hostnames are resolved again on the next attempt.
 ///
 /// # Arguments
-/// - `address`: A string representing the server's address (e.g., "127.0.0.1:8080").
+/// - `addr`: The address of the peer, as read from the processes file.
+/// - `timeout`: The maximum time to wait for each address to accept the connection.
 ///
 /// # Returns
 /// Returns a `Result` containing a `TcpStream` if the connection is successful, or a
 /// `String` with an error message if an error occurs.
 ///
 /// # Errors
-/// Returns an error message as a `String` if there is an issue connecting to the server.
-pub fn get_server_connection(address: &str) -> Result<TcpStream, String> {
-    match TcpStream::connect(address) {
-        Ok(stream) => Ok(stream),
-        Err(error) => Err(format!("Error connecting to server: {}", error)),
+/// Returns an error message as a `String` if the address cannot be resolved or if
+/// no resolved address accepts the connection in time.
+pub(crate) fn get_peer_connection_timeout(addr: &PeerAddr, timeout: Duration) -> Result<TcpStream, String> {
+    let mut last_error = format!("Error connecting to server: {} has no addresses", addr);
+
+    for socket_addr in addr.socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = format!("Error connecting to server: {}", error),
+        }
+    }
+
+    addr.forget_resolution();
+    Err(last_error)
+}
+
+/// Connect to another node of the cluster and send it a message.
+///
+/// This is the path every message to another node goes through, so the faults
+/// configured with `--faults` or the `FAULTS` command are applied here: the message
+/// may be dropped, delayed or corrupted before it is written.
+///
+/// # Arguments
+/// - `addr`: The address of the peer, as read from the processes file.
+/// - `message`: A slice of bytes to be sent to the peer.
+/// - `timeout`: The maximum time to wait for the connection, and for each later read
+///   or write on the returned stream.
+///
+/// # Returns
+/// Returns a `Result` containing the `TcpStream` the message was written to, so the
+/// caller can read the answer, or a `String` with an error message if an error occurs.
+///
+/// # Errors
+/// Returns an error message as a `String` if the message is dropped by the fault
+/// injection layer, if the peer cannot be reached in time or if the write fails.
+pub(crate) fn send_to_peer(addr: &PeerAddr, message: &[u8], timeout: Duration) -> Result<TcpStream, String> {
+    let message = faults::apply(addr, message)?;
+    let mut stream = get_peer_connection_timeout(addr, timeout)?;
+    if let Err(error) = stream.set_read_timeout(Some(timeout)).and_then(|_| stream.set_write_timeout(Some(timeout))) {
+        return Err(format!("Error setting timeouts: {}", error));
     }
+    write_bytes_to_stream(&mut stream, &message)?;
+    Ok(stream)
 }
 
 /// Write bytes to a stream.
@@ -51,7 +150,7 @@ pub fn get_server_connection(address: &str) -> Result<TcpStream, String> {
 /// # Errors
 /// Returns an error message as a `String` if there is an issue writing bytes to the stream.
 pub fn write_bytes_to_stream(stream: &mut dyn Write, message: &[u8]) -> Result<(), String> {
-    match stream.write(message) {
+    match stream.write_all(message) {
         Ok(_) => Ok(()),
         Err(error) => Err(format!("Error sending message: {}", error)),
     }
@@ -77,36 +176,120 @@ pub fn write_bytes_to_stream(stream: &mut dyn Write, message: &[u8]) -> Result<(
 pub fn get_response_from_server_as_string(stream: &mut dyn Read) -> Result<String, String> {
     let response_buffer = get_response_from_server_as_u8_buffer(stream)?;
 
-    match String::from_utf8(response_buffer.to_vec()) {
+    match String::from_utf8(response_buffer) {
         Ok(response) => Ok(response),
         Err(error) => Err(format!("Error converting response to string: {}", error)),
     }
 }
 
-/// Read a response from a stream into a fixed-size buffer of u8 bytes.
+/// Read a response from a stream into a buffer of u8 bytes.
 ///
 /// This function takes a mutable reference to a `Read` trait object (`stream`) and
 /// reads the response into a fixed-size buffer of u8 bytes. It returns a `Result`
-/// containing the buffer if the read operation is successful, or an error message
-/// as a `String` if an error occurs.
+/// containing only the bytes that were read if the operation is successful, or an
+/// error message as a `String` if an error occurs.
 ///
 /// # Arguments
 /// - `stream`: A mutable reference to a `Read` trait object, allowing reading bytes.
 ///
 /// # Returns
-/// Returns a `Result` containing a fixed-size buffer of u8 bytes if the read
-/// operation is successful, or a `String` with an error message if an error occurs.
+/// Returns a `Result` containing the bytes read if the read operation is successful,
+/// or a `String` with an error message if an error occurs.
 ///
 /// # Errors
 /// Returns an error message as a `String` if there is an issue reading from the stream.
-pub fn get_response_from_server_as_u8_buffer(stream: &mut dyn Read) -> Result<[u8; 16384], String> {
+pub fn get_response_from_server_as_u8_buffer(stream: &mut dyn Read) -> Result<Vec<u8>, String> {
     let mut response_buffer = [0u8; 16384];
 
-    if stream.read(&mut response_buffer).is_err() {
-        return Err("Error reading response from server.".to_string());
+    match stream.read(&mut response_buffer) {
+        Ok(bytes_read) => Ok(response_buffer[..bytes_read].to_vec()),
+        Err(error) => Err(format!("Error reading response from server: {}", error)),
+    }
+}
+
+/// Write a frame of a persistent session between nodes.
+///
+/// A frame is a header line with the correlation id and the length of the payload,
+/// followed by the payload: `{id} {len}\n{payload}`. Requests and their answers
+/// carry the same id, so several requests can share one connection.
+///
+/// # Arguments
+/// - `stream`: A mutable reference to a `Write` trait object, allowing writing bytes.
+/// - `id`: The correlation id of the request.
+/// - `payload`: The message or answer to send.
+///
+/// # Errors
+/// Returns an error message as a `String` if there is an issue writing to the stream.
+pub(crate) fn write_frame(stream: &mut dyn Write, id: u64, payload: &[u8]) -> Result<(), String> {
+    let mut frame = format!("{} {}\n", id, payload.len()).into_bytes();
+    frame.extend_from_slice(payload);
+    write_bytes_to_stream(stream, &frame)
+}
+
+/// Read the next frame of a persistent session, as written by `write_frame`.
+///
+/// # Arguments
+/// - `reader`: A buffered reader over the session's stream.
+///
+/// # Returns
+/// Returns `Some((id, payload))` with the next frame, or `None` if the other end
+/// closed the connection between frames.
+///
+/// # Errors
+/// Returns an error message as a `String` if the read fails, times out, or the
+/// header is malformed or announces a payload bigger than `MAX_FRAME_SIZE`.
+pub(crate) fn read_frame(reader: &mut dyn BufRead) -> Result<Option<(u64, String)>, String> {
+    let mut header = String::new();
+    match reader.read_line(&mut header) {
+        Ok(0) => return Ok(None),
+        Ok(_) => {}
+        Err(e) => return Err(format!("Error reading frame: {}", e)),
     }
 
-    Ok(response_buffer)
+    let (id, len) = match header.trim_end().split_once(' ').map(|(id, len)| (id.parse::<u64>(), len.parse::<usize>())) {
+        Some((Ok(id), Ok(len))) if len <= MAX_FRAME_SIZE => (id, len),
+        _ => return Err(format!("Invalid frame header: {:?}", header)),
+    };
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).map_err(|e| format!("Error reading frame: {}", e))?;
+    Ok(Some((id, String::from_utf8_lossy(&payload).into_owned())))
+}
+
+/// Take the first complete frame out of a buffer of bytes read from a session.
+///
+/// This is the non-blocking counterpart of `read_frame`: the bytes come from a
+/// socket that may have delivered only part of a frame so far.
+///
+/// # Arguments
+/// - `buffer`: The bytes received from the session that were not consumed yet.
+///
+/// # Returns
+/// Returns `Some((id, payload, consumed))` with the first frame and how many bytes of
+/// `buffer` it took, or `None` if the buffer does not hold a complete frame yet.
+///
+/// # Errors
+/// Returns an error message as a `String` if the header is malformed or announces a
+/// payload bigger than `MAX_FRAME_SIZE`.
+pub(crate) fn split_frame(buffer: &[u8]) -> Result<Option<(u64, String, usize)>, String> {
+    let header_end = match buffer.iter().position(|byte| *byte == b'\n') {
+        Some(end) => end,
+        None if buffer.len() > MAX_FRAME_SIZE => return Err("Invalid frame header: too long".to_string()),
+        None => return Ok(None),
+    };
+
+    let header = String::from_utf8_lossy(&buffer[..header_end]);
+    let (id, len) = match header.split_once(' ').map(|(id, len)| (id.parse::<u64>(), len.parse::<usize>())) {
+        Some((Ok(id), Ok(len))) if len <= MAX_FRAME_SIZE => (id, len),
+        _ => return Err(format!("Invalid frame header: {:?}", header)),
+    };
+
+    let payload_start = header_end + 1;
+    if buffer.len() < payload_start + len {
+        return Ok(None);
+    }
+    let payload = String::from_utf8_lossy(&buffer[payload_start..payload_start + len]).into_owned();
+    Ok(Some((id, payload, payload_start + len)))
 }
 
 pub fn get_peer_addr(stream: &TcpStream) -> Result<String, String> {
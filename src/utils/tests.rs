@@ -0,0 +1,198 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use crate::utils::hmac::{self, hmac_sha256, sha256, Envelope};
+use crate::utils::peer_addr::{Host, PeerAddr};
+use crate::utils::json::{self, JsonValue};
+use crate::utils::peers_file::{parse_csv, parse_json, parse_toml};
+
+// * Pruebas de las utilidades que no necesitan red: direcciones de los nodos, JSON, archivos de procesos y firmas.
+
+#[test]
+fn hosts_accept_ipv4_bracketed_ipv6_and_names() {
+    assert_eq!(Host::parse(" 127.0.0.1 "), Ok(Host::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST))));
+    assert_eq!(Host::parse("[::1]"), Ok(Host::Ip(IpAddr::V6(Ipv6Addr::LOCALHOST))));
+    assert_eq!(Host::parse("::1"), Ok(Host::Ip(IpAddr::V6(Ipv6Addr::LOCALHOST))));
+    assert_eq!(Host::parse("nodo-1.local"), Ok(Host::Name("nodo-1.local".to_string())));
+}
+
+#[test]
+fn invalid_hosts_are_rejected() {
+    for host in ["[127.0.0.1]", "[::1", "[]", "", "-nodo", "nodo..local", "nodo_1", "127.0.0.1:8080"] {
+        assert!(Host::parse(host).is_err(), "{} deberia ser inválido", host);
+    }
+}
+
+#[test]
+fn addresses_are_shown_as_host_and_port() {
+    assert_eq!(PeerAddr::new(Host::parse("127.0.0.1").unwrap(), 8081).to_string(), "127.0.0.1:8081");
+    assert_eq!(PeerAddr::new(Host::parse("::1").unwrap(), 8081).to_string(), "[::1]:8081");
+    assert_eq!(PeerAddr::new(Host::parse("nodo-1").unwrap(), 80).to_string(), "nodo-1:80");
+
+    let addr = PeerAddr::new(Host::parse("[::1]").unwrap(), 8081);
+    assert_eq!(addr.socket_addrs(), Ok(vec!["[::1]:8081".parse::<SocketAddr>().unwrap()]));
+}
+
+#[test]
+fn peer_lines_reject_invalid_ports() {
+    let processes = parse_csv("1;[::1];8081\n").unwrap();
+    assert_eq!(processes[0].addr.to_string(), "[::1]:8081");
+
+    for line in ["1;127.0.0.1;65536", "1;127.0.0.1;-1", "1;127.0.0.1;http", "1;127.0.0.1;", "1;127.0.0.1:8081"] {
+        assert!(parse_csv(line).is_err(), "{} deberia ser inválida", line);
+    }
+}
+
+#[test]
+fn json_strings_round_trip_with_escapes() {
+    let text = "comillas \" barra \\ salto\n tab\t control\u{1} ñ";
+    let value = JsonValue::String(text.to_string());
+    assert_eq!(value.to_string(), r#""comillas \" barra \\ salto\n tab\t control\u0001 ñ""#);
+    assert_eq!(json::parse(&value.to_string()), Ok(value));
+
+    assert_eq!(json::parse(r#""\/\b\f\u00f1""#), Ok(JsonValue::String("/\u{8}\u{c}ñ".to_string())));
+    assert!(json::parse(r#""\x""#).is_err());
+    assert!(json::parse(r#""\u00g1""#).is_err());
+    assert!(json::parse(r#""\u+0f1""#).is_err());
+}
+
+#[test]
+fn json_surrogate_pairs_are_joined() {
+    assert_eq!(json::parse(r#""\ud83d\ude80""#), Ok(JsonValue::String("🚀".to_string())));
+    assert_eq!(json::parse(r#""\uD834\uDD1E fin""#), Ok(JsonValue::String("𝄞 fin".to_string())));
+
+    for lone in [r#""\ud83d""#, r#""\ud83d x""#, r#""\ud83d\u0041""#, r#""\ude80""#] {
+        assert!(json::parse(lone).is_err(), "{} deberia ser inválido", lone);
+    }
+}
+
+#[test]
+fn json_numbers_are_always_finite() {
+    assert_eq!(json::parse("[1, -2.5, 3e2]"), Ok(JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Number(-2.5), JsonValue::Number(300.0)])));
+    assert!(json::parse("1e999").is_err());
+    assert!(json::parse("-1e999").is_err());
+
+    let values = JsonValue::Array(vec![JsonValue::Number(f64::INFINITY), JsonValue::Number(f64::NAN), JsonValue::Number(0.5)]);
+    assert_eq!(values.to_string(), "[null,null,0.5]");
+    assert!(json::parse(&values.to_string()).is_ok());
+}
+
+#[test]
+fn malformed_json_reports_the_line() {
+    assert_eq!(json::parse("{\n  \"id\": 1,\n  \"host\" \"x\"\n}"), Err("línea 3: se esperaba ':'".to_string()));
+    assert!(json::parse("[1, 2").is_err());
+    assert!(json::parse("{\"id\": 1} extra").is_err());
+    assert!(json::parse("\"sin cerrar").is_err());
+    assert!(json::parse("").is_err());
+}
+
+#[test]
+fn peer_files_in_json_and_toml_read_every_field() {
+    let from_json = parse_json(r#"{"nodes": [
+        {"id": 1, "host": "127.0.0.1", "port": 8081, "work_port": 5051, "priority": 10, "zone": "zona-a"},
+        {"id": 2, "host": "nodo-2", "port": 8082, "eligible": false}
+    ]}"#).unwrap();
+    let from_toml = parse_toml(r#"
+        [[node]]
+        id = 1
+        host = "127.0.0.1" # local
+        port = 8_081
+        work_port = 5051
+        priority = 10
+        zone = "zona-a"
+
+        [[node]]
+        id = 2
+        host = "nodo-2"
+        port = 8082
+        eligible = false
+    "#).unwrap();
+
+    for processes in [from_json, from_toml] {
+        assert_eq!(processes.len(), 2);
+        assert_eq!((processes[0].id, processes[0].addr.to_string(), processes[0].work_port), (1, "127.0.0.1:8081".to_string(), Some(5051)));
+        assert_eq!((processes[0].priority, processes[0].zone.as_deref(), processes[0].eligible), (10, Some("zona-a"), true));
+        assert_eq!((processes[1].addr.to_string(), processes[1].work_port, processes[1].eligible), ("nodo-2:8082".to_string(), None, false));
+    }
+}
+
+#[test]
+fn malformed_peer_files_report_every_error() {
+    let errors = parse_json(r#"[{"id": 1, "host": "127.0.0.1"}, {"id": -1, "host": "x", "port": 1}, {"id": 3, "host": "x", "port": 70000}, 4]"#).err().unwrap_or_default();
+    assert_eq!(errors, vec![
+        "nodo #1: falta el campo 'port'".to_string(),
+        "nodo #2: el campo 'id' debe ser un número entero".to_string(),
+        "nodo #3: el campo 'port' debe ser un entero entre 0 y 65535".to_string(),
+        "nodo #4: cada nodo debe ser un objeto".to_string(),
+    ]);
+    assert!(parse_json(r#"{"nodos": []}"#).is_err());
+    assert!(parse_json("[{").is_err());
+
+    let errors = parse_toml("id = 1\n[[node]]\nhost = \"sin cerrar\nport\n[[node]]\nid = 2\n").err().unwrap_or_default();
+    assert_eq!(errors, vec![
+        "línea 1: la clave está fuera de una tabla [[node]]".to_string(),
+        "línea 3: string inválido \"sin cerrar".to_string(),
+        "línea 4: se esperaba '[[node]]' o 'clave = valor'".to_string(),
+    ]);
+
+    let errors = parse_csv("1;127.0.0.1;8081\n\ndos;127.0.0.1;8082\n3;127.0.0.1\n").err().unwrap_or_default();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("línea 3 (dos;127.0.0.1;8082)"));
+    assert!(errors[1].starts_with("línea 4 (3;127.0.0.1)"));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// ? ejemplos de FIPS 180-4 (los de un bloque, dos bloques y un millon de "a")
+#[test]
+fn sha256_matches_the_fips_180_4_examples() {
+    let cases: [(&[u8], &str); 4] = [
+        (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        (b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq", "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+        (
+            b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+        ),
+    ];
+    for (message, digest) in cases {
+        assert_eq!(hex(&sha256(message)), digest, "sha256({:?})", String::from_utf8_lossy(message));
+    }
+    assert_eq!(hex(&sha256(&vec![b'a'; 1_000_000])), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+}
+
+// ? casos 1 a 4, 6 y 7 de RFC 4231; el 5 trunca el resultado y no aplica
+#[test]
+fn hmac_sha256_matches_the_rfc_4231_test_cases() {
+    let long_key = vec![0xaa; 131];
+    let cases: [(&[u8], &[u8], &str); 6] = [
+        (&[0x0b; 20], b"Hi There", "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+        (b"Jefe", b"what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+        (&[0xaa; 20], &[0xdd; 50], "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+        (&(1..=25).collect::<Vec<u8>>(), &[0xcd; 50], "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"),
+        (&long_key, b"Test Using Larger Than Block-Size Key - Hash Key First", "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+        (
+            &long_key,
+            b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.",
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+        ),
+    ];
+    for (index, (key, data, mac)) in cases.iter().enumerate() {
+        assert_eq!(hex(&hmac_sha256(key, data)), *mac, "caso {} de la lista", index + 1);
+    }
+}
+
+#[test]
+fn envelopes_only_authenticate_for_their_destination() {
+    let key = b"clave-del-cluster-de-prueba";
+    let sealed = hmac::seal(key, 2, 1_700_000_000_000, "nonce", "NEW LEADER 2 1");
+
+    let envelope = Envelope::parse(&sealed).unwrap();
+    assert_eq!((envelope.to, envelope.payload), (2, "NEW LEADER 2 1"));
+    assert!(envelope.is_authentic(key));
+    assert!(!envelope.is_authentic(b"otra-clave-del-cluster"));
+
+    // ? cambiar el destino del sobre invalida la firma
+    let redirected = sealed.replacen("AUTH 2 ", "AUTH 3 ", 1);
+    assert!(!Envelope::parse(&redirected).unwrap().is_authentic(key));
+}
@@ -1,37 +1,310 @@
-use std::sync::{Arc, RwLock};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::consts::{GET_TRIP_MSG, LAST_TRIP_MSG, LEADER_CHECK_MSG, NOT_LEADER_ANSWER, PULL_TRIPS_MSG, REPLICATE_TRIP_MSG, SYNC_TRIPS_MSG, TAKE_OVER_MSG, TRIP_MSG, UNAUTHORIZED_ANSWER, FORBIDDEN_ANSWER};
+use crate::{allowlist, auth};
+use crate::election::{send_and_wait_answer, Term};
+use crate::trips::{apply_replicated_trip, catch_up, check_leader, collect_trips, create_trip, fetch_leader_trips, get_pulled_trips_page, get_trip, get_trips_page, Leadership, Trips};
+use crate::listener::CONNECTION_TIMEOUT;
+use crate::transport::{TcpTransport, Transport};
 use crate::process::Process;
+use crate::shutdown::{StopSignal, STOP_POLL_INTERVAL};
+use crate::supervisor::spawn_supervised;
+use crate::utils::peer_addr::PeerAddr;
+use crate::utils::tcp::{get_peer_addr, get_tcp_listener_or_kill_process, write_bytes_to_stream};
+use crate::worker_pool::{serve_connections, WORKERS};
 
-pub(crate) fn start_work_thread(processes: Arc<RwLock<Vec<Process>>>) -> std::thread::JoinHandle<()> {
-    std::thread::spawn(move || {
-        println!("Iniciando hilo de trabajo...");
-        start_work(processes);
+// ? tiempo que se espera a que el nodo elegido se anuncie como lider al traspasarle el liderazgo
+const TAKE_OVER_TIMEOUT: Duration = Duration::from_secs(5);
+// ? con clave de cluster estos mensajes solo se atienden firmados
+const PEER_MESSAGES: [&str; 5] = [REPLICATE_TRIP_MSG, LEADER_CHECK_MSG, LAST_TRIP_MSG, SYNC_TRIPS_MSG, PULL_TRIPS_MSG];
+
+/// Pedidos de otros hilos al hilo de trabajo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum WorkCommand {
+    // ? resincronizar los trips con el lider indicado (por ejemplo, al resolver un split brain)
+    Resync(u32),
+    // ? traspasar el liderazgo al nodo indicado
+    TransferLeadership(u32),
+}
+
+pub(crate) fn start_work_thread(processes: Arc<RwLock<Vec<Process>>>, term: Arc<Term>, trips: Trips, bind_ip: IpAddr, commands_rx: Receiver<WorkCommand>, stop: StopSignal) -> JoinHandle<()> {
+    let trips = Arc::new(Mutex::new(trips));
+    /*
+        mensaje -> listener(work_port) -> soyLider? -> si -> intento procesarlo como un trip
+        mensaje -> listener(work_port) -> soyLider? -> no -> intento procesarlo como una actualizacion de estado
+    */
+    let mut connections = match get_my_work_port(&processes) {
+        Some(work_port) => Some(serve_work_port(bind_ip, work_port, &processes, &trips, &term, &stop)),
+        None => {
+            info!("no hay puerto de trabajo configurado, el nodo no atiende trips");
+            None
+        }
+    };
+
+    spawn_supervised("work", stop.clone(), move || {
+        info!("Iniciando hilo de trabajo...");
+        let transport = TcpTransport;
+        while !stop.is_stopped() {
+            match commands_rx.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(command) => handle_command(command, &processes, &trips, &term, &transport),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    stop.wait(STOP_POLL_INTERVAL);
+                }
+            }
+        }
+
+        if let Some(connections) = connections.take() {
+            let _ = connections.join();
+        }
+        Ok(())
     })
 }
 
-fn am_i_leader(processes: &Arc<RwLock<Vec<Process>>>) -> bool {
+// ? los trips los comparten los workers del puerto de trabajo y los comandos: se atiende un pedido a la vez
+fn lock_trips(trips: &Mutex<Trips>) -> MutexGuard<'_, Trips> {
+    match trips.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// ? term en el que este nodo se anuncio como lider, o None si no lo es. Si ya conoce un term mayor otro nodo pudo ganar
+// ? una eleccion despues: hasta pasar a un term nuevo no atiende trips.
+fn my_leadership(processes: &Arc<RwLock<Vec<Process>>>, term: &Term) -> Option<Leadership> {
     let processes_guard = match processes.read() {
         Ok(guard) => guard,
         Err(e) => {
-            eprintln!("Error al obtener el guard de procesos: {}", e);
-            return false;
+            error!("Error al obtener el guard de procesos: {}", e);
+            return None;
         }
     };
 
-    for process in processes_guard.iter() {
-        if process.leader && process.me {
-            return true;
+    // ? puede ser de un term que ya paso: `collect_trips` no lo deja atender y el nodo renuncia
+    let me = processes_guard.iter().find(|process| process.leader && process.me)?;
+    Some(Leadership { term: me.leader_term.unwrap_or_else(|| term.current()), leader: me.id })
+}
+
+fn get_my_work_port(processes: &Arc<RwLock<Vec<Process>>>) -> Option<u16> {
+    match processes.read() {
+        Ok(guard) => guard.iter().find(|process| process.me).and_then(|me| me.work_port),
+        Err(e) => {
+            error!("Error al obtener el guard de procesos: {}", e);
+            None
         }
     }
+}
 
-    false
+fn serve_work_port(bind_ip: IpAddr, work_port: u16, processes: &Arc<RwLock<Vec<Process>>>, trips: &Arc<Mutex<Trips>>, term: &Arc<Term>, stop: &StopSignal) -> JoinHandle<()> {
+    let listener = get_tcp_listener_or_kill_process(bind_ip, work_port);
+    info!("escuchando trips en {} con {} workers", SocketAddr::new(bind_ip, work_port), WORKERS);
+
+    let processes = Arc::clone(processes);
+    let trips = Arc::clone(trips);
+    let term = Arc::clone(term);
+    serve_connections("work listener", "work worker", listener, stop.clone(), move |stream| {
+        handle_work_connection(stream, &processes, &trips, &term, &TcpTransport)
+    })
 }
 
-fn start_work(processes: Arc<RwLock<Vec<Process>>>) {
-    /*
-        TODO iniciar estructuras
-        TODO Abrir listener. Por cada mensaje del listener, preguntar si soy lider o no y manejar mensaje de una manera u otra.
+fn handle_work_connection(mut stream: TcpStream, processes: &Arc<RwLock<Vec<Process>>>, trips: &Mutex<Trips>, term: &Term, transport: &dyn Transport) {
+    // ? un cliente que se conecta y no envia nada (o no lee la respuesta) solo ocupa a este worker hasta el timeout
+    if let Err(e) = stream.set_read_timeout(Some(CONNECTION_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(CONNECTION_TIMEOUT))) {
+        error!("Error al configurar timeout en la conexión: {}", e);
+        return;
+    }
 
-        mensaje -> listener(5050) -> soyLider? -> si -> intento procesarlo como un trip
-        mensaje -> listener(5050) -> soyLider? -> no -> intento procesarlo como una actualizacion de estado
-    */
-}
\ No newline at end of file
+    let mut buffer = [0; 1024];
+    let bytes_read = match stream.read(&mut buffer) {
+        Ok(bytes_read) => bytes_read,
+        Err(e) => {
+            let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+            error!("Error al leer mensaje de {}: {}", peer, e);
+            return;
+        }
+    };
+    let message = String::from_utf8_lossy(&buffer[..bytes_read]);
+    let message = match authenticate(&message) {
+        Ok(message) => message,
+        Err(e) => {
+            let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+            warn!("Mensaje rechazado de {}: {}", peer, e);
+            let _ = write_bytes_to_stream(&mut stream, UNAUTHORIZED_ANSWER.as_bytes());
+            return;
+        }
+    };
+    // ? desde fuera de la lista de procesos solo se atienden los mensajes de cliente, como crear un trip
+    if !allowlist::is_client_message(&message) && !is_listed_peer(&stream, processes) {
+        allowlist::record_refused(&stream, "el mensaje");
+        let _ = write_bytes_to_stream(&mut stream, FORBIDDEN_ANSWER.as_bytes());
+        return;
+    }
+    // ? el lock se suelta antes de responder: un cliente que no lee la respuesta no frena al resto
+    let answer = handle_work_message(&message, processes, &mut lock_trips(trips), term, transport);
+
+    if let Err(e) = write_bytes_to_stream(&mut stream, answer.as_bytes()) {
+        error!("Error al enviar respuesta: {}", e);
+    }
+}
+
+fn is_listed_peer(stream: &TcpStream, processes: &Arc<RwLock<Vec<Process>>>) -> bool {
+    match processes.read() {
+        Ok(guard) => allowlist::is_listed(stream, &guard),
+        Err(poisoned) => allowlist::is_listed(stream, &poisoned.into_inner()),
+    }
+}
+
+// ? lo que se mandan los nodos entre si por el puerto de trabajo, nunca un cliente
+fn is_peer_message(message: &str) -> bool {
+    PEER_MESSAGES.iter().any(|peer_message| message.starts_with(peer_message))
+}
+
+// ? los clientes no tienen la clave del cluster: sin firma pueden crear y consultar trips. Los mensajes entre nodos
+// ? siempre pasan por `auth::open`, que con clave exige la firma aunque se hayan configurado como mensajes de cliente.
+fn authenticate(message: &str) -> Result<String, String> {
+    if allowlist::is_client_message(message) && !is_peer_message(message) && !auth::is_sealed(message) {
+        return Ok(message.to_string());
+    }
+    auth::open(message)
+}
+
+/// Atiende un mensaje recibido por el puerto de trabajo y devuelve la respuesta.
+pub(crate) fn handle_work_message(message: &str, processes: &Arc<RwLock<Vec<Process>>>, trips: &mut Trips, term: &Term, transport: &dyn Transport) -> String {
+    // ? lo que el lider le pide al resto
+    if message.starts_with(REPLICATE_TRIP_MSG) {
+        return apply_replicated_trip(message, trips, term);
+    }
+    if message.starts_with(LEADER_CHECK_MSG) {
+        return check_leader(message, term);
+    }
+    if message.starts_with(LAST_TRIP_MSG) {
+        return format!("{} {}", LAST_TRIP_MSG, trips.last_id());
+    }
+    if message.starts_with(PULL_TRIPS_MSG) {
+        return get_pulled_trips_page(message, trips, term);
+    }
+
+    // ? los trips y sus consultas solo los atiende el lider
+    let leadership = match my_leadership(processes, term) {
+        Some(leadership) => leadership,
+        None => return format!("{} {}", NOT_LEADER_ANSWER, get_leader_id(processes).map_or("?".to_string(), |id| id.to_string())),
+    };
+    // ? el lider anterior pudo confirmar trips que este nodo no recibio: hasta juntarlos no se atiende como lider
+    if !collect_trips(processes, trips, leadership, term, transport) {
+        return format!("{} ?", NOT_LEADER_ANSWER);
+    }
+    if let Some(after) = message.strip_prefix(SYNC_TRIPS_MSG) {
+        get_trips_page(after.trim(), trips)
+    } else if let Some(id) = message.strip_prefix(GET_TRIP_MSG) {
+        get_trip(id.trim(), processes, trips, leadership, transport)
+    } else if let Some(description) = message.strip_prefix(TRIP_MSG) {
+        create_trip(description.trim(), processes, trips, leadership, transport)
+    } else {
+        warn!("Mensaje desconocido: {}", message);
+        "error".to_string()
+    }
+}
+
+// ? lider conocido, salvo que sea este nodo: un lider viejo no sabe quien lo reemplazo
+fn get_leader_id(processes: &Arc<RwLock<Vec<Process>>>) -> Option<u32> {
+    match processes.read() {
+        Ok(guard) => guard.iter().find(|process| process.leader && !process.me).map(|leader| leader.id),
+        Err(_) => None,
+    }
+}
+
+// ? lo necesario para hablarle al hilo de trabajo del proceso, copiado para no retener el lock durante la conexion
+fn get_worker(processes: &Arc<RwLock<Vec<Process>>>, id: u32) -> Option<Process> {
+    let guard = processes.read().ok()?;
+    let process = guard.iter().find(|process| process.id == id && process.work_port.is_some())?;
+
+    let mut worker = Process::new(process.id, PeerAddr::new(process.addr.host.clone(), process.addr.port));
+    worker.work_port = process.work_port;
+    Some(worker)
+}
+
+// ? los trips se bloquean solo para leerlos o cambiarlos: mientras se espera a otro nodo el puerto de trabajo sigue atendiendo
+pub(crate) fn handle_command(command: WorkCommand, processes: &Arc<RwLock<Vec<Process>>>, trips: &Mutex<Trips>, term: &Term, transport: &dyn Transport) {
+    match command {
+        WorkCommand::Resync(leader) => match get_worker(processes, leader) {
+            Some(worker) => resync_trips(&worker, trips, transport),
+            None => warn!("{} no tiene puerto de trabajo, no se pueden resincronizar los trips", leader),
+        },
+        WorkCommand::TransferLeadership(target) => transfer_leadership(target, processes, trips, term, transport),
+    }
+}
+
+/// Trae los trips del hilo de trabajo de `leader` y se queda con los suyos cuando son de un term mayor a los locales.
+///
+/// Los que solo conocia este nodo se conservan: no llegaron a confirmarse y el proximo lider decide si los junta.
+fn resync_trips(leader: &Process, trips: &Mutex<Trips>, transport: &dyn Transport) {
+    let fetched = match fetch_leader_trips(leader, transport) {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            warn!("Error resincronizando trips con {}: {}", leader.id, e);
+            return;
+        }
+    };
+
+    let count = fetched.len();
+    match lock_trips(trips).merge(fetched) {
+        Ok(changed) => info!("{} trips resincronizados con {}, {} actualizados", count, leader.id, changed),
+        Err(e) => error!("Error resincronizando trips con {}: {}", leader.id, e),
+    }
+}
+
+/// Traspasa el liderazgo a `target`: lo pone al dia con los trips y le pide que se anuncie como lider.
+///
+/// Se le envia una copia de los trips: el puerto de trabajo sigue atendiendo durante el traspaso, y los trips creados
+/// despues de la copia el nuevo lider los junta de la mayoria antes de atender. Si algo falla, este nodo sigue siendo el lider.
+fn transfer_leadership(target: u32, processes: &Arc<RwLock<Vec<Process>>>, trips: &Mutex<Trips>, term: &Term, transport: &dyn Transport) {
+    let leadership = match my_leadership(processes, term) {
+        Some(leadership) if leadership.term >= term.current() => leadership,
+        _ => {
+            warn!("no soy lider, no puedo traspasar el liderazgo");
+            return;
+        }
+    };
+    info!("traspasando el liderazgo a {}...", target);
+
+    let trips = lock_trips(trips).snapshot();
+    if !trips.is_empty() {
+        let result = match get_worker(processes, target) {
+            Some(worker) => catch_up(transport, &worker, &trips, leadership),
+            None => Err("no tiene puerto de trabajo".to_string()),
+        };
+        if let Err(e) = result {
+            warn!("no se pudo poner al dia a {}, sigo siendo lider: {}", target, e);
+            return;
+        }
+        info!("{} está al día con {} trips", target, trips.len());
+    }
+
+    let target_process = match processes.read() {
+        Ok(guard) => guard.iter().find(|process| process.id == target).cloned(),
+        Err(_) => None,
+    };
+    let answer = match target_process {
+        Some(process) => send_and_wait_answer(transport, &process, TAKE_OVER_MSG, TAKE_OVER_TIMEOUT),
+        None => Err(format!("{} no está en la lista de procesos", target)),
+    };
+    if let Err(e) = answer {
+        warn!("{} no aceptó el liderazgo, sigo siendo lider: {}", target, e);
+        return;
+    }
+
+    // ? el nuevo lider se anuncia con NEW LEADER como en cualquier eleccion
+    let started = Instant::now();
+    while started.elapsed() < TAKE_OVER_TIMEOUT {
+        if get_leader_id(processes) == Some(target) {
+            info!("{} es el nuevo lider", target);
+            return;
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+    warn!("{} no se anunció como lider a tiempo", target);
+}
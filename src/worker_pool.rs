@@ -0,0 +1,87 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use crate::consts::BUSY_ANSWER;
+use crate::metrics::CONNECTIONS_SHED;
+use crate::shutdown::{StopSignal, STOP_POLL_INTERVAL};
+use crate::supervisor::spawn_supervised;
+use crate::utils::tcp::{accept_until_stopped, get_peer_addr, write_bytes_to_stream};
+
+// * Pool acotado de workers para atender las conexiones de un puerto: un hilo acepta y encola las conexiones, y los
+// * workers las atienden en paralelo. Con todos ocupados y la cola llena, las nuevas se rechazan con BUSY.
+
+// ? hilos que atienden conexiones en paralelo, y conexiones aceptadas que pueden esperar a uno libre
+pub(crate) const WORKERS: usize = 4;
+pub(crate) const MAX_PENDING_CONNECTIONS: usize = 16;
+
+/// Acepta conexiones de `listener` hasta que se detenga `stop` y las atiende con `handle` desde `WORKERS` hilos.
+///
+/// `handle` debe poner sus propios timeouts: un worker queda ocupado mientras dure la conexion. Al detenerse se
+/// terminan de atender las conexiones encoladas y se espera a los workers.
+pub(crate) fn serve_connections<F>(name: &'static str, worker_name: &'static str, listener: TcpListener, stop: StopSignal, handle: F) -> JoinHandle<()>
+where
+    F: Fn(TcpStream) + Send + Sync + 'static,
+{
+    // ? el accept no bloquea para poder revisar periodicamente si hay que dejar de escuchar
+    if let Err(e) = listener.set_nonblocking(true) {
+        error!("Error al configurar el socket como no bloqueante: {}", e);
+        std::process::exit(1);
+    }
+
+    let handle = Arc::new(handle);
+    spawn_supervised(name, stop.clone(), move || {
+        let (connections_tx, connections_rx) = sync_channel(MAX_PENDING_CONNECTIONS);
+        let connections_rx = Arc::new(Mutex::new(connections_rx));
+        let workers: Vec<JoinHandle<()>> = (0..WORKERS)
+            .map(|_| start_worker(worker_name, Arc::clone(&connections_rx), Arc::clone(&handle), stop.clone()))
+            .collect();
+
+        while let Some(stream) = accept_until_stopped(&listener, &stop) {
+            match connections_tx.try_send(stream) {
+                Ok(_) => {}
+                Err(TrySendError::Full(stream)) => shed_connection(stream),
+                Err(TrySendError::Disconnected(_)) => break,
+            }
+        }
+
+        // ? sin el tx los workers terminan al vaciar la cola
+        drop(connections_tx);
+        for worker in workers {
+            let _ = worker.join();
+        }
+        Ok(())
+    })
+}
+
+// ? atiende conexiones de la cola hasta que se cierre
+fn start_worker<F>(name: &'static str, connections: Arc<Mutex<Receiver<TcpStream>>>, handle: Arc<F>, stop: StopSignal) -> JoinHandle<()>
+where
+    F: Fn(TcpStream) + Send + Sync + 'static,
+{
+    spawn_supervised(name, stop, move || loop {
+        // ? el lock se suelta antes de atender la conexion, para que otro worker pueda tomar la siguiente
+        let next = match connections.lock() {
+            Ok(rx) => rx.recv_timeout(STOP_POLL_INTERVAL),
+            Err(poisoned) => poisoned.into_inner().recv_timeout(STOP_POLL_INTERVAL),
+        };
+
+        match next {
+            Ok(stream) => handle(stream),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    })
+}
+
+// ? con todos los workers ocupados y la cola llena, se rechaza la conexion en vez de bloquear al resto
+fn shed_connection(mut stream: TcpStream) {
+    CONNECTIONS_SHED.increment();
+    let peer = get_peer_addr(&stream).unwrap_or_else(|e| e);
+    warn!("Nodo sobrecargado, rechazando la conexión de {}", peer);
+
+    // ? la respuesta es de cortesia: si no se puede escribir enseguida, se descarta
+    if stream.set_nonblocking(true).is_ok() {
+        let _ = write_bytes_to_stream(&mut stream, BUSY_ANSWER.as_bytes());
+    }
+}